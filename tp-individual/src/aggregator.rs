@@ -0,0 +1,150 @@
+use std::{any::Any, sync::Arc};
+
+use crate::{deaths::Death, fields::Field};
+
+/// A metric computed over the deaths.
+///
+/// Each thread folds its share of the deaths into its own instance, and the partial results are then merged.
+/// Because of that, `merge` must give the same result as folding all the deaths of `other` into `self`.
+pub trait Aggregator: Send {
+    /// Adds a death to the metric.
+    fn fold(&mut self, death: &Death);
+
+    /// Merges the partial result of another instance into this one.
+    fn merge(&mut self, other: Self)
+    where
+        Self: Sized;
+
+    /// Returns the metric in a JSON format.
+    fn render(&self) -> serde_json::Value;
+}
+
+/// Object safe version of `Aggregator`, so aggregators of different types can be stored together.
+/// It is implemented for every `Aggregator`.
+pub trait DynAggregator: Send {
+    /// Adds a death to the metric.
+    fn fold_death(&mut self, death: &Death);
+
+    /// Merges another instance into this one.
+    /// Both instances must have been created by the same factory, otherwise `other` is ignored.
+    fn merge_boxed(&mut self, other: Box<dyn DynAggregator>);
+
+    /// Returns the metric in a JSON format.
+    fn render_json(&self) -> serde_json::Value;
+
+    /// Converts the aggregator into `Any`, to recover its concrete type when merging.
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<A: Aggregator + 'static> DynAggregator for A {
+    fn fold_death(&mut self, death: &Death) {
+        self.fold(death);
+    }
+
+    fn merge_boxed(&mut self, other: Box<dyn DynAggregator>) {
+        if let Ok(other) = other.into_any().downcast::<A>() {
+            self.merge(*other);
+        }
+    }
+
+    fn render_json(&self) -> serde_json::Value {
+        self.render()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+type AggregatorFactory = Arc<dyn Fn() -> Box<dyn DynAggregator> + Send + Sync>;
+
+/// Custom metrics to compute alongside the players and weapons stats, in the same parallel pass.
+/// Each metric is registered with a name, used as its key in the JSON output, and a factory that creates empty instances.
+/// The deaths can also be grouped by a combination of fields, see [`GroupByAggregator`](crate::group_by::GroupByAggregator).
+#[derive(Clone, Default)]
+pub struct CustomAggregators {
+    factories: Vec<(String, AggregatorFactory)>,
+    group_by: Vec<Field>,
+}
+
+impl CustomAggregators {
+    /// Creates a new empty `CustomAggregators` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a custom metric with the given name.
+    pub fn register<A, F>(&mut self, name: &str, factory: F)
+    where
+        A: Aggregator + 'static,
+        F: Fn() -> A + Send + Sync + 'static,
+    {
+        self.factories.push((
+            name.to_string(),
+            Arc::new(move || Box::new(factory()) as Box<dyn DynAggregator>),
+        ));
+    }
+
+    /// Groups the deaths by the given fields, in order. An empty list disables the grouping.
+    pub fn group_by(&mut self, fields: Vec<Field>) {
+        self.group_by = fields;
+    }
+
+    /// Returns the fields used to group the deaths.
+    pub(crate) fn group_by_fields(&self) -> &[Field] {
+        &self.group_by
+    }
+
+    /// Creates an empty instance of each registered metric.
+    pub(crate) fn instantiate(&self) -> Vec<(String, Box<dyn DynAggregator>)> {
+        self.factories
+            .iter()
+            .map(|(name, factory)| (name.clone(), factory()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct DeathsCount(usize);
+
+    impl Aggregator for DeathsCount {
+        fn fold(&mut self, _death: &Death) {
+            self.0 += 1;
+        }
+
+        fn merge(&mut self, other: Self) {
+            self.0 += other.0;
+        }
+
+        fn render(&self) -> serde_json::Value {
+            serde_json::json!(self.0)
+        }
+    }
+
+    fn death() -> Death {
+        Death::new(None, None, None, None, None, None, None)
+    }
+
+    #[test]
+    fn test_dyn_aggregator_fold_and_merge() {
+        let mut custom = CustomAggregators::new();
+        custom.register("deaths", DeathsCount::default);
+
+        let mut first = custom.instantiate();
+        let mut second = custom.instantiate();
+
+        first[0].1.fold_death(&death());
+        second[0].1.fold_death(&death());
+        second[0].1.fold_death(&death());
+
+        let (_, second) = second.pop().unwrap();
+        first[0].1.merge_boxed(second);
+
+        assert_eq!(first[0].0, "deaths");
+        assert_eq!(first[0].1.render_json(), serde_json::json!(3));
+    }
+}
@@ -1,37 +1,929 @@
-const ARGS: usize = 3;
+use std::slice::Iter;
 
-/// Reads the arguments from the command line.
+use serde_json::{Map, Value};
+use tp_individual::{AnalysisConfig, AnalyzerError, OutputFormat};
+
+const CONFIG_FLAG: &str = "--config";
+const PRINT_CONFIG_FLAG: &str = "--print-config";
+const OUTPUT_FLAG: &str = "--output";
+const FORMAT_FLAG: &str = "--format";
+const ROWS_FLAG: &str = "--rows";
+const PORT_FLAG: &str = "--port";
+const INPUT_DIR_KEY: &str = "input_dir";
+const HELP_FLAGS: [&str; 2] = ["--help", "-h"];
+
+/// Options about reading the input files, with the kind of value they take.
+/// Each option overrides the setting of the configuration file with the same name, without dashes.
+const INGEST_FLAGS: [(&str, FlagValue); 5] = [
+    ("--threads", FlagValue::Count),
+    ("--strict", FlagValue::Switch),
+    ("--reject-file", FlagValue::Text),
+    ("--max-errors", FlagValue::Count),
+    ("--filter", FlagValue::Text),
+];
+
+/// Options about the leaderboards and the output, only accepted by `analyze`.
+const ANALYZE_FLAGS: [(&str, FlagValue); 16] = [
+    (OUTPUT_FLAG, FlagValue::Text),
+    (FORMAT_FLAG, FlagValue::Text),
+    ("--top-killers", FlagValue::Count),
+    ("--top-weapons", FlagValue::Count),
+    ("--top-weapons-of-player", FlagValue::Count),
+    ("--rank-players-by", FlagValue::Text),
+    ("--rank-weapons-by", FlagValue::Text),
+    ("--group-by", FlagValue::Text),
+    ("--top-groups", FlagValue::Count),
+    ("--snapshot", FlagValue::Text),
+    ("--incremental", FlagValue::Text),
+    ("--watch", FlagValue::Switch),
+    ("--watch-interval", FlagValue::Count),
+    ("--follow", FlagValue::Switch),
+    ("--metrics", FlagValue::Text),
+    ("--tui", FlagValue::Switch),
+];
+
+/// Options about the leaderboards, accepted by `explore`.
+const EXPLORE_FLAGS: [(&str, FlagValue); 5] = [
+    ("--top-killers", FlagValue::Count),
+    ("--top-weapons", FlagValue::Count),
+    ("--top-weapons-of-player", FlagValue::Count),
+    ("--rank-players-by", FlagValue::Text),
+    ("--rank-weapons-by", FlagValue::Text),
+];
+
+/// Options about the leaderboards and the ingestion in the background, accepted by `serve`.
+const SERVE_FLAGS: [(&str, FlagValue); 8] = [
+    ("--top-killers", FlagValue::Count),
+    ("--top-weapons", FlagValue::Count),
+    ("--top-weapons-of-player", FlagValue::Count),
+    ("--rank-players-by", FlagValue::Text),
+    ("--rank-weapons-by", FlagValue::Text),
+    ("--watch-interval", FlagValue::Count),
+    ("--follow", FlagValue::Switch),
+    ("--max-count", FlagValue::Count),
+];
+
+const DEFAULT_SAMPLE_ROWS: usize = 100;
+const DEFAULT_PORT: u16 = 8080;
+
+const USAGE: &str = "Usage: tp-individual <command> [options]
+
+Commands:
+  analyze [input_dir]               Aggregate the deaths and write the leaderboards
+  validate [input_dir]              Check the input files and report the discarded lines
+  query <query> [input_dir]         Run a SQL-like query over the deaths
+  sample <input_dir>                Write the first records of the CSV files into a single CSV
+  merge <input_file>...             Concatenate CSV files, or merge snapshots, into a single file
+  diff <old_output> <new_output>    Compare two JSON outputs of analyze
+  serve [input_dir]                 Serve the leaderboards with an HTTP API on localhost
+  explore [input_dir]               Load the stats once and explore them in an interactive shell
+
+Run 'tp-individual <command> --help' for the options of each command.";
+
+const ANALYZE_USAGE: &str = "Usage: tp-individual analyze [input_dir] [options]
+
+The input can also be a snapshot file, written by --snapshot or merge, instead of a directory.
+
+Options:
+  --config <path>                   TOML or JSON file with the settings, overridden by the options
+  --print-config                    Print the effective settings instead of running
+  --threads <count>                 Threads used to process the files (default: number of CPUs)
+  --output <path>                   Output file, or directory for csv
+                                    (default: output.json, . for csv, report.html for html, summary.md for markdown)
+  --format <format>                 Output format: json, csv, html or markdown (default: json)
+  --top-killers <count>             Players in the leaderboard (default: 10)
+  --top-weapons <count>             Weapons in the leaderboard (default: 10)
+  --top-weapons-of-player <count>   Weapons of each player in the leaderboard (default: 3)
+  --rank-players-by <metric>        kills, distance, share or kd (default: kills)
+  --rank-weapons-by <metric>        kills, distance or share (default: kills)
+  --group-by <fields>               Group the deaths by these comma separated fields,
+                                    e.g. map,killed_by
+  --top-groups <count>              Groups kept at each level of the grouping (default: 10)
+  --snapshot <path>                 Also save the unfiltered stats to this snapshot file
+  --incremental <path>              Only read the files not yet in this snapshot and merge them into it,
+                                    rebuilding it if a file was modified or removed
+  --watch                           Keep scanning the input directory, merging the new files
+                                    and rewriting the output after each change
+  --watch-interval <seconds>        Seconds between two scans in watch or follow mode (default: 2)
+  --follow                          Like --watch, but also read the rows appended to the CSV files,
+                                    like tail -F
+  --metrics <path>                  Also save the row counts, stage durations and totals of the run
+                                    to this file, in the Prometheus text format
+  --tui                             Show the files read, the rows per second of each thread, the rejected
+                                    rows and the top killers while the deaths are read
+  --strict                          Discard lines with suspicious values
+  --reject-file <path>              Write the discarded lines to this file
+  --max-errors <count>              Abort after discarding more than this many lines
+  --filter <expression>             Only aggregate the deaths matching the expression,
+                                    e.g. \"map = 'MIRAMAR' and time > 1200\"";
+
+const VALIDATE_USAGE: &str = "Usage: tp-individual validate [input_dir] [options]
+
+Options:
+  --config <path>                   TOML or JSON file with the settings, overridden by the options
+  --print-config                    Print the effective settings instead of running
+  --threads <count>                 Threads used to process the files (default: number of CPUs)
+  --strict                          Discard lines with suspicious values
+  --reject-file <path>              Write the discarded lines to this file
+  --max-errors <count>              Abort after discarding more than this many lines
+  --filter <expression>             Only aggregate the deaths matching the expression,
+                                    e.g. \"map = 'MIRAMAR' and time > 1200\"";
+
+const QUERY_USAGE: &str = "Usage: tp-individual query <query> [input_dir] [options]
+
+Queries take the form:
+  SELECT <column>, ... FROM deaths [WHERE <expression>] [GROUP BY <field>, ...]
+  [ORDER BY <column> [ASC|DESC], ...] [LIMIT <count>]
+where each column is a grouped field or one of count(*), count(field), sum(field),
+avg(field), min(field), max(field) or percentile(field, p), optionally named with AS.
+
+Options:
+  --config <path>                   TOML or JSON file with the settings, overridden by the options
+  --print-config                    Print the effective settings instead of running
+  --threads <count>                 Threads used to process the files (default: number of CPUs)
+  --output <path>                   Output file (default: standard output)
+  --format <format>                 Output format: json or csv (default: json)
+  --strict                          Discard lines with suspicious values
+  --reject-file <path>              Write the discarded lines to this file
+  --max-errors <count>              Abort after discarding more than this many lines
+  --filter <expression>             Only query the deaths matching the expression";
+
+const SAMPLE_USAGE: &str = "Usage: tp-individual sample <input_dir> [options]
+
+Options:
+  --rows <count>                    Records to keep (default: 100)
+  --output <path>                   Output CSV file (default: standard output)";
+
+const MERGE_USAGE: &str = "Usage: tp-individual merge <input_file>... --output <path>
+
+The input files are either CSV files, concatenated into a single CSV,
+or snapshot files (.snapshot), whose stats are added up into a single snapshot.
+
+Options:
+  --output <path>                   Output CSV or snapshot file (required)";
+
+const DIFF_USAGE: &str = "Usage: tp-individual diff <old_output> <new_output>";
+
+const SERVE_USAGE: &str = "Usage: tp-individual serve [input_dir] [options]
+
+Loads the stats once and serves them as JSON, while the input directory keeps being scanned for new files.
+The input can also be a snapshot file, which is not scanned again.
+
+Endpoints:
+  GET /top_killers?n=<count>        Top players, like the output of analyze
+  GET /top_weapons?n=<count>        Top weapons, like the output of analyze
+  GET /players/<name>               Rank and stats of a player
+  GET /weapons/<name>               Rank and stats of a weapon
+  GET /metrics                      Row counts, stage durations and totals, in the Prometheus text format
+
+Options:
+  --config <path>                   TOML or JSON file with the settings, overridden by the options
+  --print-config                    Print the effective settings instead of running
+  --port <port>                     Port to listen on, on localhost (default: 8080)
+  --threads <count>                 Threads used to process the files (default: number of CPUs)
+  --top-killers <count>             Default number of players of /top_killers (default: 10)
+  --top-weapons <count>             Default number of weapons of /top_weapons (default: 10)
+  --top-weapons-of-player <count>   Weapons of each player in /top_killers (default: 3)
+  --max-count <count>               Largest n accepted by /top_killers and /top_weapons (default: 1000)
+  --rank-players-by <metric>        kills, distance, share or kd (default: kills)
+  --rank-weapons-by <metric>        kills, distance or share (default: kills)
+  --watch-interval <seconds>        Seconds between two scans of the input directory (default: 2)
+  --follow                          Also read the rows appended to the CSV files, like tail -F
+  --strict                          Discard lines with suspicious values
+  --reject-file <path>              Write the discarded lines to this file
+  --max-errors <count>              Abort after discarding more than this many lines
+  --filter <expression>             Only aggregate the deaths matching the expression";
+
+const EXPLORE_USAGE: &str = "Usage: tp-individual explore [input_dir] [options]
+
+Loads the deaths once and answers the commands of an interactive shell, without reading the files again:
+top killers [count], top weapons [count], player <name>, weapon <name>, filter <expression>, filter clear
+and export <format> <path>. Type help in the shell for the details.
+The input can also be a snapshot file, whose deaths cannot be filtered.
+
+Options:
+  --config <path>                   TOML or JSON file with the settings, overridden by the options
+  --print-config                    Print the effective settings instead of running
+  --threads <count>                 Threads used to process the files (default: number of CPUs)
+  --top-killers <count>             Default number of players of top killers (default: 10)
+  --top-weapons <count>             Default number of weapons of top weapons (default: 10)
+  --top-weapons-of-player <count>   Weapons of each player in top killers (default: 3)
+  --rank-players-by <metric>        kills, distance, share or kd (default: kills)
+  --rank-weapons-by <metric>        kills, distance or share (default: kills)
+  --strict                          Discard lines with suspicious values
+  --reject-file <path>              Write the discarded lines to this file
+  --max-errors <count>              Abort after discarding more than this many lines
+  --filter <expression>             Only load the deaths matching the expression";
+
+/// Parses the arguments of a command, returning the reason when they are invalid.
+type CommandParser = fn(&[String]) -> Result<Command, String>;
+
+/// Kinds of values taken by the options.
+#[derive(Clone, Copy)]
+enum FlagValue {
+    /// The option takes no value.
+    Switch,
+    /// The option takes a non-negative integer.
+    Count,
+    /// The option takes any text.
+    Text,
+}
+
+/// Options of the commands that run an analysis over the input files.
+#[derive(Debug, PartialEq)]
+pub struct RunArgs {
+    pub config_file: Option<String>,
+    /// Settings given by the command line, keyed like in the configuration file.
+    pub overrides: Map<String, Value>,
+    pub print_config: bool,
+}
+
+impl RunArgs {
+    fn new() -> Self {
+        Self {
+            config_file: None,
+            overrides: Map::new(),
+            print_config: false,
+        }
+    }
+
+    /// Overrides the input directory of the configuration file, if one is given.
+    fn set_input_dir(&mut self, input_dir: Option<String>) {
+        if let Some(input_dir) = input_dir {
+            self.overrides
+                .insert(INPUT_DIR_KEY.to_string(), Value::from(input_dir));
+        }
+    }
+
+    /// Returns the effective configuration of the run: the configuration file, if any, overridden by the command line.
+    pub fn config(self) -> Result<AnalysisConfig, AnalyzerError> {
+        AnalysisConfig::load(self.config_file.as_deref(), self.overrides)
+    }
+}
+
+/// Options of the `query` command.
+#[derive(Debug, PartialEq)]
+pub struct QueryArgs {
+    pub query: String,
+    pub run: RunArgs,
+    /// File to write the result to, instead of the standard output.
+    pub output_file: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// Options of the `sample` command.
+#[derive(Debug, PartialEq)]
+pub struct SampleArgs {
+    pub input_dir: String,
+    pub rows: usize,
+    pub output_file: Option<String>,
+}
+
+/// Options of the `merge` command.
+#[derive(Debug, PartialEq)]
+pub struct MergeArgs {
+    pub input_files: Vec<String>,
+    pub output_file: String,
+}
+
+/// Options of the `diff` command.
+#[derive(Debug, PartialEq)]
+pub struct DiffArgs {
+    pub old_output: String,
+    pub new_output: String,
+}
+
+/// Options of the `serve` command.
+#[derive(Debug, PartialEq)]
+pub struct ServeArgs {
+    pub run: RunArgs,
+    /// Port to listen on, on localhost.
+    pub port: u16,
+}
+
+/// Command to run, with its options.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Analyze(RunArgs),
+    Validate(RunArgs),
+    Query(QueryArgs),
+    Sample(SampleArgs),
+    Merge(MergeArgs),
+    Diff(DiffArgs),
+    Serve(ServeArgs),
+    Explore(RunArgs),
+    /// Prints the given usage.
+    Help(&'static str),
+}
+
+/// Reads the command and its options from the command line.
+///
+/// The program receives a command followed by its arguments and named options, in any order:
 ///
-/// The program receives the following arguments:
+/// - `analyze [input_dir]`: aggregates the deaths and writes the leaderboards.
+/// - `validate [input_dir]`: reads the input files and reports the discarded lines.
+/// - `query <query> [input_dir]`: runs a SQL-like query over the deaths.
+/// - `sample <input_dir>`: writes the first records of the CSV files into a single CSV.
+/// - `merge <input_file>...`: concatenates CSV files into a single CSV.
+/// - `diff <old_output> <new_output>`: compares two JSON outputs of `analyze`.
+/// - `serve [input_dir]`: serves the leaderboards with an HTTP API on localhost.
+/// - `explore [input_dir]`: explores the stats in an interactive shell.
 ///
-/// - The path to the directory containing the CSV files.
-/// - The number of threads to use for processing the CSV files.
-/// - The name of the output JSON file.
+/// The settings of `analyze`, `validate`, `query`, `serve` and `explore` can also be read from a file given with `--config`,
+/// in which case the options of the command line override its values.
 ///
-/// Any other number of arguments will cause the program to exit with an error message.
-pub fn read_args() -> (String, usize, String) {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != ARGS + 1 {
-        eprintln!("Usage: {} <input_dir> <threads> <output_file>", args[0]);
-        std::process::exit(1);
-    }
-
-    let input_dir = args[1].to_string();
-    let output_file = args[3].to_string();
-
-    let threads = match args[2].parse() {
-        Ok(threads) => {
-            if threads == 0 {
-                eprintln!("Invalid number of threads");
-                std::process::exit(1);
+/// `--help` prints the usage of the program, or of the command if it comes after it.
+/// Invalid arguments return a configuration error including the usage.
+pub fn read_args() -> Result<Command, AnalyzerError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    parse_args(&args)
+}
+
+/// Parses the arguments of the program, without the program name.
+fn parse_args(args: &[String]) -> Result<Command, AnalyzerError> {
+    let Some((command, args)) = args.split_first() else {
+        return Err(AnalyzerError::Config(format!("Missing command\n{}", USAGE)));
+    };
+
+    let (parse, usage): (CommandParser, _) = match command.as_str() {
+        "analyze" => (parse_analyze, ANALYZE_USAGE),
+        "validate" => (parse_validate, VALIDATE_USAGE),
+        "query" => (parse_query, QUERY_USAGE),
+        "sample" => (parse_sample, SAMPLE_USAGE),
+        "merge" => (parse_merge, MERGE_USAGE),
+        "diff" => (parse_diff, DIFF_USAGE),
+        "serve" => (parse_serve, SERVE_USAGE),
+        "explore" => (parse_explore, EXPLORE_USAGE),
+        "help" => return Ok(Command::Help(USAGE)),
+        command if HELP_FLAGS.contains(&command) => return Ok(Command::Help(USAGE)),
+        command => {
+            return Err(AnalyzerError::Config(format!(
+                "Unknown command: {}\n{}",
+                command, USAGE
+            )))
+        }
+    };
+
+    if args.iter().any(|arg| HELP_FLAGS.contains(&arg.as_str())) {
+        return Ok(Command::Help(usage));
+    }
+
+    parse(args).map_err(|e| AnalyzerError::Config(format!("{}\n{}", e, usage)))
+}
+
+/// Iterates the arguments of a command, separating the positional arguments from the named options.
+struct ArgsParser<'a> {
+    args: Iter<'a, String>,
+    positional: Vec<String>,
+}
+
+impl<'a> ArgsParser<'a> {
+    fn new(args: &'a [String]) -> Self {
+        Self {
+            args: args.iter(),
+            positional: Vec::new(),
+        }
+    }
+
+    /// Returns the next named option, keeping the positional arguments found before it.
+    fn next_flag(&mut self) -> Option<&'a str> {
+        for arg in self.args.by_ref() {
+            if arg.starts_with("--") {
+                return Some(arg);
             }
-            threads
+            self.positional.push(arg.to_string());
         }
-        Err(_) => {
-            eprintln!("Invalid number of threads");
-            std::process::exit(1);
+        None
+    }
+
+    /// Returns the value of the given option.
+    fn value(&mut self, flag: &str) -> Result<&'a str, String> {
+        self.args
+            .next()
+            .map(|value| value.as_str())
+            .ok_or_else(|| format!("Missing value for {}", flag))
+    }
+
+    /// Returns the value of the given option, parsed as a count.
+    fn count(&mut self, flag: &str) -> Result<usize, String> {
+        let value = self.value(flag)?;
+        value
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+    }
+
+    /// Returns the positional arguments, which must be exactly `N`.
+    fn positional<const N: usize>(self) -> Result<[String; N], String> {
+        if let Some(unexpected) = self.positional.get(N) {
+            return Err(format!("Unexpected argument: {}", unexpected));
         }
-    };
+        self.positional
+            .try_into()
+            .map_err(|_| "Missing arguments".to_string())
+    }
+
+    /// Returns the positional argument, if any.
+    fn optional_positional(self) -> Result<Option<String>, String> {
+        if let Some(unexpected) = self.positional.get(1) {
+            return Err(format!("Unexpected argument: {}", unexpected));
+        }
+        Ok(self.positional.into_iter().next())
+    }
+}
+
+fn parse_analyze(args: &[String]) -> Result<Command, String> {
+    parse_run(args, &[&INGEST_FLAGS[..], &ANALYZE_FLAGS[..]].concat()).map(Command::Analyze)
+}
+
+fn parse_validate(args: &[String]) -> Result<Command, String> {
+    parse_run(args, &INGEST_FLAGS).map(Command::Validate)
+}
+
+/// Parses the arguments of a command that runs an analysis, accepting the given options besides the configuration ones.
+/// The values of the options are only checked to be of the right kind, the rest is validated with the configuration.
+fn parse_run(args: &[String], flags: &[(&str, FlagValue)]) -> Result<RunArgs, String> {
+    let mut parser = ArgsParser::new(args);
+    let mut run_args = RunArgs::new();
+
+    while let Some(flag) = parser.next_flag() {
+        parse_run_flag(&mut parser, flag, flags, &mut run_args)?;
+    }
+
+    run_args.set_input_dir(parser.optional_positional()?);
+
+    Ok(run_args)
+}
+
+/// Parses a configuration option, or one of the given options, into `run_args`.
+fn parse_run_flag(
+    parser: &mut ArgsParser,
+    flag: &str,
+    flags: &[(&str, FlagValue)],
+    run_args: &mut RunArgs,
+) -> Result<(), String> {
+    match flag {
+        CONFIG_FLAG => run_args.config_file = Some(parser.value(flag)?.to_string()),
+        PRINT_CONFIG_FLAG => run_args.print_config = true,
+        _ => {
+            let Some((_, flag_value)) = flags.iter().find(|(name, _)| *name == flag) else {
+                return Err(format!("Unknown option: {}", flag));
+            };
+            let value = match flag_value {
+                FlagValue::Switch => Value::Bool(true),
+                FlagValue::Count => Value::from(parser.count(flag)?),
+                FlagValue::Text => Value::from(parser.value(flag)?),
+            };
+            run_args.overrides.insert(config_key(flag), value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the arguments of `query`: the query, an optional input directory and the options.
+/// Unlike in `analyze`, the output and its format are not part of the configuration, and the output defaults to the standard output.
+fn parse_query(args: &[String]) -> Result<Command, String> {
+    let mut parser = ArgsParser::new(args);
+    let mut run_args = RunArgs::new();
+    let mut output_file = None;
+    let mut format = OutputFormat::Json;
 
-    (input_dir, threads, output_file)
+    while let Some(flag) = parser.next_flag() {
+        match flag {
+            OUTPUT_FLAG => output_file = Some(parser.value(flag)?.to_string()),
+            FORMAT_FLAG => {
+                format = parser.value(flag)?.parse()?;
+                if matches!(format, OutputFormat::Html | OutputFormat::Markdown) {
+                    return Err(format!("Query results cannot be saved as {}", format));
+                }
+            }
+            _ => parse_run_flag(&mut parser, flag, &INGEST_FLAGS, &mut run_args)?,
+        }
+    }
+
+    let mut positional = parser.positional.into_iter();
+    let query = positional.next().ok_or("Missing query")?;
+    run_args.set_input_dir(positional.next());
+    if let Some(unexpected) = positional.next() {
+        return Err(format!("Unexpected argument: {}", unexpected));
+    }
+
+    Ok(Command::Query(QueryArgs {
+        query,
+        run: run_args,
+        output_file,
+        format,
+    }))
+}
+
+/// Parses the arguments of `serve`: an optional input directory and the options.
+/// The port is not part of the configuration.
+fn parse_serve(args: &[String]) -> Result<Command, String> {
+    let mut parser = ArgsParser::new(args);
+    let mut run_args = RunArgs::new();
+    let mut port = DEFAULT_PORT;
+
+    while let Some(flag) = parser.next_flag() {
+        match flag {
+            PORT_FLAG => {
+                let value = parser.value(flag)?;
+                port = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+            }
+            _ => parse_run_flag(
+                &mut parser,
+                flag,
+                &[&INGEST_FLAGS[..], &SERVE_FLAGS[..]].concat(),
+                &mut run_args,
+            )?,
+        }
+    }
+
+    run_args.set_input_dir(parser.optional_positional()?);
+
+    Ok(Command::Serve(ServeArgs {
+        run: run_args,
+        port,
+    }))
+}
+
+fn parse_explore(args: &[String]) -> Result<Command, String> {
+    parse_run(args, &[&INGEST_FLAGS[..], &EXPLORE_FLAGS[..]].concat()).map(Command::Explore)
+}
+
+/// Returns the key of the configuration file overridden by the given option.
+fn config_key(flag: &str) -> String {
+    flag.trim_start_matches("--").replace('-', "_")
+}
+
+fn parse_sample(args: &[String]) -> Result<Command, String> {
+    let mut parser = ArgsParser::new(args);
+    let mut rows = DEFAULT_SAMPLE_ROWS;
+    let mut output_file = None;
+
+    while let Some(flag) = parser.next_flag() {
+        match flag {
+            ROWS_FLAG => rows = parser.count(flag)?,
+            OUTPUT_FLAG => output_file = Some(parser.value(flag)?.to_string()),
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+
+    let [input_dir] = parser.positional()?;
+
+    Ok(Command::Sample(SampleArgs {
+        input_dir,
+        rows,
+        output_file,
+    }))
+}
+
+fn parse_merge(args: &[String]) -> Result<Command, String> {
+    let mut parser = ArgsParser::new(args);
+    let mut output_file = None;
+
+    while let Some(flag) = parser.next_flag() {
+        match flag {
+            OUTPUT_FLAG => output_file = Some(parser.value(flag)?.to_string()),
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+
+    if parser.positional.is_empty() {
+        return Err("Missing input files".to_string());
+    }
+
+    Ok(Command::Merge(MergeArgs {
+        input_files: parser.positional,
+        output_file: output_file.ok_or_else(|| format!("Missing {}", OUTPUT_FLAG))?,
+    }))
+}
+
+fn parse_diff(args: &[String]) -> Result<Command, String> {
+    let mut parser = ArgsParser::new(args);
+
+    if let Some(flag) = parser.next_flag() {
+        return Err(format!("Unknown option: {}", flag));
+    }
+
+    let [old_output, new_output] = parser.positional()?;
+
+    Ok(Command::Diff(DiffArgs {
+        old_output,
+        new_output,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tp_individual::{Field, OutputFormat, RankingMetric};
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, AnalyzerError> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    /// Parses the arguments and, for the commands that run an analysis, resolves their configuration.
+    fn resolve(args: &[&str]) -> Result<Option<AnalysisConfig>, AnalyzerError> {
+        match parse(args)? {
+            Command::Analyze(run_args) | Command::Validate(run_args) => run_args.config().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn analyze_config(args: &[&str]) -> AnalysisConfig {
+        match parse(args) {
+            Ok(Command::Analyze(run_args)) => run_args.config().unwrap(),
+            other => panic!("Expected the analyze command, got {:?}", other),
+        }
+    }
+
+    fn assert_config_error(args: &[&str]) {
+        assert!(matches!(resolve(args), Err(AnalyzerError::Config(_))));
+    }
+
+    #[test]
+    fn test_analyze_defaults() {
+        let config = analyze_config(&["analyze", "dataset"]);
+
+        assert_eq!(config, AnalysisConfig::new("dataset"));
+    }
+
+    #[test]
+    fn test_analyze_options_in_any_order() {
+        let config = analyze_config(&[
+            "analyze",
+            "--threads",
+            "4",
+            "dataset",
+            "--output",
+            "stats.json",
+            "--format",
+            "json",
+            "--strict",
+            "--reject-file",
+            "rejects.tsv",
+            "--max-errors",
+            "10",
+            "--top-killers",
+            "20",
+            "--top-weapons",
+            "5",
+            "--top-weapons-of-player",
+            "1",
+            "--rank-players-by",
+            "kd",
+            "--rank-weapons-by",
+            "distance",
+            "--filter",
+            "map = 'MIRAMAR'",
+            "--group-by",
+            "map,killed_by",
+            "--top-groups",
+            "2",
+            "--watch",
+            "--watch-interval",
+            "10",
+            "--follow",
+            "--metrics",
+            "run.prom",
+            "--tui",
+        ]);
+
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.threads, 4);
+        assert_eq!(config.reject_file, Some("rejects.tsv".to_string()));
+        assert_eq!(config.max_errors, Some(10));
+        assert!(config.strict);
+        assert_eq!(config.output_file, "stats.json");
+        assert_eq!(config.format, OutputFormat::Json);
+        assert_eq!(config.top_players_count, 20);
+        assert_eq!(config.top_weapons_count, 5);
+        assert_eq!(config.top_weapons_of_player_count, 1);
+        assert_eq!(config.players_ranking, RankingMetric::KillDeathRatio);
+        assert_eq!(config.weapons_ranking, RankingMetric::AverageDistance);
+        assert_eq!(config.filter, Some("map = 'MIRAMAR'".parse().unwrap()));
+        assert_eq!(config.group_by, vec![Field::Map, Field::KilledBy]);
+        assert_eq!(config.top_groups_count, 2);
+        assert!(config.watch);
+        assert_eq!(config.watch_interval, 10);
+        assert!(config.follow);
+        assert_eq!(config.metrics_file, Some("run.prom".to_string()));
+        assert!(config.tui);
+    }
+
+    #[test]
+    fn test_options_override_config_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_file = dir.path().join("run.toml");
+        std::fs::write(
+            &config_file,
+            "input_dir = \"dataset\"\nthreads = 8\ntop_killers = 5\n",
+        )
+        .unwrap();
+
+        let Command::Analyze(run_args) = parse(&[
+            "analyze",
+            "--config",
+            config_file.to_str().unwrap(),
+            "--threads",
+            "2",
+            "--print-config",
+        ])
+        .unwrap() else {
+            panic!("Expected the analyze command");
+        };
+
+        assert!(run_args.print_config);
+
+        let config = run_args.config().unwrap();
+
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.threads, 2);
+        assert_eq!(config.top_players_count, 5);
+    }
+
+    #[test]
+    fn test_other_commands() {
+        let Command::Validate(run_args) = parse(&["validate", "dataset", "--strict"]).unwrap()
+        else {
+            panic!("Expected the validate command");
+        };
+        assert!(run_args.config().unwrap().strict);
+
+        assert_eq!(
+            parse(&["sample", "dataset", "--rows", "5"]).unwrap(),
+            Command::Sample(SampleArgs {
+                input_dir: "dataset".to_string(),
+                rows: 5,
+                output_file: None,
+            })
+        );
+        assert_eq!(
+            parse(&["merge", "a.csv", "b.csv", "--output", "all.csv"]).unwrap(),
+            Command::Merge(MergeArgs {
+                input_files: vec!["a.csv".to_string(), "b.csv".to_string()],
+                output_file: "all.csv".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(&["diff", "old.json", "new.json"]).unwrap(),
+            Command::Diff(DiffArgs {
+                old_output: "old.json".to_string(),
+                new_output: "new.json".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(parse(&["--help"]).unwrap(), Command::Help(USAGE));
+        assert_eq!(parse(&["help"]).unwrap(), Command::Help(USAGE));
+        assert_eq!(
+            parse(&["analyze", "dataset", "-h"]).unwrap(),
+            Command::Help(ANALYZE_USAGE)
+        );
+    }
+
+    #[test]
+    fn test_missing_command() {
+        assert_config_error(&[]);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        assert_config_error(&["analyse", "dataset"]);
+    }
+
+    #[test]
+    fn test_missing_positional_args() {
+        assert_config_error(&["analyze"]);
+        assert_config_error(&["validate", "--strict"]);
+        assert_config_error(&["diff", "old.json"]);
+    }
+
+    #[test]
+    fn test_unexpected_positional_args() {
+        assert_config_error(&["analyze", "dataset", "4", "output.json"]);
+    }
+
+    #[test]
+    fn test_invalid_threads() {
+        assert_config_error(&["analyze", "dataset", "--threads", "0"]);
+        assert_config_error(&["analyze", "dataset", "--threads", "four"]);
+    }
+
+    #[test]
+    fn test_option_without_value() {
+        assert_config_error(&["analyze", "dataset", "--max-errors"]);
+        assert_config_error(&["analyze", "dataset", "--config"]);
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        assert_config_error(&["analyze", "dataset", "--unknown", "1"]);
+        assert_config_error(&["validate", "dataset", "--top-killers", "1"]);
+        assert_config_error(&["sample", "dataset", "--strict"]);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert_config_error(&["analyze", "dataset", "--rank-players-by", "deaths"]);
+        assert_config_error(&["analyze", "dataset", "--rank-weapons-by", "kd"]);
+        assert_config_error(&["analyze", "dataset", "--top-killers", "-1"]);
+        assert_config_error(&["analyze", "dataset", "--format", "xml"]);
+        assert_config_error(&["analyze", "dataset", "--filter", "map = 1"]);
+    }
+
+    #[test]
+    fn test_query() {
+        let Command::Query(query_args) = parse(&[
+            "query",
+            "SELECT map, count(*) FROM deaths GROUP BY map",
+            "dataset",
+            "--format",
+            "csv",
+            "--threads",
+            "2",
+        ])
+        .unwrap() else {
+            panic!("Expected the query command");
+        };
+
+        assert_eq!(
+            query_args.query,
+            "SELECT map, count(*) FROM deaths GROUP BY map"
+        );
+        assert_eq!(query_args.output_file, None);
+        assert_eq!(query_args.format, OutputFormat::Csv);
+
+        let config = query_args.run.config().unwrap();
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.threads, 2);
+    }
+
+    #[test]
+    fn test_query_invalid_args() {
+        assert_config_error(&["query"]);
+        assert_config_error(&["query", "SELECT count(*) FROM deaths", "dataset", "extra"]);
+        assert_config_error(&["query", "SELECT count(*) FROM deaths", "--format", "xml"]);
+        assert_config_error(&["query", "SELECT count(*) FROM deaths", "--format", "html"]);
+        assert_config_error(&["query", "SELECT count(*) FROM deaths", "--top-killers", "1"]);
+    }
+
+    #[test]
+    fn test_merge_requires_inputs_and_output() {
+        assert_config_error(&["merge", "a.csv", "b.csv"]);
+        assert_config_error(&["merge", "--output", "all.csv"]);
+    }
+
+    #[test]
+    fn test_serve() {
+        let Command::Serve(serve_args) = parse(&[
+            "serve",
+            "dataset",
+            "--port",
+            "9000",
+            "--top-killers",
+            "5",
+            "--max-count",
+            "100",
+            "--follow",
+        ])
+        .unwrap() else {
+            panic!("Expected the serve command");
+        };
+
+        assert_eq!(serve_args.port, 9000);
+
+        let config = serve_args.run.config().unwrap();
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.top_players_count, 5);
+        assert_eq!(config.max_count, 100);
+        assert!(config.follow);
+    }
+
+    #[test]
+    fn test_serve_invalid_args() {
+        assert_config_error(&["serve", "dataset", "--port", "70000"]);
+        assert_config_error(&["serve", "dataset", "--output", "output.json"]);
+        assert_config_error(&["serve", "dataset", "other"]);
+    }
+
+    #[test]
+    fn test_explore() {
+        let Command::Explore(run_args) =
+            parse(&["explore", "dataset", "--rank-players-by", "kd", "--strict"]).unwrap()
+        else {
+            panic!("Expected the explore command");
+        };
+
+        let config = run_args.config().unwrap();
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.players_ranking, RankingMetric::KillDeathRatio);
+        assert!(config.strict);
+        assert_config_error(&["explore", "dataset", "--watch"]);
+    }
 }
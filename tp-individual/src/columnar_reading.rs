@@ -0,0 +1,591 @@
+use arrow_array::{
+    cast::AsArray, types::Float64Type, Array, ArrayRef, Float64Array, RecordBatch, StringArray,
+};
+use arrow_cast::display::array_value_to_string;
+use arrow_schema::{ArrowError, DataType, Schema};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+use rayon::{prelude::*, ThreadPool};
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    deaths::{Death, RecordError},
+    file_reading::{count_file_read, InputFormat},
+    ingestion_report::IngestionReport,
+};
+
+const KILLED_BY: &str = "killed_by";
+const KILLER_NAME: &str = "killer_name";
+const VICTIM_NAME: &str = "victim_name";
+const KILLER_POSITION_X: &str = "killer_position_x";
+const KILLER_POSITION_Y: &str = "killer_position_y";
+const VICTIM_POSITION_X: &str = "victim_position_x";
+const VICTIM_POSITION_Y: &str = "victim_position_y";
+const KILLER_PLACEMENT: &str = "killer_placement";
+const MAP: &str = "map";
+const MATCH_ID: &str = "match_id";
+const TIME: &str = "time";
+const VICTIM_PLACEMENT: &str = "victim_placement";
+
+/// Columns needed to build a `Death`. Only these columns are read from the files.
+const DEATH_COLUMNS: [&str; 7] = [
+    KILLED_BY,
+    KILLER_NAME,
+    VICTIM_NAME,
+    KILLER_POSITION_X,
+    KILLER_POSITION_Y,
+    VICTIM_POSITION_X,
+    VICTIM_POSITION_Y,
+];
+
+/// Columns read into a `Death` when the files have them, left missing otherwise.
+const OPTIONAL_DEATH_COLUMNS: [&str; 5] = [KILLER_PLACEMENT, MAP, MATCH_ID, TIME, VICTIM_PLACEMENT];
+
+type RecordBatches = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>> + Send>;
+
+/// Returns the indices of the columns needed to build a `Death` in the given schema,
+/// followed by the indices of the optional columns it has.
+fn death_column_indices(schema: &Schema) -> Result<Vec<usize>, ArrowError> {
+    let mut indices = DEATH_COLUMNS
+        .iter()
+        .map(|column| schema.index_of(column))
+        .collect::<Result<Vec<_>, _>>()?;
+    indices.extend(
+        OPTIONAL_DEATH_COLUMNS
+            .iter()
+            .filter_map(|column| schema.index_of(column).ok()),
+    );
+    Ok(indices)
+}
+
+/// Opens a Parquet file, reading only the columns needed to build a `Death`.
+fn read_parquet_batches(file: &Path) -> Result<RecordBatches, ArrowError> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(file)?)?;
+
+    let indices = death_column_indices(builder.schema())?;
+    let mask = ProjectionMask::roots(builder.parquet_schema(), indices);
+
+    Ok(Box::new(builder.with_projection(mask).build()?))
+}
+
+/// Opens an Arrow IPC file, reading only the columns needed to build a `Death`.
+fn read_ipc_batches(file: &Path) -> Result<RecordBatches, ArrowError> {
+    let mut file = File::open(file)?;
+    let schema = arrow_ipc::reader::FileReader::try_new(&mut file, None)?.schema();
+    let indices = death_column_indices(&schema)?;
+
+    Ok(Box::new(arrow_ipc::reader::FileReader::try_new(
+        file,
+        Some(indices),
+    )?))
+}
+
+/// Returns the given column of the batch as strings, casting it if needed.
+fn string_column(batch: &RecordBatch, name: &str) -> Result<StringArray, ArrowError> {
+    let column = column(batch, name)?;
+    Ok(arrow_cast::cast(&column, &DataType::Utf8)?
+        .as_string::<i32>()
+        .clone())
+}
+
+/// Column of a batch cast to floats.
+/// Values that cannot be cast are kept as invalid numbers, instead of being turned into missing values by the cast.
+struct FloatColumn {
+    name: &'static str,
+    source: ArrayRef,
+    values: Float64Array,
+}
+
+impl FloatColumn {
+    /// Returns the float at the given row, treating nulls as missing values.
+    /// Returns an error if the row has a value that is not a finite number.
+    fn value(&self, row: usize) -> Result<Option<f64>, RecordError> {
+        if self.values.is_valid(row) && self.values.value(row).is_finite() {
+            Ok(Some(self.values.value(row)))
+        } else if self.source.is_valid(row) {
+            Err(RecordError::InvalidNumber {
+                field: self.name,
+                value: array_value_to_string(&self.source, row).unwrap_or_default(),
+            })
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Returns the given column of the batch as floats, casting it if needed.
+fn float_column(batch: &RecordBatch, name: &'static str) -> Result<FloatColumn, ArrowError> {
+    let source = column(batch, name)?;
+    let values = arrow_cast::cast(&source, &DataType::Float64)?
+        .as_primitive::<Float64Type>()
+        .clone();
+
+    Ok(FloatColumn {
+        name,
+        source,
+        values,
+    })
+}
+
+/// Returns the given column of the batch as strings, if the batch has it.
+fn optional_string_column(
+    batch: &RecordBatch,
+    name: &str,
+) -> Result<Option<StringArray>, ArrowError> {
+    match batch.column_by_name(name) {
+        Some(_) => string_column(batch, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Returns the given column of the batch as floats, if the batch has it.
+fn optional_float_column(
+    batch: &RecordBatch,
+    name: &'static str,
+) -> Result<Option<FloatColumn>, ArrowError> {
+    match batch.column_by_name(name) {
+        Some(_) => float_column(batch, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn column(batch: &RecordBatch, name: &str) -> Result<ArrayRef, ArrowError> {
+    batch
+        .column_by_name(name)
+        .cloned()
+        .ok_or_else(|| ArrowError::SchemaError(format!("Missing column {}", name)))
+}
+
+/// Returns the string at the given row, treating nulls and empty strings as missing values.
+fn string_value(array: &StringArray, row: usize) -> Option<String> {
+    (array.is_valid(row) && !array.value(row).is_empty()).then(|| array.value(row).to_string())
+}
+
+/// Returns the float at the given row of a column the batch may not have.
+fn optional_float_value(
+    column: &Option<FloatColumn>,
+    row: usize,
+) -> Result<Option<f64>, RecordError> {
+    column.as_ref().map_or(Ok(None), |column| column.value(row))
+}
+
+/// Builds a `Death` for each row of the batch, or the reason why the row is not valid.
+/// In `strict` mode, the rows must also pass [`Death::validate_strict`], as the CSV records.
+fn deaths_from_batch(
+    batch: &RecordBatch,
+    strict: bool,
+) -> Result<Vec<Result<Death, RecordError>>, ArrowError> {
+    let killed_by = string_column(batch, KILLED_BY)?;
+    let killer_name = string_column(batch, KILLER_NAME)?;
+    let victim_name = string_column(batch, VICTIM_NAME)?;
+    let killer_position_x = float_column(batch, KILLER_POSITION_X)?;
+    let killer_position_y = float_column(batch, KILLER_POSITION_Y)?;
+    let victim_position_x = float_column(batch, VICTIM_POSITION_X)?;
+    let victim_position_y = float_column(batch, VICTIM_POSITION_Y)?;
+    let killer_placement = optional_float_column(batch, KILLER_PLACEMENT)?;
+    let map = optional_string_column(batch, MAP)?;
+    let match_id = optional_string_column(batch, MATCH_ID)?;
+    let time = optional_float_column(batch, TIME)?;
+    let victim_placement = optional_float_column(batch, VICTIM_PLACEMENT)?;
+
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            let death = Death {
+                killed_by: string_value(&killed_by, row),
+                killer_name: string_value(&killer_name, row),
+                killer_placement: optional_float_value(&killer_placement, row)?,
+                killer_position_x: killer_position_x.value(row)?,
+                killer_position_y: killer_position_y.value(row)?,
+                map: map.as_ref().and_then(|array| string_value(array, row)),
+                match_id: match_id.as_ref().and_then(|array| string_value(array, row)),
+                time: optional_float_value(&time, row)?,
+                victim_name: string_value(&victim_name, row),
+                victim_placement: optional_float_value(&victim_placement, row)?,
+                victim_position_x: victim_position_x.value(row)?,
+                victim_position_y: victim_position_y.value(row)?,
+            };
+            if strict {
+                death.validate_strict()?;
+            }
+            Ok(death)
+        })
+        .collect())
+}
+
+/// Read all the Parquet and Arrow IPC files in parallel and turn their rows into `Death` instances.
+/// Only the columns of a `Death` are read, the optional ones only if the files have them, and the record batches of each file are processed in parallel.
+/// If a file or a batch cannot be read, it will be skipped and recorded in the `report`,
+/// like the rows with a value that is not a number in a numeric column.
+/// In `strict` mode, the rows with suspicious values are also recorded, see [`Death::validate_strict`].
+/// Once the `report` exceeds its maximum number of errors, no more files nor batches will be read.
+pub fn read_columnar_files(
+    files: Vec<PathBuf>,
+    strict: bool,
+    report: Arc<IngestionReport>,
+    pool: &ThreadPool,
+) -> impl ParallelIterator<Item = Death> {
+    pool.install(|| {
+        files.into_par_iter().flat_map(move |file| {
+            let batches = match InputFormat::from_path(&file) {
+                // No more files are opened once the report was aborted
+                _ if report.is_aborted() => Ok(Box::new(std::iter::empty()) as RecordBatches),
+                Some(InputFormat::Parquet) => read_parquet_batches(&file),
+                Some(InputFormat::ArrowIpc) => read_ipc_batches(&file),
+                _ => Err(ArrowError::InvalidArgumentError(
+                    "Not a columnar file".to_string(),
+                )),
+            };
+
+            let report = report.clone();
+            let batches: RecordBatches = match batches {
+                Ok(batches) => batches,
+                Err(e) => {
+                    report.add_file_error(&file, &e.to_string());
+                    Box::new(std::iter::empty())
+                }
+            };
+
+            // Number of the first row of each batch in the file, to report the invalid rows.
+            // No more batches are read once the report was aborted.
+            let scan_report = report.clone();
+            let first_rows = batches.scan(1, move |next_row, batch| {
+                if scan_report.is_aborted() {
+                    return None;
+                }
+                let first_row = *next_row;
+                if let Ok(batch) = &batch {
+                    *next_row += batch.num_rows();
+                }
+                Some((first_row, batch))
+            });
+
+            first_rows
+                .chain(count_file_read(report.clone()))
+                .map(move |(first_row, batch)| (file.clone(), first_row, batch))
+                .par_bridge()
+                .flat_map_iter(move |(file, first_row, batch)| {
+                    if report.is_aborted() {
+                        return Vec::new();
+                    }
+                    match batch.and_then(|batch| deaths_from_batch(&batch, strict)) {
+                        Ok(rows) => {
+                            report.add_rows_read(rows.len());
+                            rows.into_iter()
+                                .enumerate()
+                                .filter_map(|(index, death)| {
+                                    death
+                                        .map_err(|e| {
+                                            report.add_record_error(
+                                                &file,
+                                                first_row + index,
+                                                "",
+                                                &e,
+                                            )
+                                        })
+                                        .ok()
+                                })
+                                .collect()
+                        }
+                        Err(e) => {
+                            report.add_file_error(&file, &e.to_string());
+                            Vec::new()
+                        }
+                    }
+                })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Float32Array, Int64Array};
+    use arrow_schema::Field;
+    use parquet::{
+        arrow::ArrowWriter,
+        basic::{Compression, GzipLevel, ZstdLevel},
+        file::properties::WriterProperties,
+    };
+    use rayon::ThreadPoolBuilder;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn report() -> Arc<IngestionReport> {
+        Arc::new(IngestionReport::new(None, None).unwrap())
+    }
+
+    fn deaths_batch() -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new(KILLED_BY, DataType::Utf8, true),
+            Field::new(KILLER_NAME, DataType::Utf8, true),
+            Field::new("match_id", DataType::Utf8, true),
+            Field::new(VICTIM_NAME, DataType::Utf8, true),
+            Field::new(KILLER_POSITION_X, DataType::Float64, true),
+            Field::new(KILLER_POSITION_Y, DataType::Float32, true),
+            Field::new(VICTIM_POSITION_X, DataType::Float64, true),
+            Field::new(VICTIM_POSITION_Y, DataType::Int64, true),
+        ]);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(vec![Some("AK47"), Some("Bluezone")])),
+                Arc::new(StringArray::from(vec![Some("Player1"), None])),
+                Arc::new(StringArray::from(vec!["match-id", "match-id"])),
+                Arc::new(StringArray::from(vec!["Player2", "Player3"])),
+                Arc::new(Float64Array::from(vec![Some(0.0), None])),
+                Arc::new(Float32Array::from(vec![Some(0.0), None])),
+                Arc::new(Float64Array::from(vec![100.0, 10.0])),
+                Arc::new(Int64Array::from(vec![0, 0])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deaths_from_batch() {
+        let deaths = deaths_from_batch(&deaths_batch(), false)
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(deaths.len(), 2);
+        assert_eq!(deaths[0].killed_by, Some("AK47".to_string()));
+        assert_eq!(deaths[0].killer_name, Some("Player1".to_string()));
+        assert_eq!(deaths[0].distance(), Some(100.0));
+        assert_eq!(deaths[0].match_id, Some("match-id".to_string()));
+        assert_eq!(deaths[0].map, None);
+        assert_eq!(deaths[1].killer_name, None);
+        assert_eq!(deaths[1].distance(), None);
+    }
+
+    /// Returns the deaths of [`deaths_batch`] with a victim position that is not a number in the second row.
+    fn invalid_deaths_batch() -> RecordBatch {
+        let batch = deaths_batch();
+        let index = batch.schema().index_of(VICTIM_POSITION_X).unwrap();
+        let mut columns = batch.columns().to_vec();
+        columns[index] = Arc::new(StringArray::from(vec!["100.0", "far"]));
+        let mut fields = batch.schema().fields().to_vec();
+        fields[index] = Arc::new(Field::new(VICTIM_POSITION_X, DataType::Utf8, true));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_numbers_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.parquet");
+        let batch = invalid_deaths_batch();
+
+        assert_eq!(
+            deaths_from_batch(&batch, false).unwrap()[1].as_ref().err(),
+            Some(&RecordError::InvalidNumber {
+                field: VICTIM_POSITION_X,
+                value: "far".to_string(),
+            })
+        );
+
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let report = report();
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 1);
+        assert_eq!(report.rows_read(), 2);
+        assert_eq!(report.error_count(), 1);
+        assert_eq!(report.file_reports()[0].1.invalid_numbers, 1);
+    }
+
+    #[test]
+    fn test_non_finite_numbers_are_invalid() {
+        let batch = deaths_batch();
+        let mut columns = batch.columns().to_vec();
+        columns[6] = Arc::new(Float64Array::from(vec![f64::NAN, f64::NEG_INFINITY]));
+        let batch = RecordBatch::try_new(batch.schema(), columns).unwrap();
+
+        let deaths = deaths_from_batch(&batch, false).unwrap();
+
+        assert_eq!(
+            deaths,
+            vec![
+                Err(RecordError::InvalidNumber {
+                    field: VICTIM_POSITION_X,
+                    value: "NaN".to_string(),
+                }),
+                Err(RecordError::InvalidNumber {
+                    field: VICTIM_POSITION_X,
+                    value: "-inf".to_string(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stops_reading_after_max_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.arrow");
+
+        let invalid_batch = invalid_deaths_batch();
+        let index = invalid_batch.schema().index_of(VICTIM_POSITION_X).unwrap();
+        let mut writer = arrow_ipc::writer::FileWriter::try_new(
+            File::create(&path).unwrap(),
+            &invalid_batch.schema(),
+        )
+        .unwrap();
+        writer.write(&invalid_batch).unwrap();
+        let batch = RecordBatch::try_new(invalid_batch.schema(), {
+            let mut columns = invalid_batch.columns().to_vec();
+            columns[index] = Arc::new(StringArray::from(vec!["100.0", "10.0"]));
+            columns
+        })
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let report = Arc::new(IngestionReport::new(None, Some(0)).unwrap());
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 1);
+        assert_eq!(report.rows_read(), 2);
+        assert!(report.is_aborted());
+    }
+
+    #[test]
+    fn test_read_parquet_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.parquet");
+
+        let batch = deaths_batch();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let report = report();
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 2);
+        assert_eq!(report.error_count(), 0);
+        assert_eq!(report.rows_read(), 2);
+        assert_eq!(report.files_read(), 1);
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_out_of_bounds_positions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.parquet");
+
+        let batch = deaths_batch();
+        let mut columns = batch.columns().to_vec();
+        columns[6] = Arc::new(Float64Array::from(vec![100.0, 500000.0]));
+        columns.push(Arc::new(StringArray::from(vec!["SANHOK", "SANHOK"])));
+        columns.push(Arc::new(Float64Array::from(vec![1.0, 2.0])));
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(MAP, DataType::Utf8, true)));
+        fields.push(Arc::new(Field::new(TIME, DataType::Float64, true)));
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let report = report();
+        let deaths = read_columnar_files(vec![path.clone()], true, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 1);
+        assert_eq!(report.error_count(), 1);
+        assert_eq!(report.file_reports()[0].1.out_of_bounds, 1);
+
+        let report = self::report();
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 2);
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn test_read_compressed_parquet_files() {
+        let dir = TempDir::new().unwrap();
+        let compressions = [
+            Compression::SNAPPY,
+            Compression::ZSTD(ZstdLevel::default()),
+            Compression::GZIP(GzipLevel::default()),
+        ];
+
+        let batch = deaths_batch();
+        let files = compressions
+            .into_iter()
+            .enumerate()
+            .map(|(index, compression)| {
+                let path = dir.path().join(format!("deaths-{}.parquet", index));
+                let properties = WriterProperties::builder()
+                    .set_compression(compression)
+                    .build();
+                let mut writer = ArrowWriter::try_new(
+                    File::create(&path).unwrap(),
+                    batch.schema(),
+                    Some(properties),
+                )
+                .unwrap();
+                writer.write(&batch).unwrap();
+                writer.close().unwrap();
+                path
+            })
+            .collect();
+
+        let report = report();
+        let deaths = read_columnar_files(files, false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 6);
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn test_read_ipc_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.arrow");
+
+        let batch = deaths_batch();
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(File::create(&path).unwrap(), &batch.schema())
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let report = report();
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 2);
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn test_missing_column_is_reported() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.parquet");
+
+        let batch = deaths_batch();
+        let batch = batch.project(&[0, 1, 2]).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let report = report();
+        let deaths = read_columnar_files(vec![path], false, report.clone(), &pool());
+
+        assert_eq!(deaths.count(), 0);
+        assert_eq!(report.error_count(), 1);
+    }
+}
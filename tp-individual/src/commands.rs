@@ -0,0 +1,361 @@
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+use tp_individual::{
+    check_dashboard, diff_outputs, find_input_files_in_dir, is_snapshot, load_snapshot, load_stats,
+    merge_csv_files, read_output, read_stats, read_stats_with_dashboard, run_query,
+    sample_csv_files, save_snapshot, serve_http, split_by_format, write_output, AnalysisConfig,
+    AnalyzerError, CustomAggregators, ExploreSession, InputWatcher, Leaderboards, Query, RowCounts,
+    SharedLeaderboards, ShellCommand, ShellHelper, Stats, Timer,
+};
+
+use crate::args_reading::{
+    Command, DiffArgs, MergeArgs, QueryArgs, RunArgs, SampleArgs, ServeArgs,
+};
+
+/// Runs the given command.
+pub fn run_command(command: Command) -> Result<(), AnalyzerError> {
+    match command {
+        Command::Analyze(args) => analyze(args),
+        Command::Validate(args) => validate(args),
+        Command::Query(args) => query(args),
+        Command::Sample(args) => sample(args),
+        Command::Merge(args) => merge(args),
+        Command::Diff(args) => diff(args),
+        Command::Serve(args) => serve(args),
+        Command::Explore(args) => explore(args),
+        Command::Help(usage) => {
+            println!("{}", usage);
+            Ok(())
+        }
+    }
+}
+
+fn analyze(args: RunArgs) -> Result<(), AnalyzerError> {
+    let Some(config) = resolve_config(args)? else {
+        return Ok(());
+    };
+    let pool = build_pool(config.threads)?;
+    let mut timer = Timer::new();
+
+    // READ INPUT FILES AND PROCESS DEATHS INTO STATS
+
+    let mut custom = CustomAggregators::new();
+    custom.group_by(config.group_by.clone());
+
+    if config.tui {
+        check_dashboard(&config)?;
+    }
+    if config.watch || config.follow {
+        return watch(&config, &custom, &pool);
+    }
+
+    let (stats, input_files, rows) = if config.tui {
+        read_stats_with_dashboard(&config, &custom, &pool)?
+    } else {
+        load_stats(&config, &custom, &pool)?
+    };
+    timer.print_lap("Processing deaths");
+
+    if let Some(snapshot_file) = &config.snapshot_file {
+        save_snapshot(&stats, snapshot_file)?;
+        println!("Snapshot saved in {}", snapshot_file);
+        timer.print_lap("Saving snapshot");
+    }
+
+    write_output(&config, stats, input_files, rows, &mut timer, &pool)?;
+    timer.print_total();
+
+    Ok(())
+}
+
+/// Keeps scanning the input directory every `watch_interval` seconds, and rewrites the output whenever it changes.
+/// The errors of a scan are printed and the directory is scanned again after the interval, unless too many lines were
+/// discarded, so it never returns Ok.
+fn watch(
+    config: &AnalysisConfig,
+    custom: &CustomAggregators,
+    pool: &ThreadPool,
+) -> Result<(), AnalyzerError> {
+    let mut watcher = InputWatcher::new(config.clone(), custom.clone())?;
+    println!(
+        "{} {} every {}s, press Ctrl+C to stop",
+        if config.follow {
+            "Following"
+        } else {
+            "Watching"
+        },
+        config.input_dir,
+        config.watch_interval
+    );
+
+    loop {
+        let mut timer = Timer::new();
+        let result =
+            watcher
+                .scan(&mut timer, pool)
+                .and_then(|changed| match (changed, watcher.stats()) {
+                    (Some(input_files), Some(stats)) => {
+                        write_output(
+                            config,
+                            stats.clone_without_custom(),
+                            input_files,
+                            watcher.rows(),
+                            &mut timer,
+                            pool,
+                        )?;
+                        timer.print_total();
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                });
+        if let Err(e) = result {
+            if watcher.is_aborted() {
+                return Err(e);
+            }
+            eprintln!("{}", e);
+        }
+        std::thread::sleep(Duration::from_secs(config.watch_interval as u64));
+    }
+}
+
+/// Loads the stats once and serves their leaderboards with an HTTP API on localhost, see [`tp_individual::handle_request`].
+/// Unless the input is a snapshot, the input directory keeps being scanned in the background as in watch or follow
+/// mode, and the leaderboards are replaced after each change while the requests keep being answered.
+fn serve(args: ServeArgs) -> Result<(), AnalyzerError> {
+    let Some(config) = resolve_config(args.run)? else {
+        return Ok(());
+    };
+    let pool = build_pool(config.threads)?;
+    let address = (Ipv4Addr::LOCALHOST, args.port);
+    let listener = TcpListener::bind(address)
+        .map_err(|e| AnalyzerError::io(format!("{}:{}", address.0, address.1), e))?;
+
+    let leaderboards = if is_snapshot(&config.input_dir) {
+        let mut timer = Timer::new();
+        let stats = load_snapshot(&config.input_dir)?;
+        timer.print_lap("Loading snapshot");
+        Arc::new(SharedLeaderboards::new(Leaderboards::of_run(
+            &stats,
+            &config,
+            RowCounts::default(),
+            timer.laps(),
+        )))
+    } else {
+        // The leaderboards of no deaths are replaced by the ones of the first scan, before serving any request
+        let custom = CustomAggregators::new();
+        let leaderboards = Arc::new(SharedLeaderboards::new(Leaderboards::new(
+            &Stats::new(&custom),
+            &config,
+        )));
+        let mut watcher = InputWatcher::new(config, custom)?;
+        watcher.publish_changes(&leaderboards, &pool)?;
+        watcher.spawn_publisher(leaderboards.clone(), pool);
+        leaderboards
+    };
+
+    println!(
+        "Serving the stats on http://{}, press Ctrl+C to stop",
+        listener
+            .local_addr()
+            .map_err(|e| AnalyzerError::io("listener", e))?
+    );
+    serve_http(listener, leaderboards);
+
+    Ok(())
+}
+
+/// Loads the deaths once and answers the commands of an interactive shell over their stats until `quit`,
+/// Ctrl+C or Ctrl+D, see [`ShellCommand`]. Player and weapon names are completed with Tab.
+fn explore(args: RunArgs) -> Result<(), AnalyzerError> {
+    let Some(config) = resolve_config(args)? else {
+        return Ok(());
+    };
+    let pool = build_pool(config.threads)?;
+    let mut timer = Timer::new();
+    let mut session = ExploreSession::load(config, &pool)?;
+    timer.print_lap("Loading deaths");
+
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new().map_err(shell_error)?;
+    editor.set_helper(Some(ShellHelper::new(session.unfiltered())));
+    println!(
+        "{} deaths loaded, type help to list the commands",
+        session.unfiltered().total_deaths()
+    );
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => return Ok(()),
+            Err(e) => return Err(shell_error(e)),
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        match ShellCommand::parse(&line) {
+            Ok(Some(ShellCommand::Quit)) => return Ok(()),
+            Ok(Some(command)) => match session.run(command, &pool) {
+                Ok(Some(output)) => println!("{}", output),
+                Ok(None) => {}
+                Err(message) => eprintln!("{}", message),
+            },
+            Ok(None) => {}
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+}
+
+/// Returns the IO error of the terminal of the shell.
+fn shell_error(e: ReadlineError) -> AnalyzerError {
+    match e {
+        ReadlineError::Io(e) => AnalyzerError::io("terminal", e),
+        e => AnalyzerError::io("terminal", std::io::Error::other(e)),
+    }
+}
+
+fn merge_snapshots(args: &MergeArgs) -> Result<(), AnalyzerError> {
+    let mut merged: Option<Stats> = None;
+    for input_file in &args.input_files {
+        let stats = load_snapshot(input_file)?;
+        match merged.as_mut() {
+            Some(merged) => merged.merge(stats),
+            None => merged = Some(stats),
+        }
+    }
+
+    if let Some(merged) = merged {
+        save_snapshot(&merged, &args.output_file)?;
+        println!(
+            "{} snapshots with {} deaths merged in {}",
+            args.input_files.len(),
+            merged.total_deaths(),
+            args.output_file
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads the input files, without writing any output.
+/// Returns a parse error if any line was discarded.
+fn validate(args: RunArgs) -> Result<(), AnalyzerError> {
+    let Some(config) = resolve_config(args)? else {
+        return Ok(());
+    };
+    let pool = build_pool(config.threads)?;
+    let input_files = find_input_files_in_dir(&config.input_dir)?;
+    let (stats, report) = read_stats(&config, input_files, &CustomAggregators::new(), &pool)?;
+
+    println!("{} deaths read", stats.total_deaths());
+
+    match report.error_count() {
+        0 => Ok(()),
+        errors => Err(AnalyzerError::Parse(format!(
+            "{} lines were discarded",
+            errors
+        ))),
+    }
+}
+
+/// Runs a query over the deaths of the input files, writing its result to the output file or the standard output.
+/// The summary of the discarded lines is only printed when writing to a file.
+fn query(args: QueryArgs) -> Result<(), AnalyzerError> {
+    let query = Query::parse(&args.query)
+        .map_err(|e| AnalyzerError::Config(format!("Invalid query: {}", e)))?;
+    let Some(config) = resolve_config(args.run)? else {
+        return Ok(());
+    };
+    let pool = build_pool(config.threads)?;
+
+    let result = run_query(&config, &query, args.output_file.is_some(), &pool)?;
+    result.save(args.output_file.as_deref(), args.format)?;
+    if let Some(output_file) = &args.output_file {
+        println!(
+            "{} rows saved as {} in {}",
+            result.rows().len(),
+            args.format,
+            output_file
+        );
+    }
+
+    Ok(())
+}
+
+fn sample(args: SampleArgs) -> Result<(), AnalyzerError> {
+    let (csv_files, _) = split_by_format(find_input_files_in_dir(&args.input_dir)?);
+    let written = sample_csv_files(&csv_files, args.rows, args.output_file.as_deref())?;
+
+    if let Some(output_file) = &args.output_file {
+        println!("{} records saved as CSV in {}", written, output_file);
+    }
+
+    Ok(())
+}
+
+/// Merges the snapshots given to `merge` into a single one, or concatenates the CSV files if they are not snapshots.
+fn merge(args: MergeArgs) -> Result<(), AnalyzerError> {
+    let snapshots = args
+        .input_files
+        .iter()
+        .filter(|file| is_snapshot(file))
+        .count();
+    if snapshots == args.input_files.len() {
+        return merge_snapshots(&args);
+    } else if snapshots > 0 {
+        return Err(AnalyzerError::Config(
+            "Snapshots cannot be merged with CSV files".to_string(),
+        ));
+    }
+
+    let input_files = args
+        .input_files
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    let written = merge_csv_files(&input_files, &args.output_file)?;
+
+    println!("{} records saved as CSV in {}", written, args.output_file);
+
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<(), AnalyzerError> {
+    let changes = diff_outputs(
+        &read_output(&args.old_output)?,
+        &read_output(&args.new_output)?,
+    );
+
+    if changes.is_empty() {
+        println!("The outputs are equal");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+
+    Ok(())
+}
+
+/// Returns the effective configuration of the run.
+/// With `--print-config`, it is printed instead and `None` is returned, so the run is skipped.
+fn resolve_config(args: RunArgs) -> Result<Option<AnalysisConfig>, AnalyzerError> {
+    let print_config = args.print_config;
+    let config = args.config()?;
+
+    if print_config {
+        println!("{}", serde_json::to_string_pretty(&config.json_display())?);
+        return Ok(None);
+    }
+
+    Ok(Some(config))
+}
+
+fn build_pool(threads: usize) -> Result<ThreadPool, AnalyzerError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| AnalyzerError::Config(format!("Error creating thread pool: {}", e)))
+}
@@ -0,0 +1,511 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    errors::AnalyzerError, fields::Field, filter::Filter, output_format::OutputFormat,
+    ranking::RankingMetric,
+};
+
+const TOML_EXTENSION: &str = "toml";
+const JSON_EXTENSION: &str = "json";
+
+const INPUT_DIR_KEY: &str = "input_dir";
+const THREADS_KEY: &str = "threads";
+const STRICT_KEY: &str = "strict";
+const REJECT_FILE_KEY: &str = "reject_file";
+const MAX_ERRORS_KEY: &str = "max_errors";
+const FILTER_KEY: &str = "filter";
+const OUTPUT_KEY: &str = "output";
+const FORMAT_KEY: &str = "format";
+const TOP_KILLERS_KEY: &str = "top_killers";
+const TOP_WEAPONS_KEY: &str = "top_weapons";
+const TOP_WEAPONS_OF_PLAYER_KEY: &str = "top_weapons_of_player";
+const RANK_PLAYERS_BY_KEY: &str = "rank_players_by";
+const RANK_WEAPONS_BY_KEY: &str = "rank_weapons_by";
+const GROUP_BY_KEY: &str = "group_by";
+const TOP_GROUPS_KEY: &str = "top_groups";
+const SNAPSHOT_KEY: &str = "snapshot";
+const INCREMENTAL_KEY: &str = "incremental";
+const WATCH_KEY: &str = "watch";
+const WATCH_INTERVAL_KEY: &str = "watch_interval";
+const FOLLOW_KEY: &str = "follow";
+const METRICS_KEY: &str = "metrics";
+const TUI_KEY: &str = "tui";
+const MAX_COUNT_KEY: &str = "max_count";
+
+const DEFAULT_TOP_PLAYERS_COUNT: usize = 10;
+const DEFAULT_TOP_WEAPONS_COUNT: usize = 10;
+const DEFAULT_TOP_WEAPONS_OF_PLAYER_COUNT: usize = 3;
+const DEFAULT_TOP_GROUPS_COUNT: usize = 10;
+const DEFAULT_WATCH_INTERVAL: usize = 2;
+const DEFAULT_MAX_COUNT: usize = 1000;
+
+/// Settings of an analysis run.
+///
+/// They can be read from a TOML or JSON file with flat keys, named like the options of the command line:
+///
+/// ```toml
+/// input_dir = "dataset/"
+/// threads = 8
+/// strict = true
+/// reject_file = "rejects.tsv"
+/// max_errors = 100
+/// filter = "map = 'MIRAMAR' and time > 1200"
+/// output = "output.json"
+/// format = "json"
+/// top_killers = 10
+/// top_weapons = 10
+/// top_weapons_of_player = 3
+/// rank_players_by = "kd"
+/// rank_weapons_by = "kills"
+/// group_by = "map,killed_by"
+/// top_groups = 10
+/// snapshot = "day.snapshot"
+/// incremental = "dataset.snapshot"
+/// watch = true
+/// watch_interval = 5
+/// follow = false
+/// metrics = "run.prom"
+/// tui = false
+/// max_count = 1000
+/// ```
+///
+/// Every key is optional, except for `input_dir` which must be given by the file or by the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisConfig {
+    pub input_dir: String,
+    pub threads: usize,
+    pub strict: bool,
+    pub reject_file: Option<String>,
+    pub max_errors: Option<usize>,
+    /// Only the deaths matching the filter are aggregated.
+    pub filter: Option<Filter>,
+    /// File of the JSON output, or directory of the CSV files. Defaults to [`OutputFormat::default_output`].
+    pub output_file: String,
+    pub format: OutputFormat,
+    pub top_players_count: usize,
+    pub top_weapons_count: usize,
+    pub top_weapons_of_player_count: usize,
+    pub players_ranking: RankingMetric,
+    pub weapons_ranking: RankingMetric,
+    /// Fields to group the deaths by, in order. Empty when the deaths are not grouped.
+    pub group_by: Vec<Field>,
+    pub top_groups_count: usize,
+    /// File to save the unfiltered stats to, to merge them with other runs later.
+    pub snapshot_file: Option<String>,
+    /// Snapshot with the manifest of the files already read. Only the new files are read and merged into it.
+    pub incremental_file: Option<String>,
+    /// Keep scanning the input directory, merging the new files and rewriting the output after each change.
+    pub watch: bool,
+    /// Seconds between two scans of the input directory in watch and follow modes.
+    pub watch_interval: usize,
+    /// Keep reading the rows appended to the CSV files, like `tail -F`, rewriting the output after each change.
+    pub follow: bool,
+    /// File to save the metrics of the run to, in the Prometheus text format.
+    pub metrics_file: Option<String>,
+    /// Show a live dashboard in the terminal while the deaths are read.
+    pub tui: bool,
+    /// Largest leaderboard that can be requested from the HTTP API of `serve`.
+    pub max_count: usize,
+}
+
+impl AnalysisConfig {
+    /// Creates the configuration of a run over the given directory, with the default settings.
+    /// By default, one thread is used per CPU.
+    pub fn new(input_dir: &str) -> Self {
+        Self {
+            input_dir: input_dir.to_string(),
+            threads: std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1),
+            strict: false,
+            reject_file: None,
+            max_errors: None,
+            filter: None,
+            output_file: OutputFormat::Json.default_output().to_string(),
+            format: OutputFormat::Json,
+            top_players_count: DEFAULT_TOP_PLAYERS_COUNT,
+            top_weapons_count: DEFAULT_TOP_WEAPONS_COUNT,
+            top_weapons_of_player_count: DEFAULT_TOP_WEAPONS_OF_PLAYER_COUNT,
+            players_ranking: RankingMetric::Kills,
+            weapons_ranking: RankingMetric::Kills,
+            group_by: Vec::new(),
+            top_groups_count: DEFAULT_TOP_GROUPS_COUNT,
+            snapshot_file: None,
+            incremental_file: None,
+            watch: false,
+            watch_interval: DEFAULT_WATCH_INTERVAL,
+            follow: false,
+            metrics_file: None,
+            tui: false,
+            max_count: DEFAULT_MAX_COUNT,
+        }
+    }
+
+    /// Loads the configuration from the given file, if any, with the `overrides` replacing its values.
+    /// The overrides use the same keys as the file, see [`AnalysisConfig`].
+    pub fn load(
+        config_file: Option<&str>,
+        overrides: Map<String, Value>,
+    ) -> Result<Self, AnalyzerError> {
+        let mut values = match config_file {
+            Some(config_file) => read_config_file(config_file)?,
+            None => Map::new(),
+        };
+        values.extend(overrides);
+
+        Self::from_json(&values)
+    }
+
+    /// Creates the configuration from its values, using the defaults for the missing ones.
+    /// Returns a configuration error if a key is unknown or a value is invalid.
+    pub fn from_json(values: &Map<String, Value>) -> Result<Self, AnalyzerError> {
+        let input_dir = match values.get(INPUT_DIR_KEY) {
+            Some(value) => string_value(INPUT_DIR_KEY, value)?,
+            None => return Err(AnalyzerError::Config("Missing input directory".to_string())),
+        };
+        let mut config = Self::new(&input_dir);
+
+        for (key, value) in values {
+            match key.as_str() {
+                INPUT_DIR_KEY => {}
+                THREADS_KEY => {
+                    config.threads = count_value(key, value)?;
+                    if config.threads == 0 {
+                        return Err(invalid_value(key, value));
+                    }
+                }
+                STRICT_KEY => {
+                    config.strict = value.as_bool().ok_or_else(|| invalid_value(key, value))?
+                }
+                REJECT_FILE_KEY => config.reject_file = Some(string_value(key, value)?),
+                MAX_ERRORS_KEY => config.max_errors = Some(count_value(key, value)?),
+                FILTER_KEY => {
+                    config.filter = Some(
+                        Filter::parse(&string_value(key, value)?)
+                            .map_err(|e| AnalyzerError::Config(format!("Invalid filter: {}", e)))?,
+                    )
+                }
+                OUTPUT_KEY => config.output_file = string_value(key, value)?,
+                FORMAT_KEY => config.format = parsed_value(key, value)?,
+                TOP_KILLERS_KEY => config.top_players_count = count_value(key, value)?,
+                TOP_WEAPONS_KEY => config.top_weapons_count = count_value(key, value)?,
+                TOP_WEAPONS_OF_PLAYER_KEY => {
+                    config.top_weapons_of_player_count = count_value(key, value)?
+                }
+                RANK_PLAYERS_BY_KEY => config.players_ranking = parsed_value(key, value)?,
+                RANK_WEAPONS_BY_KEY => {
+                    config.weapons_ranking = parsed_value(key, value)?;
+                    if !config.weapons_ranking.applies_to_weapons() {
+                        return Err(AnalyzerError::Config(format!(
+                            "Weapons cannot be ranked by {}",
+                            config.weapons_ranking
+                        )));
+                    }
+                }
+                GROUP_BY_KEY => config.group_by = fields_value(key, value)?,
+                TOP_GROUPS_KEY => config.top_groups_count = count_value(key, value)?,
+                SNAPSHOT_KEY => config.snapshot_file = Some(string_value(key, value)?),
+                INCREMENTAL_KEY => config.incremental_file = Some(string_value(key, value)?),
+                METRICS_KEY => config.metrics_file = Some(string_value(key, value)?),
+                WATCH_KEY => {
+                    config.watch = value.as_bool().ok_or_else(|| invalid_value(key, value))?
+                }
+                FOLLOW_KEY => {
+                    config.follow = value.as_bool().ok_or_else(|| invalid_value(key, value))?
+                }
+                TUI_KEY => config.tui = value.as_bool().ok_or_else(|| invalid_value(key, value))?,
+                WATCH_INTERVAL_KEY => {
+                    config.watch_interval = count_value(key, value)?;
+                    if config.watch_interval == 0 {
+                        return Err(invalid_value(key, value));
+                    }
+                }
+                MAX_COUNT_KEY => config.max_count = count_value(key, value)?,
+                _ => return Err(AnalyzerError::Config(format!("Unknown setting: {}", key))),
+            }
+        }
+
+        if !values.contains_key(OUTPUT_KEY) {
+            config.output_file = config.format.default_output().to_string();
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the configuration in a JSON format, which can be used as a configuration file.
+    pub fn json_display(&self) -> Value {
+        json!({
+            INPUT_DIR_KEY: self.input_dir,
+            THREADS_KEY: self.threads,
+            STRICT_KEY: self.strict,
+            REJECT_FILE_KEY: self.reject_file,
+            MAX_ERRORS_KEY: self.max_errors,
+            FILTER_KEY: self.filter.as_ref().map(|filter| filter.to_string()),
+            OUTPUT_KEY: self.output_file,
+            FORMAT_KEY: self.format.to_string(),
+            TOP_KILLERS_KEY: self.top_players_count,
+            TOP_WEAPONS_KEY: self.top_weapons_count,
+            TOP_WEAPONS_OF_PLAYER_KEY: self.top_weapons_of_player_count,
+            RANK_PLAYERS_BY_KEY: self.players_ranking.to_string(),
+            RANK_WEAPONS_BY_KEY: self.weapons_ranking.to_string(),
+            GROUP_BY_KEY: (!self.group_by.is_empty()).then(|| {
+                self.group_by
+                    .iter()
+                    .map(|field| field.name())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            TOP_GROUPS_KEY: self.top_groups_count,
+            SNAPSHOT_KEY: self.snapshot_file,
+            INCREMENTAL_KEY: self.incremental_file,
+            WATCH_KEY: self.watch,
+            WATCH_INTERVAL_KEY: self.watch_interval,
+            FOLLOW_KEY: self.follow,
+            METRICS_KEY: self.metrics_file,
+            TUI_KEY: self.tui,
+            MAX_COUNT_KEY: self.max_count,
+        })
+    }
+}
+
+/// Reads the values of a TOML or JSON configuration file, selected by its extension.
+/// Returns an IO error if the file cannot be read, or a configuration error if it cannot be parsed.
+pub fn read_config_file(path: &str) -> Result<Map<String, Value>, AnalyzerError> {
+    let content = std::fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
+
+    let values = match std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some(TOML_EXTENSION) => toml::from_str::<Value>(&content).map_err(|e| e.to_string()),
+        Some(JSON_EXTENSION) => serde_json::from_str::<Value>(&content).map_err(|e| e.to_string()),
+        _ => Err("expected a .toml or .json file".to_string()),
+    };
+
+    match values {
+        Ok(Value::Object(values)) => Ok(values
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .collect()),
+        Ok(_) => Err(AnalyzerError::Config(format!(
+            "Invalid configuration file {}: expected a table of settings",
+            path
+        ))),
+        Err(e) => Err(AnalyzerError::Config(format!(
+            "Invalid configuration file {}: {}",
+            path, e
+        ))),
+    }
+}
+
+fn invalid_value(key: &str, value: &Value) -> AnalyzerError {
+    AnalyzerError::Config(format!("Invalid value for {}: {}", key, value))
+}
+
+fn string_value(key: &str, value: &Value) -> Result<String, AnalyzerError> {
+    value
+        .as_str()
+        .map(|value| value.to_string())
+        .ok_or_else(|| invalid_value(key, value))
+}
+
+fn count_value(key: &str, value: &Value) -> Result<usize, AnalyzerError> {
+    value
+        .as_u64()
+        .map(|value| value as usize)
+        .ok_or_else(|| invalid_value(key, value))
+}
+
+/// Parses a list of fields, given as a comma separated string or as an array of names.
+fn fields_value(key: &str, value: &Value) -> Result<Vec<Field>, AnalyzerError> {
+    let names = match value {
+        Value::String(names) => names.split(',').map(str::trim).collect::<Vec<_>>(),
+        Value::Array(names) => names
+            .iter()
+            .map(|name| name.as_str().ok_or_else(|| invalid_value(key, value)))
+            .collect::<Result<_, _>>()?,
+        _ => return Err(invalid_value(key, value)),
+    };
+
+    names
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            name.parse()
+                .map_err(|e| AnalyzerError::Config(format!("Invalid value for {}: {}", key, e)))
+        })
+        .collect()
+}
+
+fn parsed_value<T: std::str::FromStr<Err = String>>(
+    key: &str,
+    value: &Value,
+) -> Result<T, AnalyzerError> {
+    string_value(key, value)?
+        .parse()
+        .map_err(|e| AnalyzerError::Config(format!("Invalid value for {}: {}", key, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn values(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(values) => values,
+            _ => panic!("Expected an object"),
+        }
+    }
+
+    fn write_file(dir: &TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = AnalysisConfig::from_json(&values(json!({"input_dir": "dataset"}))).unwrap();
+
+        assert_eq!(config, AnalysisConfig::new("dataset"));
+        assert_eq!(config.output_file, "output.json");
+        assert!(config.threads > 0);
+    }
+
+    #[test]
+    fn test_load_toml_with_overrides() {
+        let dir = TempDir::new().unwrap();
+        let config_file = write_file(
+            &dir,
+            "run.toml",
+            "input_dir = \"dataset\"\nthreads = 8\nstrict = true\ntop_killers = 5\nrank_players_by = \"kd\"\n",
+        );
+
+        let config = AnalysisConfig::load(
+            Some(&config_file),
+            values(json!({"threads": 2, "output": "stats.json"})),
+        )
+        .unwrap();
+
+        assert_eq!(config.input_dir, "dataset");
+        assert_eq!(config.threads, 2);
+        assert!(config.strict);
+        assert_eq!(config.top_players_count, 5);
+        assert_eq!(config.players_ranking, RankingMetric::KillDeathRatio);
+        assert_eq!(config.output_file, "stats.json");
+    }
+
+    #[test]
+    fn test_load_json() {
+        let dir = TempDir::new().unwrap();
+        let config_file = write_file(
+            &dir,
+            "run.json",
+            r#"{"input_dir": "dataset", "max_errors": 10, "reject_file": null}"#,
+        );
+
+        let config = AnalysisConfig::load(Some(&config_file), Map::new()).unwrap();
+
+        assert_eq!(config.max_errors, Some(10));
+        assert_eq!(config.reject_file, None);
+    }
+
+    #[test]
+    fn test_default_output_depends_on_format() {
+        let csv =
+            AnalysisConfig::from_json(&values(json!({"input_dir": "dataset", "format": "csv"})))
+                .unwrap();
+        let given = AnalysisConfig::from_json(&values(
+            json!({"input_dir": "dataset", "format": "csv", "output": "leaderboards"}),
+        ))
+        .unwrap();
+
+        assert_eq!(csv.output_file, ".");
+        assert_eq!(given.output_file, "leaderboards");
+    }
+
+    #[test]
+    fn test_json_display_roundtrip() {
+        let mut config = AnalysisConfig::new("dataset");
+        config.reject_file = Some("rejects.tsv".to_string());
+        config.weapons_ranking = RankingMetric::AverageDistance;
+        config.filter = Some(Filter::parse("not map in ('SANHOK') and time > 1200").unwrap());
+        config.group_by = vec![Field::Map, Field::KilledBy];
+        config.watch = true;
+        config.watch_interval = 5;
+        config.follow = true;
+        config.metrics_file = Some("run.prom".to_string());
+        config.tui = true;
+        config.max_count = 50;
+
+        let Value::Object(mut displayed) = config.json_display() else {
+            panic!("Expected an object");
+        };
+        displayed.retain(|_, value| !value.is_null());
+
+        assert_eq!(AnalysisConfig::from_json(&displayed).unwrap(), config);
+    }
+
+    #[test]
+    fn test_group_by_as_string_or_array() {
+        let from_string = AnalysisConfig::from_json(&values(
+            json!({"input_dir": "dataset", "group_by": "map, killed_by"}),
+        ))
+        .unwrap();
+        let from_array = AnalysisConfig::from_json(&values(
+            json!({"input_dir": "dataset", "group_by": ["map", "killed_by"], "top_groups": 3}),
+        ))
+        .unwrap();
+
+        assert_eq!(from_string.group_by, vec![Field::Map, Field::KilledBy]);
+        assert_eq!(from_array.group_by, from_string.group_by);
+        assert_eq!(from_array.top_groups_count, 3);
+    }
+
+    #[test]
+    fn test_invalid_values_are_config_errors() {
+        let invalid = [
+            json!({}),
+            json!({"input_dir": "dataset", "threads": 0}),
+            json!({"input_dir": "dataset", "threads": "four"}),
+            json!({"input_dir": "dataset", "top_killers": -1}),
+            json!({"input_dir": "dataset", "rank_weapons_by": "kd"}),
+            json!({"input_dir": "dataset", "format": "xml"}),
+            json!({"input_dir": "dataset", "filter": "map = "}),
+            json!({"input_dir": "dataset", "group_by": "map,weapon"}),
+            json!({"input_dir": "dataset", "group_by": ["map", 1]}),
+            json!({"input_dir": "dataset", "watch": "yes"}),
+            json!({"input_dir": "dataset", "watch_interval": 0}),
+            json!({"input_dir": "dataset", "unknown": 1}),
+        ];
+
+        for value in invalid {
+            assert!(matches!(
+                AnalysisConfig::from_json(&values(value)),
+                Err(AnalyzerError::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_config_files() {
+        let dir = TempDir::new().unwrap();
+        let invalid_toml = write_file(&dir, "run.toml", "threads = ");
+        let unknown_extension = write_file(&dir, "run.yaml", "threads: 4");
+
+        assert!(matches!(
+            read_config_file(&invalid_toml),
+            Err(AnalyzerError::Config(_))
+        ));
+        assert!(matches!(
+            read_config_file(&unknown_extension),
+            Err(AnalyzerError::Config(_))
+        ));
+        assert!(matches!(
+            read_config_file("missing.toml"),
+            Err(AnalyzerError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::errors::AnalyzerError;
+
+/// Name used in the errors when writing to the standard output.
+const STDOUT_NAME: &str = "standard output";
+
+/// Writes the header and the first `rows` records of the given CSV files into `output_path`,
+/// or into the standard output if no path is given.
+/// The files are read in order of their paths, so the sample is the same on every run.
+/// Returns the number of records written.
+pub fn sample_csv_files(
+    files: &[PathBuf],
+    rows: usize,
+    output_path: Option<&str>,
+) -> Result<usize, AnalyzerError> {
+    let (mut writer, output_name) = open_output(output_path)?;
+    let mut header_written = false;
+    let mut written = 0;
+
+    for file in sorted(files) {
+        if written == rows {
+            break;
+        }
+
+        let mut lines = open_lines(file)?;
+        if let Some(header) = lines.next() {
+            let header = header.map_err(|e| AnalyzerError::io(file, e))?;
+            if !header_written {
+                write_line(&mut writer, &header, &output_name)?;
+                header_written = true;
+            }
+        }
+
+        for line in lines.take(rows - written) {
+            let line = line.map_err(|e| AnalyzerError::io(file, e))?;
+            write_line(&mut writer, &line, &output_name)?;
+            written += 1;
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|e| AnalyzerError::io(&output_name, e))?;
+
+    Ok(written)
+}
+
+/// Concatenates the given CSV files into `output_path`, keeping a single header.
+/// All the files must have the same header, otherwise a parse error is returned.
+/// Returns the number of records written.
+pub fn merge_csv_files(files: &[PathBuf], output_path: &str) -> Result<usize, AnalyzerError> {
+    let mut merged_header: Option<String> = None;
+    let (mut writer, output_name) = open_output(Some(output_path))?;
+    let mut written = 0;
+
+    for file in files {
+        let mut lines = open_lines(file)?;
+        let Some(header) = lines.next() else {
+            continue;
+        };
+        let header = header.map_err(|e| AnalyzerError::io(file, e))?;
+
+        match &merged_header {
+            Some(merged_header) if *merged_header != header => {
+                return Err(AnalyzerError::Parse(format!(
+                    "The header of {} does not match the header of the other files",
+                    file.display()
+                )));
+            }
+            Some(_) => {}
+            None => {
+                write_line(&mut writer, &header, &output_name)?;
+                merged_header = Some(header);
+            }
+        }
+
+        for line in lines {
+            let line = line.map_err(|e| AnalyzerError::io(file, e))?;
+            write_line(&mut writer, &line, &output_name)?;
+            written += 1;
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|e| AnalyzerError::io(&output_name, e))?;
+
+    Ok(written)
+}
+
+/// Returns the given files sorted by their path.
+fn sorted(files: &[PathBuf]) -> Vec<&PathBuf> {
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Opens a file and returns an iterator over its lines.
+fn open_lines(file: &Path) -> Result<std::io::Lines<BufReader<File>>, AnalyzerError> {
+    File::open(file)
+        .map(|file| BufReader::new(file).lines())
+        .map_err(|e| AnalyzerError::io(file, e))
+}
+
+/// Opens the given output file, or the standard output if no path is given.
+/// Returns the writer and the name to use in the errors.
+pub(crate) fn open_output(
+    output_path: Option<&str>,
+) -> Result<(Box<dyn Write>, String), AnalyzerError> {
+    match output_path {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| AnalyzerError::io(path, e))?;
+            Ok((Box::new(BufWriter::new(file)), path.to_string()))
+        }
+        None => Ok((
+            Box::new(BufWriter::new(std::io::stdout())),
+            STDOUT_NAME.to_string(),
+        )),
+    }
+}
+
+pub(crate) fn write_line(
+    writer: &mut impl Write,
+    line: &str,
+    output_name: &str,
+) -> Result<(), AnalyzerError> {
+    writeln!(writer, "{}", line).map_err(|e| AnalyzerError::io(output_name, e))
+}
+
+/// Joins the given fields into a CSV line.
+/// Fields with commas, quotes or line breaks are quoted, doubling the quotes inside them.
+pub(crate) fn csv_line<S: AsRef<str>>(fields: impl IntoIterator<Item = S>) -> String {
+    fields
+        .into_iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const HEADER: &str = "killed_by,killer_name";
+
+    fn write_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn output_path(dir: &TempDir) -> String {
+        dir.path().join("output.csv").to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_sample_keeps_first_rows_in_path_order() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            write_file(&dir, "b.csv", &format!("{}\nM4,Player3\n", HEADER)),
+            write_file(
+                &dir,
+                "a.csv",
+                &format!("{}\nAK47,Player1\nAK47,Player2\n", HEADER),
+            ),
+        ];
+        let output = output_path(&dir);
+
+        let written = sample_csv_files(&files, 2, Some(&output)).unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            format!("{}\nAK47,Player1\nAK47,Player2\n", HEADER)
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_single_header() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            write_file(&dir, "a.csv", &format!("{}\nAK47,Player1\n", HEADER)),
+            write_file(&dir, "b.csv", &format!("{}\nM4,Player2\n", HEADER)),
+        ];
+        let output = output_path(&dir);
+
+        let written = merge_csv_files(&files, &output).unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            format!("{}\nAK47,Player1\nM4,Player2\n", HEADER)
+        );
+    }
+
+    #[test]
+    fn test_merge_different_headers_is_parse_error() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            write_file(&dir, "a.csv", &format!("{}\nAK47,Player1\n", HEADER)),
+            write_file(&dir, "b.csv", "weapon,player\nM4,Player2\n"),
+        ];
+
+        let result = merge_csv_files(&files, &output_path(&dir));
+
+        assert!(matches!(result, Err(AnalyzerError::Parse(_))));
+    }
+
+    #[test]
+    fn test_csv_line_quotes_special_fields() {
+        assert_eq!(csv_line(["AK47", "1.5"]), "AK47,1.5");
+        assert_eq!(
+            csv_line(["a,b", "say \"hi\"", ""]),
+            "\"a,b\",\"say \"\"hi\"\"\","
+        );
+    }
+
+    #[test]
+    fn test_merge_missing_file_is_io_error() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![dir.path().join("missing.csv")];
+
+        let result = merge_csv_files(&files, &output_path(&dir));
+
+        assert!(matches!(result, Err(AnalyzerError::Io { .. })));
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use crate::{
+    csv_tools::csv_line, errors::AnalyzerError, file_writting::write_atomically, stats::Stats,
+    weapon_stats::WeaponStats,
+};
+
+/// Name of the file with the top killers, inside the output directory.
+pub const TOP_KILLERS_FILE: &str = "top_killers.csv";
+/// Name of the file with the top weapons, inside the output directory.
+pub const TOP_WEAPONS_FILE: &str = "top_weapons.csv";
+
+/// Save the leaderboards as CSV files in the given directory, creating it if needed.
+///
+/// `top_killers.csv` has the rank, name and kills of each player, followed by a pair of columns
+/// per weapon with its name and the percentage of the kills of the player made with it.
+/// `top_weapons.csv` has the rank, name, share of the deaths and average distance of each weapon.
+/// The rows are written in order of rank, with the same values as [`Stats::json_display`].
+/// Returns an error if a file cannot be written.
+pub fn save_as_csv(stats: &Stats, output_dir: &str) -> Result<(), AnalyzerError> {
+    std::fs::create_dir_all(output_dir).map_err(|e| AnalyzerError::io(output_dir, e))?;
+
+    write_table(output_dir, TOP_KILLERS_FILE, &top_killers_lines(stats))?;
+    write_table(output_dir, TOP_WEAPONS_FILE, &top_weapons_lines(stats))?;
+    println!("Stats saved as CSV in {}", output_dir);
+
+    Ok(())
+}
+
+/// Returns the lines of `top_killers.csv`, starting with the header.
+/// There are as many weapon columns as weapons has the player with the most of them.
+fn top_killers_lines(stats: &Stats) -> Vec<String> {
+    let ranked = stats.players().ranked();
+    let weapon_columns = ranked
+        .iter()
+        .map(|(_, player_stats)| player_stats.weapons_percentage().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut header = vec![
+        "rank".to_string(),
+        "player".to_string(),
+        "kills".to_string(),
+    ];
+    for index in 1..=weapon_columns {
+        header.push(format!("weapon_{}", index));
+        header.push(format!("weapon_{}_percentage", index));
+    }
+
+    let rows = ranked
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, player_stats))| {
+            let mut fields = vec![
+                (index + 1).to_string(),
+                name.clone(),
+                player_stats.kills().to_string(),
+            ];
+            let weapons = player_stats.weapons_percentage();
+            for column in 0..weapon_columns {
+                match weapons.get(column) {
+                    Some((weapon_name, percentage)) => {
+                        fields.push(weapon_name.to_string());
+                        fields.push(percentage.to_string());
+                    }
+                    None => fields.extend([String::new(), String::new()]),
+                }
+            }
+            csv_line(fields)
+        });
+
+    std::iter::once(csv_line(header)).chain(rows).collect()
+}
+
+/// Returns the lines of `top_weapons.csv`, starting with the header.
+fn top_weapons_lines(stats: &Stats) -> Vec<String> {
+    let total_deaths = stats.total_deaths();
+
+    let ranked = stats.weapons().ranked();
+
+    let rows = ranked
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, weapon_stats))| {
+            weapon_line(index + 1, name, weapon_stats, total_deaths)
+        });
+
+    std::iter::once(csv_line(["rank", "weapon", "share", "average_distance"]))
+        .chain(rows)
+        .collect()
+}
+
+fn weapon_line(rank: usize, name: &str, weapon_stats: &WeaponStats, total_deaths: usize) -> String {
+    csv_line([
+        rank.to_string(),
+        name.to_string(),
+        weapon_stats.deaths_percentage(total_deaths).to_string(),
+        weapon_stats.rounded_average_distance().to_string(),
+    ])
+}
+
+fn write_table(output_dir: &str, file_name: &str, lines: &[String]) -> Result<(), AnalyzerError> {
+    let contents = lines
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+
+    write_atomically(Path::new(output_dir).join(file_name), contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPool, ThreadPoolBuilder};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(killer: &str, weapon: &str, distance: f64) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            None,
+            Some(0.0),
+            Some(0.0),
+            Some(distance),
+            Some(0.0),
+        )
+    }
+
+    fn stats() -> Stats {
+        let pool = pool();
+        let deaths = vec![
+            death("Player1", "AK47", 10.0),
+            death("Player1", "AK47", 20.0),
+            death("Player1", "M4A4", 30.0),
+            death("Player, 2", "M4A4", 40.0),
+        ];
+
+        let mut stats = Stats::from_deaths(deaths.into_par_iter(), &pool);
+        stats.filter_top_killers(10, 3, &pool);
+        stats.filter_top_weapons(10, &pool);
+        stats
+    }
+
+    #[test]
+    fn test_top_killers_lines() {
+        assert_eq!(
+            top_killers_lines(&stats()),
+            vec![
+                "rank,player,kills,weapon_1,weapon_1_percentage,weapon_2,weapon_2_percentage",
+                "1,Player1,3,AK47,66.67,M4A4,33.33",
+                "2,\"Player, 2\",1,M4A4,100,,",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_weapons_lines() {
+        assert_eq!(
+            top_weapons_lines(&stats()),
+            vec![
+                "rank,weapon,share,average_distance",
+                "1,AK47,50,15",
+                "2,M4A4,50,35",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_as_csv_creates_the_directory() {
+        let dir = TempDir::new().unwrap();
+        let output_dir = dir.path().join("leaderboards");
+
+        save_as_csv(&stats(), output_dir.to_str().unwrap()).unwrap();
+
+        let top_weapons = std::fs::read_to_string(output_dir.join(TOP_WEAPONS_FILE)).unwrap();
+        assert_eq!(top_weapons.lines().count(), 3);
+        assert!(output_dir.join(TOP_KILLERS_FILE).exists());
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{errors::AnalyzerError, ingestion_report::IngestionReport, stats::Stats};
+
+/// Time between two redraws of the dashboard.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+/// Players of the live leaderboard.
+const TOP_KILLERS_COUNT: usize = 10;
+/// Switches to the alternate screen of the terminal and hides the cursor.
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+/// Shows the cursor and switches back to the main screen, as it was before the dashboard.
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+/// Moves the cursor to the top left corner and clears the screen.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// Progress of a run at some point while its deaths are read, as drawn by the [`Dashboard`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub elapsed: Duration,
+    pub files_read: usize,
+    pub files_total: usize,
+    pub rows_read: usize,
+    pub rows_rejected: usize,
+    /// Rows read per second by each thread of the pool since the previous frame.
+    pub thread_rates: Vec<f64>,
+    /// Deaths of the stats merged so far.
+    pub deaths: usize,
+    /// Top killers of the stats merged so far, with their kills.
+    pub top_killers: Vec<(String, usize)>,
+}
+
+/// Live view of a run in the terminal, redrawn a few times per second from another thread while the deaths are read:
+/// the files read, the rows read per second by each thread, the rejected rows, and the top killers of the partial
+/// stats merged so far, see [`Stats::merge_deaths_into`].
+///
+/// The dashboard is drawn on the alternate screen of the terminal, and its last frame is printed on the main screen
+/// once it is stopped.
+pub struct Dashboard {
+    stopped: Arc<AtomicBool>,
+    drawer: JoinHandle<()>,
+}
+
+impl Dashboard {
+    /// Starts drawing the progress of the `report` and the `stats` while the `files_total` files of `input` are read.
+    /// The `report` should count the rows of each thread, see [`IngestionReport::with_thread_counters`].
+    /// Returns a configuration error if the standard output is not a terminal.
+    pub fn start(
+        input: &str,
+        report: Arc<IngestionReport>,
+        stats: Arc<Mutex<Stats>>,
+        files_total: usize,
+    ) -> Result<Self, AnalyzerError> {
+        if !std::io::stdout().is_terminal() {
+            return Err(AnalyzerError::Config(
+                "The dashboard needs a terminal as standard output".to_string(),
+            ));
+        }
+
+        let title = format!("Reading {}", input);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_drawer = stopped.clone();
+        let drawer = std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut previous = (start, report.rows_read_by_thread());
+            print!("{}", ENTER_SCREEN);
+
+            let frame = loop {
+                let is_last = stopped_drawer.load(Ordering::Relaxed);
+                let progress = take_progress(&report, &stats, files_total, start, &mut previous);
+                let frame = render_progress(&title, &progress);
+                print!("{}{}", CLEAR_SCREEN, frame);
+                let _ = std::io::stdout().flush();
+
+                if is_last {
+                    break frame;
+                }
+                std::thread::park_timeout(REFRESH_INTERVAL);
+            };
+
+            print!("{}{}", LEAVE_SCREEN, frame);
+            let _ = std::io::stdout().flush();
+        });
+
+        Ok(Self { stopped, drawer })
+    }
+
+    /// Draws the last frame and leaves the alternate screen, waiting for the drawing thread to finish.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.drawer.thread().unpark();
+        if self.drawer.join().is_err() {
+            eprintln!("Error drawing the dashboard");
+        }
+    }
+}
+
+/// Returns the current progress, with the rates of each thread since the `previous` rows read, which are updated.
+fn take_progress(
+    report: &IngestionReport,
+    stats: &Mutex<Stats>,
+    files_total: usize,
+    start: Instant,
+    previous: &mut (Instant, Vec<usize>),
+) -> Progress {
+    let now = Instant::now();
+    let rows_by_thread = report.rows_read_by_thread();
+    let seconds = (now - previous.0).as_secs_f64();
+    let thread_rates = rows_by_thread
+        .iter()
+        .zip(&previous.1)
+        .map(|(rows, previous_rows)| {
+            if seconds > 0.0 {
+                rows.saturating_sub(*previous_rows) as f64 / seconds
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    *previous = (now, rows_by_thread);
+
+    let (deaths, top_killers) = match stats.lock() {
+        Ok(stats) => (
+            stats.total_deaths(),
+            stats
+                .players()
+                .top(TOP_KILLERS_COUNT)
+                .into_iter()
+                .map(|(name, player_stats)| (name.clone(), player_stats.kills()))
+                .collect(),
+        ),
+        Err(_) => (0, Vec::new()),
+    };
+
+    Progress {
+        elapsed: now - start,
+        files_read: report.files_read(),
+        files_total,
+        rows_read: report.rows_read(),
+        rows_rejected: report.error_count(),
+        thread_rates,
+        deaths,
+        top_killers,
+    }
+}
+
+/// Returns a frame of the dashboard with the given title, as text lines to print on a cleared terminal.
+pub fn render_progress(title: &str, progress: &Progress) -> String {
+    let mut frame = String::new();
+
+    let _ = writeln!(
+        frame,
+        "{} ({:.1}s)\n",
+        title,
+        progress.elapsed.as_secs_f64()
+    );
+    let _ = writeln!(
+        frame,
+        "{:<10}{}/{}",
+        "Files", progress.files_read, progress.files_total
+    );
+    let _ = writeln!(
+        frame,
+        "{:<10}{} read, {} rejected",
+        "Rows", progress.rows_read, progress.rows_rejected
+    );
+    let _ = writeln!(frame, "{:<10}{} merged\n", "Deaths", progress.deaths);
+
+    let _ = writeln!(frame, "{:<10}Rows/s", "Thread");
+    for (thread, rate) in progress.thread_rates.iter().enumerate() {
+        let _ = writeln!(frame, "{:<10}{:.0}", thread, rate);
+    }
+
+    let _ = writeln!(frame, "\n{:<6}{:<32}Kills", "Rank", "Top killers");
+    for (index, (name, kills)) in progress.top_killers.iter().enumerate() {
+        let _ = writeln!(frame, "{:<6}{:<32}{}", index + 1, name, kills);
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_progress() {
+        let progress = Progress {
+            elapsed: Duration::from_millis(12340),
+            files_read: 1,
+            files_total: 3,
+            rows_read: 1500,
+            rows_rejected: 2,
+            thread_rates: vec![1000.4, 499.6],
+            deaths: 1498,
+            top_killers: vec![("Player1".to_string(), 20), ("Player2".to_string(), 7)],
+        };
+
+        assert_eq!(
+            render_progress("Reading dataset", &progress),
+            "Reading dataset (12.3s)
+
+Files     1/3
+Rows      1500 read, 2 rejected
+Deaths    1498 merged
+
+Thread    Rows/s
+0         1000
+1         500
+
+Rank  Top killers                     Kills
+1     Player1                         20
+2     Player2                         7
+"
+        );
+    }
+}
@@ -1,40 +1,159 @@
+use std::fmt;
+
+use crate::record_validation::validate_death;
+
+const FIELDS_COUNT: usize = 12;
+
+/// Reasons why a CSV record could not be turned into a `Death`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordError {
+    /// The record does not have the expected number of fields.
+    InvalidFieldCount(usize),
+    /// A numeric field is not empty but could not be parsed.
+    InvalidNumber { field: &'static str, value: String },
+    /// A required field is empty. Only checked in strict mode.
+    MissingValue { field: &'static str },
+    /// A field that cannot be negative has a negative value. Only checked in strict mode.
+    NegativeValue { field: &'static str, value: f64 },
+    /// A coordinate lies outside the bounds of its map. Only checked in strict mode.
+    OutOfBounds {
+        field: &'static str,
+        value: f64,
+        map: String,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::InvalidFieldCount(count) => {
+                write!(f, "Invalid number of fields: {}", count)
+            }
+            RecordError::InvalidNumber { field, value } => {
+                write!(f, "Invalid number in {}: {:?}", field, value)
+            }
+            RecordError::MissingValue { field } => write!(f, "Missing value in {}", field),
+            RecordError::NegativeValue { field, value } => {
+                write!(f, "Negative value in {}: {}", field, value)
+            }
+            RecordError::OutOfBounds { field, value, map } => {
+                write!(
+                    f,
+                    "Value of {} out of the bounds of {}: {}",
+                    field, map, value
+                )
+            }
+        }
+    }
+}
+
+/// Parses an optional numeric field.
+/// Empty fields are considered missing values, while non empty fields that cannot be parsed are an error,
+/// as well as the non finite values such as `NaN` or `inf`.
+pub fn parse_optional_number(field: &'static str, value: &str) -> Result<Option<f64>, RecordError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(Some(number)),
+        _ => Err(RecordError::InvalidNumber {
+            field,
+            value: value.to_string(),
+        }),
+    }
+}
+
 /// Struct to represent a death event in the game.
+/// Missing values of the dataset are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Death {
     pub killed_by: Option<String>,
     pub killer_name: Option<String>,
-    killer_position_x: Option<f64>,
-    killer_position_y: Option<f64>,
-    victim_position_x: Option<f64>,
-    victim_position_y: Option<f64>,
+    pub killer_placement: Option<f64>,
+    pub killer_position_x: Option<f64>,
+    pub killer_position_y: Option<f64>,
+    pub map: Option<String>,
+    pub match_id: Option<String>,
+    /// Seconds since the start of the match.
+    pub time: Option<f64>,
+    pub victim_name: Option<String>,
+    pub victim_placement: Option<f64>,
+    pub victim_position_x: Option<f64>,
+    pub victim_position_y: Option<f64>,
 }
 
 impl Death {
-    pub fn from_csv_record(record: String) -> Result<Self, String> {
+    /// Creates a new `Death` instance from the values needed to compute the stats.
+    /// The rest of the values are left missing.
+    pub fn new(
+        killed_by: Option<String>,
+        killer_name: Option<String>,
+        victim_name: Option<String>,
+        killer_position_x: Option<f64>,
+        killer_position_y: Option<f64>,
+        victim_position_x: Option<f64>,
+        victim_position_y: Option<f64>,
+    ) -> Self {
+        Self {
+            killed_by,
+            killer_name,
+            victim_name,
+            killer_position_x,
+            killer_position_y,
+            victim_position_x,
+            victim_position_y,
+            ..Self::default()
+        }
+    }
+
+    /// Parses a `Death` from a line of the deaths CSV.
+    /// Empty fields are treated as missing, but malformed numbers make the whole record invalid.
+    pub fn from_csv_record(record: String) -> Result<Self, RecordError> {
         let fields = record.split(',').collect::<Vec<_>>();
 
-        if fields.len() != 12 {
-            return Err(format!("Invalid number of fields: {}", fields.len()));
-        }
+        Self::from_fields(&fields)
+    }
 
-        let killed_by = (!fields[0].is_empty()).then(|| fields[0].to_string());
-        let killer_name = (!fields[1].is_empty()).then(|| fields[1].to_string());
+    /// Parses a `Death` from a line of the deaths CSV, rejecting records with suspicious values.
+    /// On top of the checks of `from_csv_record`, the values must pass [`Death::validate_strict`].
+    pub fn from_csv_record_strict(record: String) -> Result<Self, RecordError> {
+        let death = Self::from_csv_record(record)?;
+        death.validate_strict()?;
 
-        let killer_position_x = fields[3].parse::<f64>().ok();
-        let killer_position_y = fields[4].parse::<f64>().ok();
+        Ok(death)
+    }
 
-        let victim_position_x = fields[10].parse::<f64>().ok();
-        let victim_position_y = fields[11].parse::<f64>().ok();
+    /// Checks the values of the death in strict mode: the time is required, placements and time cannot be negative
+    /// and coordinates must lie within the bounds of the map.
+    pub fn validate_strict(&self) -> Result<(), RecordError> {
+        validate_death(self)
+    }
+
+    fn from_fields(fields: &[&str]) -> Result<Self, RecordError> {
+        if fields.len() != FIELDS_COUNT {
+            return Err(RecordError::InvalidFieldCount(fields.len()));
+        }
+
+        let text = |index: usize| (!fields[index].is_empty()).then(|| fields[index].to_string());
 
         Ok(Self {
-            killed_by,
-            killer_name,
-            killer_position_x,
-            killer_position_y,
-            victim_position_x,
-            victim_position_y,
+            killed_by: text(0),
+            killer_name: text(1),
+            killer_placement: parse_optional_number("killer_placement", fields[2])?,
+            killer_position_x: parse_optional_number("killer_position_x", fields[3])?,
+            killer_position_y: parse_optional_number("killer_position_y", fields[4])?,
+            map: text(5),
+            match_id: text(6),
+            time: parse_optional_number("time", fields[7])?,
+            victim_name: text(8),
+            victim_placement: parse_optional_number("victim_placement", fields[9])?,
+            victim_position_x: parse_optional_number("victim_position_x", fields[10])?,
+            victim_position_y: parse_optional_number("victim_position_y", fields[11])?,
         })
     }
 
+    /// Returns the distance between the killer and the victim, if both positions are known.
     pub fn distance(&self) -> Option<f64> {
         let killer_x = self.killer_position_x?;
         let killer_y = self.killer_position_y?;
@@ -61,6 +180,12 @@ mod tests {
 
         assert_eq!(death.killed_by, Some("AK47".to_string()));
         assert_eq!(death.killer_name, Some("Player1".to_string()));
+        assert_eq!(death.killer_placement, Some(1.0));
+        assert_eq!(death.map, Some("map".to_string()));
+        assert_eq!(death.match_id, Some("match-id".to_string()));
+        assert_eq!(death.time, Some(123.0));
+        assert_eq!(death.victim_name, Some("Player2".to_string()));
+        assert_eq!(death.victim_placement, Some(1.0));
         assert_eq!(death.killer_position_x, Some(0.0));
         assert_eq!(death.killer_position_y, Some(0.0));
         assert_eq!(death.victim_position_x, Some(100.0));
@@ -72,7 +197,38 @@ mod tests {
         let record = "AK47,Player1,1.0,0.0,0.0,map,match-id,123,Player2,1.0,100.0".to_string();
         let death = Death::from_csv_record(record);
 
-        assert!(death.is_err());
+        assert_eq!(death.err(), Some(RecordError::InvalidFieldCount(11)));
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let record = "AK47,Player1,1.0,0.0,abc,map,match-id,123,Player2,1.0,100.0,0.0".to_string();
+        let death = Death::from_csv_record(record);
+
+        assert_eq!(
+            death.err(),
+            Some(RecordError::InvalidNumber {
+                field: "killer_position_y",
+                value: "abc".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_non_finite_numbers_are_invalid() {
+        for value in ["NaN", "inf", "-inf"] {
+            let record = format!(
+                "AK47,Player1,1.0,0.0,0.0,map,match-id,{},Player2,1.0,100.0,0.0",
+                value
+            );
+            let error = Some(RecordError::InvalidNumber {
+                field: "time",
+                value: value.to_string(),
+            });
+
+            assert_eq!(Death::from_csv_record(record.clone()).err(), error);
+            assert_eq!(Death::from_csv_record_strict(record).err(), error);
+        }
     }
 
     #[test]
@@ -106,4 +262,26 @@ mod tests {
 
         assert_eq!(death.killer_name, None);
     }
+
+    #[test]
+    fn test_strict_accepts_complete_record() {
+        let record = COMPLETE_RECORD.to_string();
+        let death = Death::from_csv_record_strict(record);
+
+        assert!(death.is_ok());
+    }
+
+    #[test]
+    fn test_strict_rejects_what_lenient_accepts() {
+        let record = "AK47,Player1,-1.0,0.0,0.0,map,match-id,123,Player2,1.0,100.0,0.0";
+
+        assert!(Death::from_csv_record(record.to_string()).is_ok());
+        assert_eq!(
+            Death::from_csv_record_strict(record.to_string()).err(),
+            Some(RecordError::NegativeValue {
+                field: "killer_placement",
+                value: -1.0
+            })
+        );
+    }
 }
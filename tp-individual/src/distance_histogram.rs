@@ -0,0 +1,189 @@
+use serde_json::json;
+
+use crate::{
+    aggregator::Aggregator,
+    deaths::Death,
+    snapshot::{Decoder, Encoder},
+};
+
+/// A range of distances with the number of deaths in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceBucket {
+    /// Lower bound of the range, inclusive.
+    pub from: f64,
+    /// Upper bound of the range, exclusive.
+    pub to: f64,
+    pub count: usize,
+}
+
+/// Histogram of the distances of the deaths, in buckets bounded by 1, 2 and 5 times a power of ten
+/// (`[0, 1)`, `[1, 2)`, `[2, 5)`, `[5, 10)`, `[10, 20)`, ...).
+/// The buckets do not depend on the data, so partial histograms can be merged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DistanceHistogram {
+    counts: Vec<usize>,
+}
+
+impl DistanceHistogram {
+    /// Creates a new empty `DistanceHistogram` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a distance to its bucket. Negative distances are counted in the first bucket.
+    pub fn add(&mut self, distance: f64) {
+        let index = bucket_index(distance);
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+    }
+
+    /// Adds the counts of another histogram to this one.
+    pub fn merge(&mut self, other: &Self) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+    }
+
+    /// Returns the number of distances added.
+    pub fn count(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the buckets from `[0, 1)` up to the last one with distances, including the empty ones.
+    pub fn buckets(&self) -> Vec<DistanceBucket> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| DistanceBucket {
+                from: lower_bound(index),
+                to: lower_bound(index + 1),
+                count: *count,
+            })
+            .collect()
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.usize(self.counts.len());
+        for count in &self.counts {
+            encoder.usize(*count);
+        }
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        let length = decoder.usize()?;
+        let counts = (0..length)
+            .map(|_| decoder.usize())
+            .collect::<Result<_, _>>()?;
+        Ok(Self { counts })
+    }
+}
+
+/// Returns the index of the bucket of a distance: 0 below 1, then 3 buckets per power of ten.
+fn bucket_index(distance: f64) -> usize {
+    if distance.is_nan() || distance < 1.0 {
+        return 0;
+    }
+
+    let mut exponent = distance.log10().floor();
+    let mut mantissa = distance / 10f64.powf(exponent);
+    // The logarithm can be rounded down right below a power of ten.
+    if mantissa >= 10.0 {
+        exponent += 1.0;
+        mantissa /= 10.0;
+    }
+    let offset = if mantissa >= 5.0 {
+        2
+    } else if mantissa >= 2.0 {
+        1
+    } else {
+        0
+    };
+
+    1 + 3 * exponent as usize + offset
+}
+
+/// Returns the lower bound of the bucket with the given index.
+fn lower_bound(index: usize) -> f64 {
+    if index == 0 {
+        return 0.0;
+    }
+
+    let power = 10f64.powi(((index - 1) / 3) as i32);
+    match (index - 1) % 3 {
+        0 => power,
+        1 => 2.0 * power,
+        _ => 5.0 * power,
+    }
+}
+
+impl Aggregator for DistanceHistogram {
+    /// Adds the distance of the death, if both positions are known.
+    fn fold(&mut self, death: &Death) {
+        if let Some(distance) = death.distance() {
+            self.add(distance);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        DistanceHistogram::merge(self, &other);
+    }
+
+    fn render(&self) -> serde_json::Value {
+        self.buckets()
+            .into_iter()
+            .map(|bucket| json!({"from": bucket.from, "to": bucket.to, "count": bucket.count}))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let mut histogram = DistanceHistogram::new();
+        for distance in [0.5, 1.0, 3.0, 4.99, 5.0, 25.0] {
+            histogram.add(distance);
+        }
+
+        let buckets = histogram
+            .buckets()
+            .into_iter()
+            .map(|bucket| (bucket.from, bucket.to, bucket.count))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            buckets,
+            vec![
+                (0.0, 1.0, 1),
+                (1.0, 2.0, 1),
+                (2.0, 5.0, 2),
+                (5.0, 10.0, 1),
+                (10.0, 20.0, 0),
+                (20.0, 50.0, 1),
+            ]
+        );
+        assert_eq!(histogram.count(), 6);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = DistanceHistogram::new();
+        first.add(1.5);
+        let mut second = DistanceHistogram::new();
+        second.add(1.5);
+        second.add(150.0);
+
+        first.merge(&second);
+
+        assert_eq!(first.count(), 3);
+        assert_eq!(first.buckets()[1].count, 2);
+        assert_eq!(first.buckets().last().unwrap().from, 100.0);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt, path::Path};
+
+/// Errors that can stop the analysis.
+#[derive(Debug)]
+pub enum AnalyzerError {
+    /// A file or directory could not be read or written.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The input data could not be parsed.
+    Parse(String),
+    /// The arguments or configuration of the run are invalid.
+    Config(String),
+    /// The results could not be serialized.
+    Serialization(serde_json::Error),
+}
+
+impl AnalyzerError {
+    /// Creates an IO error for the given path.
+    pub fn io(path: impl AsRef<Path>, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.as_ref().display().to_string(),
+            source,
+        }
+    }
+
+    /// Returns the exit code of the process for this error.
+    /// Each kind of error has its own code, so scripts can tell them apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 2,
+            Self::Io { .. } => 3,
+            Self::Parse(_) => 4,
+            Self::Serialization(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for AnalyzerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "IO error on {}: {}", path, source),
+            Self::Parse(message) => write!(f, "Parse error: {}", message),
+            Self::Config(message) => write!(f, "Configuration error: {}", message),
+            Self::Serialization(e) => write!(f, "Serialization error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AnalyzerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Serialization(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for AnalyzerError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serialization(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            AnalyzerError::io("file", std::io::Error::other("error")),
+            AnalyzerError::Parse("error".to_string()),
+            AnalyzerError::Config("error".to_string()),
+            AnalyzerError::from(serde_json::from_str::<serde_json::Value>("{").unwrap_err()),
+        ];
+
+        let mut codes = errors.iter().map(|e| e.exit_code()).collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+    }
+
+    #[test]
+    fn test_io_display_includes_path() {
+        let error = AnalyzerError::io("dataset/", std::io::Error::other("not found"));
+
+        assert_eq!(error.to_string(), "IO error on dataset/: not found");
+    }
+}
@@ -0,0 +1,162 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::deaths::Death;
+
+/// Columns of a `Death` that can be referenced by name, e.g. in filters.
+/// Besides the columns of the dataset, `distance` is the distance between the killer and the victim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    KilledBy,
+    KillerName,
+    KillerPlacement,
+    KillerPositionX,
+    KillerPositionY,
+    Map,
+    MatchId,
+    Time,
+    VictimName,
+    VictimPlacement,
+    VictimPositionX,
+    VictimPositionY,
+    Distance,
+}
+
+/// Kinds of values held by the fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Number,
+}
+
+/// Value of a field of a `Death`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue<'a> {
+    Text(&'a str),
+    Number(f64),
+}
+
+const FIELDS: [(Field, &str); 13] = [
+    (Field::KilledBy, "killed_by"),
+    (Field::KillerName, "killer_name"),
+    (Field::KillerPlacement, "killer_placement"),
+    (Field::KillerPositionX, "killer_position_x"),
+    (Field::KillerPositionY, "killer_position_y"),
+    (Field::Map, "map"),
+    (Field::MatchId, "match_id"),
+    (Field::Time, "time"),
+    (Field::VictimName, "victim_name"),
+    (Field::VictimPlacement, "victim_placement"),
+    (Field::VictimPositionX, "victim_position_x"),
+    (Field::VictimPositionY, "victim_position_y"),
+    (Field::Distance, "distance"),
+];
+
+impl Field {
+    /// Returns the name of the field, as in the header of the dataset.
+    pub fn name(&self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|(field, _)| field == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default()
+    }
+
+    /// Returns the kind of values held by the field.
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Field::KilledBy
+            | Field::KillerName
+            | Field::Map
+            | Field::MatchId
+            | Field::VictimName => FieldKind::Text,
+            _ => FieldKind::Number,
+        }
+    }
+
+    /// Returns the value of the field in the given death, if it is not missing.
+    pub fn value<'a>(&self, death: &'a Death) -> Option<FieldValue<'a>> {
+        let text = |value: &'a Option<String>| value.as_deref().map(FieldValue::Text);
+
+        match self {
+            Field::KilledBy => text(&death.killed_by),
+            Field::KillerName => text(&death.killer_name),
+            Field::Map => text(&death.map),
+            Field::MatchId => text(&death.match_id),
+            Field::VictimName => text(&death.victim_name),
+            Field::KillerPlacement => death.killer_placement.map(FieldValue::Number),
+            Field::KillerPositionX => death.killer_position_x.map(FieldValue::Number),
+            Field::KillerPositionY => death.killer_position_y.map(FieldValue::Number),
+            Field::Time => death.time.map(FieldValue::Number),
+            Field::VictimPlacement => death.victim_placement.map(FieldValue::Number),
+            Field::VictimPositionX => death.victim_position_x.map(FieldValue::Number),
+            Field::VictimPositionY => death.victim_position_y.map(FieldValue::Number),
+            Field::Distance => death.distance().map(FieldValue::Number),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FIELDS
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(field, _)| *field)
+            .ok_or_else(|| format!("Unknown field: {}", s))
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FieldValue<'_> {
+    /// Compares two values of the same kind. Values of different kinds are not comparable.
+    pub fn compare(&self, other: &FieldValue) -> Option<Ordering> {
+        match (self, other) {
+            (FieldValue::Text(a), FieldValue::Text(b)) => Some(a.cmp(b)),
+            (FieldValue::Number(a), FieldValue::Number(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Text(value) => write!(f, "{}", value),
+            FieldValue::Number(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_names() {
+        for (field, name) in FIELDS {
+            assert_eq!(name.parse(), Ok(field));
+            assert_eq!(field.to_string(), name);
+        }
+        assert!("weapon".parse::<Field>().is_err());
+    }
+
+    #[test]
+    fn test_values() {
+        let death = Death {
+            map: Some("MIRAMAR".to_string()),
+            time: Some(1500.0),
+            ..Death::new(None, None, None, Some(0.0), Some(0.0), Some(3.0), Some(4.0))
+        };
+
+        assert_eq!(Field::Map.value(&death), Some(FieldValue::Text("MIRAMAR")));
+        assert_eq!(Field::Time.value(&death), Some(FieldValue::Number(1500.0)));
+        assert_eq!(Field::Distance.value(&death), Some(FieldValue::Number(5.0)));
+        assert_eq!(Field::KillerName.value(&death), None);
+    }
+}
@@ -2,66 +2,263 @@ use rayon::{prelude::*, ThreadPool};
 
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, ErrorKind},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+use crate::{deaths::RecordError, errors::AnalyzerError, ingestion_report::IngestionReport};
+
 const CSV_EXTENSION: &str = "csv";
+const PARQUET_EXTENSION: &str = "parquet";
+const ARROW_IPC_EXTENSIONS: [&str; 3] = ["arrow", "ipc", "feather"];
 
-/// Find all the CSV files in the given directory.
-/// If the directory cannot be read, the program will exit with an error message.
-pub fn find_csv_in_dir(input_path: &str) -> Vec<PathBuf> {
-    match std::fs::read_dir(input_path) {
-        Ok(files) => files
-            .filter_map(|file| {
-                let path = file.ok()?.path();
-                if path.extension()?.to_str()? == CSV_EXTENSION {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>(),
-        Err(e) => {
-            eprintln!("Error reading input path: {}", e);
-            std::process::exit(1);
+/// Formats of the input files, selected by their extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Csv,
+    Parquet,
+    ArrowIpc,
+}
+
+impl InputFormat {
+    /// Returns the format of the given file, if its extension is supported.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            CSV_EXTENSION => Some(Self::Csv),
+            PARQUET_EXTENSION => Some(Self::Parquet),
+            extension if ARROW_IPC_EXTENSIONS.contains(&extension) => Some(Self::ArrowIpc),
+            _ => None,
         }
     }
 }
 
+/// Find all the input files in the given directory: CSV, Parquet and Arrow IPC files.
+/// Files with any other extension are ignored.
+/// If the directory cannot be read, an IO error is returned.
+pub fn find_input_files_in_dir(input_path: &str) -> Result<Vec<PathBuf>, AnalyzerError> {
+    let files = std::fs::read_dir(input_path).map_err(|e| AnalyzerError::io(input_path, e))?;
+
+    Ok(files
+        .filter_map(|file| {
+            let path = file.ok()?.path();
+            InputFormat::from_path(&path).map(|_| path)
+        })
+        .collect::<Vec<_>>())
+}
+
 /// Read a CSV file and return a buffered reader.
-/// If the file cannot be read, the program will exit with an error message.
-fn read_csv_file(file: &Path) -> BufReader<File> {
-    match std::fs::File::open(file) {
-        Ok(file) => BufReader::new(file),
-        Err(e) => {
-            eprintln!("Error reading file {:?}: {}", file, e);
-            std::process::exit(1);
-        }
-    }
+/// If the file cannot be opened, an IO error is returned.
+fn read_csv_file(file: &Path) -> std::io::Result<BufReader<File>> {
+    std::fs::File::open(file).map(BufReader::new)
+}
+
+/// Splits the given files into the CSV files and the columnar (Parquet and Arrow IPC) files.
+pub fn split_by_format(files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    files
+        .into_iter()
+        .partition(|file| InputFormat::from_path(file) == Some(InputFormat::Csv))
 }
 
 /// Read all the lines of all the CSV files in parallel and process them with the given function.
 /// The function will return an iterator with the results.
-/// If the processing function returns an error, the line will be skipped.
-/// If the file cannot be read, the program will exit with an error message.
+/// If a line cannot be read or the processing function returns an error, the line will be skipped and recorded in the `report`.
+/// Once the `report` exceeds its maximum number of errors, no more lines will be processed.
+/// Each file is opened when its lines are first read. A file that cannot be opened or read aborts the `report`, so that
+/// the run fails with an IO error, see [`IngestionReport::add_unreadable_file`].
 pub fn read_csv_files<F, T>(
     files: Vec<PathBuf>,
     process_line: F,
+    report: Arc<IngestionReport>,
     pool: &ThreadPool,
 ) -> impl ParallelIterator<Item = T>
 where
-    F: Fn(String) -> Result<T, String> + Send + Sync,
+    F: Fn(String) -> Result<T, RecordError> + Send + Sync,
     T: Send,
 {
+    let files_report = report.clone();
     pool.install(|| {
         files
             .into_par_iter()
-            .flat_map(|file| {
-                let reader = read_csv_file(&file);
-                reader.lines().skip(1).par_bridge()
+            .flat_map(move |file| {
+                // The lines of the file share its path, to report them
+                let file = Arc::<Path>::from(file);
+                // No more files are opened once the report was aborted
+                let reader = if files_report.is_aborted() {
+                    None
+                } else {
+                    read_csv_file(&file)
+                        .map_err(|e| files_report.add_unreadable_file(&file, e))
+                        .ok()
+                };
+
+                let report = files_report.clone();
+                reader
+                    .into_iter()
+                    .flat_map(move |reader| csv_lines(file.clone(), reader, report.clone()))
+                    .par_bridge()
+            })
+            .map(move |(file, line_number, line)| {
+                process_record(&process_line, &report, &file, line_number, line)
             })
-            .filter_map(|line| line.ok())
-            .filter_map(move |line| process_line(line).ok())
+            .while_some()
+            .flatten()
+    })
+}
+
+/// Returns the lines of the file after its header, with their line number and the shared path of the file, and counts the file read in the `report`
+/// once they were all returned. A line that is not valid UTF-8 is returned as an error, but any other error means that
+/// the rest of the file cannot be read: the file is recorded as unreadable instead, and no more lines are returned.
+fn csv_lines(
+    file: Arc<Path>,
+    reader: BufReader<File>,
+    report: Arc<IngestionReport>,
+) -> impl Iterator<Item = (Arc<Path>, usize, std::io::Result<String>)> {
+    let mut lines = reader.lines().enumerate().skip(1);
+    let mut report = Some(report);
+    std::iter::from_fn(move || {
+        report.as_ref()?;
+        match lines.next() {
+            Some((_, Err(e))) if e.kind() != ErrorKind::InvalidData => {
+                report.take()?.add_unreadable_file(&file, e);
+                None
+            }
+            Some((index, line)) => Some((file.clone(), index + 1, line)),
+            None => {
+                report.take()?.add_file_read();
+                None
+            }
+        }
     })
 }
+
+/// Returns an empty iterator that counts a file read in the `report` the first time it is advanced.
+/// Chained after the records of a file, it tells when the file was read until its end.
+pub(crate) fn count_file_read<T>(report: Arc<IngestionReport>) -> impl Iterator<Item = T> {
+    let mut report = Some(report);
+    std::iter::from_fn(move || {
+        if let Some(report) = report.take() {
+            report.add_file_read();
+        }
+        None
+    })
+}
+
+/// Processes a line read from a CSV file, recording it in the `report` if it cannot be read or processed.
+/// Returns `None` once the `report` was aborted, so that no more lines are processed,
+/// or else the result of the processing if it succeeded.
+pub(crate) fn process_record<F, T>(
+    process_line: &F,
+    report: &IngestionReport,
+    file: &Path,
+    line_number: usize,
+    line: std::io::Result<String>,
+) -> Option<Option<T>>
+where
+    F: Fn(String) -> Result<T, RecordError>,
+{
+    if report.is_aborted() {
+        return None;
+    }
+    report.add_rows_read(1);
+
+    let line = match line {
+        Ok(line) => line,
+        Err(e) => {
+            report.add_read_error(file, line_number, &e);
+            return Some(None);
+        }
+    };
+
+    // The line is only kept around when it has to be written to the reject file
+    let rejected_line = report.writes_rejects().then(|| line.clone());
+
+    match process_line(line) {
+        Ok(value) => Some(Some(value)),
+        Err(e) => {
+            let line = rejected_line.unwrap_or_default();
+            report.add_record_error(file, line_number, &line, &e);
+            Some(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    #[test]
+    fn test_missing_input_dir_is_io_error() {
+        let result = find_input_files_in_dir("this/directory/does/not/exist");
+
+        assert!(matches!(result, Err(AnalyzerError::Io { .. })));
+    }
+
+    #[test]
+    fn test_missing_csv_file_fails_the_run() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        std::fs::write(&path, "header\nvalid\n").unwrap();
+        let report = Arc::new(IngestionReport::new(None, None).unwrap());
+        let files = vec![dir.path().join("missing.csv"), path];
+
+        let lines = read_csv_files(files, |line: String| Ok(line), report.clone(), &pool())
+            .collect::<Vec<_>>();
+
+        assert!(lines.is_empty());
+        assert!(report.is_aborted());
+        assert_eq!(report.files_read(), 0);
+        assert!(matches!(
+            report.check_errors(),
+            Err(AnalyzerError::Io { path, .. }) if path.ends_with("missing.csv")
+        ));
+    }
+
+    #[test]
+    fn test_unreadable_csv_file_fails_the_run() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        std::fs::create_dir(&path).unwrap();
+        let report = Arc::new(IngestionReport::new(None, None).unwrap());
+
+        let lines = read_csv_files(vec![path], |line: String| Ok(line), report.clone(), &pool())
+            .collect::<Vec<_>>();
+
+        assert!(lines.is_empty());
+        assert_eq!(report.files_read(), 0);
+        assert!(matches!(
+            report.check_errors(),
+            Err(AnalyzerError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rows_read_include_discarded_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        std::fs::write(&path, "header\nvalid\ninvalid\n").unwrap();
+        let report = Arc::new(IngestionReport::new(None, None).unwrap());
+
+        let lines = read_csv_files(
+            vec![path],
+            |line: String| match line.as_str() {
+                "valid" => Ok(line),
+                _ => Err(RecordError::InvalidFieldCount(1)),
+            },
+            report.clone(),
+            &pool(),
+        )
+        .collect::<Vec<_>>();
+
+        assert_eq!(lines, vec!["valid"]);
+        assert_eq!(report.rows_read(), 2);
+        assert_eq!(report.files_read(), 1);
+        assert_eq!(report.error_count(), 1);
+    }
+}
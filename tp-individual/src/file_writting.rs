@@ -0,0 +1,56 @@
+use std::{ffi::OsString, path::Path};
+
+use crate::errors::AnalyzerError;
+
+/// Extension added to the temporary files written before replacing the outputs.
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// Writes the contents into a temporary file next to `path`, and then renames it to `path`.
+/// As the rename is atomic, readers of the file see either its previous or its new contents, never a partial write.
+/// Returns an IO error if the file cannot be written or renamed.
+pub fn write_atomically(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), AnalyzerError> {
+    let path = path.as_ref();
+    let mut temporary_name = path.file_name().map(OsString::from).unwrap_or_default();
+    temporary_name.push(".");
+    temporary_name.push(TEMPORARY_EXTENSION);
+    let temporary_path = path.with_file_name(temporary_name);
+
+    std::fs::write(&temporary_path, contents).map_err(|e| AnalyzerError::io(&temporary_path, e))?;
+    std::fs::rename(&temporary_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temporary_path);
+        AnalyzerError::io(path, e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_write_atomically_replaces_the_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("output.json");
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, "new").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_atomically_into_a_missing_directory() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("missing").join("output.json");
+
+        assert!(matches!(
+            write_atomically(&path, "new"),
+            Err(AnalyzerError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,660 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    deaths::Death,
+    fields::{Field, FieldKind, FieldValue},
+};
+
+/// Operators to compare a field with a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Operators to match a text field with a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOp {
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+/// A constant value of a filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Text(String),
+    Number(f64),
+}
+
+/// A condition over the fields of a `Death`, used to keep only some deaths before aggregating them.
+///
+/// Filters are parsed from expressions such as
+/// `map = 'MIRAMAR' and time > 1200 and killed_by not in ('Bluezone', 'Falling')`:
+///
+/// - Comparisons of a field with a value: `=`, `!=`, `<`, `<=`, `>` and `>=`. Texts are quoted with `'` or `"`.
+/// - Lists of values: `field in (value, ...)` and `field not in (value, ...)`.
+/// - Matching of text fields: `contains`, `starts_with` and `ends_with`, e.g. `killer_name starts_with 'Bot'`.
+/// - Combinations with `and`, `or`, `not` and parentheses, where `and` binds tighter than `or`.
+///
+/// Keywords are case insensitive, while texts are compared exactly.
+/// Conditions over a missing value are false, so `not` them is true.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare {
+        field: Field,
+        op: CompareOp,
+        value: Literal,
+    },
+    In {
+        field: Field,
+        values: Vec<Literal>,
+    },
+    Matches {
+        field: Field,
+        op: TextOp,
+        pattern: String,
+    },
+}
+
+/// Error found while parsing a filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    pub message: String,
+    /// Position of the error in the expression, counting characters from 1.
+    pub position: usize,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl Filter {
+    /// Parses a filter expression.
+    /// Returns an error with its position if the expression is malformed,
+    /// references an unknown field or compares a field with a value of another kind.
+    pub fn parse(expression: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser::new(expression)?;
+
+        let filter = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some((_, position)) => Err(FilterError {
+                message: "Unexpected token".to_string(),
+                position,
+            }),
+        }
+    }
+
+    /// Returns `true` if the death fulfills the filter.
+    pub fn matches(&self, death: &Death) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(death) && right.matches(death),
+            Filter::Or(left, right) => left.matches(death) || right.matches(death),
+            Filter::Not(filter) => !filter.matches(death),
+            Filter::Compare { field, op, value } => field
+                .value(death)
+                .and_then(|field_value| field_value.compare(&value.as_field_value()))
+                .is_some_and(|ordering| match op {
+                    CompareOp::Equal => ordering.is_eq(),
+                    CompareOp::NotEqual => ordering.is_ne(),
+                    CompareOp::Less => ordering.is_lt(),
+                    CompareOp::LessOrEqual => ordering.is_le(),
+                    CompareOp::Greater => ordering.is_gt(),
+                    CompareOp::GreaterOrEqual => ordering.is_ge(),
+                }),
+            Filter::In { field, values } => field.value(death).is_some_and(|field_value| {
+                values.iter().any(|value| {
+                    field_value
+                        .compare(&value.as_field_value())
+                        .is_some_and(|o| o.is_eq())
+                })
+            }),
+            Filter::Matches { field, op, pattern } => match field.value(death) {
+                Some(FieldValue::Text(text)) => match op {
+                    TextOp::Contains => text.contains(pattern.as_str()),
+                    TextOp::StartsWith => text.starts_with(pattern.as_str()),
+                    TextOp::EndsWith => text.ends_with(pattern.as_str()),
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::And(left, right) => write!(f, "({} and {})", left, right),
+            Filter::Or(left, right) => write!(f, "({} or {})", left, right),
+            Filter::Not(filter) => write!(f, "not {}", filter),
+            Filter::Compare { field, op, value } => write!(f, "{} {} {}", field, op, value),
+            Filter::In { field, values } => {
+                let values = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "{} in ({})", field, values.join(", "))
+            }
+            Filter::Matches { field, op, pattern } => {
+                write!(f, "{} {} {}", field, op, Literal::Text(pattern.clone()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            CompareOp::Equal => "=",
+            CompareOp::NotEqual => "!=",
+            CompareOp::Less => "<",
+            CompareOp::LessOrEqual => "<=",
+            CompareOp::Greater => ">",
+            CompareOp::GreaterOrEqual => ">=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for TextOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TextOp::Contains => "contains",
+            TextOp::StartsWith => "starts_with",
+            TextOp::EndsWith => "ends_with",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Literal {
+    /// Returns the value as a field value, to compare it with the fields of a death.
+    pub fn as_field_value(&self) -> FieldValue<'_> {
+        match self {
+            Literal::Text(value) => FieldValue::Text(value),
+            Literal::Number(value) => FieldValue::Number(*value),
+        }
+    }
+
+    fn kind(&self) -> FieldKind {
+        match self {
+            Literal::Text(_) => FieldKind::Text,
+            Literal::Number(_) => FieldKind::Number,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Text(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Literal::Number(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Word(String),
+    Number(f64),
+    Text(String),
+    Operator(CompareOp),
+    LeftParen,
+    RightParen,
+    Comma,
+    Star,
+}
+
+/// Splits the expression into tokens, each with its position.
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, FilterError> {
+    let chars = expression.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let start = index;
+        let position = start + 1;
+        let c = chars[index];
+
+        let token = match c {
+            _ if c.is_whitespace() => {
+                index += 1;
+                continue;
+            }
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '*' => Token::Star,
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(index + 1).copied();
+                let (op, length) = match (c, next) {
+                    ('=', Some('=')) => (CompareOp::Equal, 2),
+                    ('=', _) => (CompareOp::Equal, 1),
+                    ('!', Some('=')) | ('<', Some('>')) => (CompareOp::NotEqual, 2),
+                    ('<', Some('=')) => (CompareOp::LessOrEqual, 2),
+                    ('<', _) => (CompareOp::Less, 1),
+                    ('>', Some('=')) => (CompareOp::GreaterOrEqual, 2),
+                    ('>', _) => (CompareOp::Greater, 1),
+                    _ => {
+                        return Err(FilterError {
+                            message: format!("Unexpected character '{}'", c),
+                            position,
+                        })
+                    }
+                };
+                index += length;
+                tokens.push((Token::Operator(op), position));
+                continue;
+            }
+            '\'' | '"' => {
+                let (text, length) = read_text(&chars[index..], position)?;
+                index += length;
+                tokens.push((Token::Text(text), position));
+                continue;
+            }
+            _ if c.is_ascii_digit() || c == '-' || c == '.' => {
+                while index + 1 < chars.len()
+                    && (chars[index + 1].is_ascii_alphanumeric() || chars[index + 1] == '.')
+                {
+                    index += 1;
+                }
+                let number = chars[start..=index].iter().collect::<String>();
+                Token::Number(number.parse().map_err(|_| FilterError {
+                    message: format!("Invalid number '{}'", number),
+                    position,
+                })?)
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                while index + 1 < chars.len()
+                    && (chars[index + 1].is_alphanumeric() || chars[index + 1] == '_')
+                {
+                    index += 1;
+                }
+                Token::Word(chars[start..=index].iter().collect())
+            }
+            _ => {
+                return Err(FilterError {
+                    message: format!("Unexpected character '{}'", c),
+                    position,
+                })
+            }
+        };
+
+        tokens.push((token, position));
+        index += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a quoted text at the start of `chars`, where a doubled quote stands for the quote itself.
+/// Returns the text and the number of characters read, including the quotes.
+fn read_text(chars: &[char], position: usize) -> Result<(String, usize), FilterError> {
+    let quote = chars[0];
+    let mut text = String::new();
+    let mut index = 1;
+
+    while index < chars.len() {
+        if chars[index] == quote {
+            if chars.get(index + 1) == Some(&quote) {
+                text.push(quote);
+                index += 2;
+                continue;
+            }
+            return Ok((text, index + 1));
+        }
+        text.push(chars[index]);
+        index += 1;
+    }
+
+    Err(FilterError {
+        message: "Unterminated text".to_string(),
+        position,
+    })
+}
+
+/// Recursive descent parser of filter expressions.
+/// It is also used to parse the filters inside queries, see [`crate::query`].
+pub(crate) struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Position reported for errors at the end of the expression.
+    end: usize,
+}
+
+impl Parser {
+    pub(crate) fn new(expression: &str) -> Result<Self, FilterError> {
+        Ok(Self {
+            tokens: tokenize(expression)?,
+            next: 0,
+            end: expression.chars().count() + 1,
+        })
+    }
+
+    pub(crate) fn peek(&self) -> Option<(&Token, usize)> {
+        self.tokens
+            .get(self.next)
+            .map(|(token, position)| (token, *position))
+    }
+
+    /// Returns the position of the next token, or of the end of the expression if there are no more tokens.
+    pub(crate) fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(_, position)| position)
+    }
+
+    pub(crate) fn advance(&mut self) -> Result<(Token, usize), FilterError> {
+        let token = self.tokens.get(self.next).cloned().ok_or(FilterError {
+            message: "Unexpected end of the expression".to_string(),
+            position: self.end,
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    /// Consumes the next token if it is the given keyword.
+    pub(crate) fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some((Token::Word(word), _)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    /// Consumes the next token if it is the given one.
+    pub(crate) fn accept(&mut self, token: &Token) -> bool {
+        let found = self.peek().is_some_and(|(next, _)| next == token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> Result<(), FilterError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(FilterError {
+                message: format!("Expected '{}'", keyword),
+                position: self.position(),
+            })
+        }
+    }
+
+    pub(crate) fn expect(&mut self, expected: Token, description: &str) -> Result<(), FilterError> {
+        let (token, position) = self.advance()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(FilterError {
+                message: format!("Expected {}", description),
+                position,
+            })
+        }
+    }
+
+    pub(crate) fn parse_or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.parse_and()?;
+        while self.accept_keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.parse_not()?;
+        while self.accept_keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_not()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter, FilterError> {
+        if self.accept_keyword("not") {
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+
+        if self.accept(&Token::LeftParen) {
+            let filter = self.parse_or()?;
+            self.expect(Token::RightParen, "')'")?;
+            return Ok(filter);
+        }
+
+        self.parse_condition()
+    }
+
+    /// Parses a condition over a single field.
+    fn parse_condition(&mut self) -> Result<Filter, FilterError> {
+        let (token, position) = self.advance()?;
+        let Token::Word(name) = token else {
+            return Err(FilterError {
+                message: "Expected a field".to_string(),
+                position,
+            });
+        };
+        let field = name
+            .parse::<Field>()
+            .map_err(|message| FilterError { message, position })?;
+
+        if self.accept_keyword("not") {
+            self.expect_keyword("in")?;
+            return Ok(Filter::Not(Box::new(self.parse_in(field)?)));
+        }
+        if self.accept_keyword("in") {
+            return self.parse_in(field);
+        }
+
+        let (token, position) = self.advance()?;
+        let text_op = match &token {
+            Token::Operator(op) => {
+                let value = self.parse_literal(field)?;
+                return Ok(Filter::Compare {
+                    field,
+                    op: *op,
+                    value,
+                });
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("contains") => TextOp::Contains,
+            Token::Word(word) if word.eq_ignore_ascii_case("starts_with") => TextOp::StartsWith,
+            Token::Word(word) if word.eq_ignore_ascii_case("ends_with") => TextOp::EndsWith,
+            _ => {
+                return Err(FilterError {
+                    message: "Expected an operator".to_string(),
+                    position,
+                })
+            }
+        };
+
+        if field.kind() != FieldKind::Text {
+            return Err(FilterError {
+                message: format!("{} can only be used with text fields", text_op),
+                position,
+            });
+        }
+        match self.parse_literal(field)? {
+            Literal::Text(pattern) => Ok(Filter::Matches {
+                field,
+                op: text_op,
+                pattern,
+            }),
+            Literal::Number(_) => unreachable!("text fields only accept texts"),
+        }
+    }
+
+    /// Parses the list of values of an `in` condition.
+    fn parse_in(&mut self, field: Field) -> Result<Filter, FilterError> {
+        self.expect(Token::LeftParen, "'('")?;
+        let mut values = vec![self.parse_literal(field)?];
+        loop {
+            let (token, position) = self.advance()?;
+            match token {
+                Token::Comma => values.push(self.parse_literal(field)?),
+                Token::RightParen => break,
+                _ => {
+                    return Err(FilterError {
+                        message: "Expected ',' or ')'".to_string(),
+                        position,
+                    })
+                }
+            }
+        }
+        Ok(Filter::In { field, values })
+    }
+
+    /// Parses a value to compare with the given field, checking that it is of the same kind.
+    fn parse_literal(&mut self, field: Field) -> Result<Literal, FilterError> {
+        let (token, position) = self.advance()?;
+        let literal = match token {
+            Token::Text(value) => Literal::Text(value),
+            Token::Number(value) => Literal::Number(value),
+            _ => {
+                return Err(FilterError {
+                    message: "Expected a value".to_string(),
+                    position,
+                })
+            }
+        };
+
+        if literal.kind() != field.kind() {
+            let expected = match field.kind() {
+                FieldKind::Text => "a text",
+                FieldKind::Number => "a number",
+            };
+            return Err(FilterError {
+                message: format!("{} must be compared with {}", field, expected),
+                position,
+            });
+        }
+
+        Ok(literal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn death(map: &str, time: f64, killed_by: &str, killer_name: &str) -> Death {
+        Death {
+            map: Some(map.to_string()),
+            time: Some(time),
+            killed_by: Some(killed_by.to_string()),
+            killer_name: Some(killer_name.to_string()),
+            ..Death::default()
+        }
+    }
+
+    fn matches(expression: &str, death: &Death) -> bool {
+        Filter::parse(expression).unwrap().matches(death)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let death = death("MIRAMAR", 1500.0, "AK47", "Player1");
+
+        assert!(matches("map = 'MIRAMAR'", &death));
+        assert!(matches("map == \"MIRAMAR\"", &death));
+        assert!(!matches("map != 'MIRAMAR'", &death));
+        assert!(matches("time > 1200", &death));
+        assert!(matches("time >= 1500", &death));
+        assert!(!matches("time < 1200", &death));
+        assert!(matches("time <= 1500.0", &death));
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let death = death("MIRAMAR", 1500.0, "Bluezone", "Player1");
+
+        assert!(!matches(
+            "map = 'MIRAMAR' and time > 1200 and not killed_by = 'Bluezone'",
+            &death
+        ));
+        assert!(matches(
+            "map = 'ERANGEL' or time > 1200 and killed_by = 'Bluezone'",
+            &death
+        ));
+        assert!(!matches(
+            "(map = 'ERANGEL' or time > 1200) and killed_by != 'Bluezone'",
+            &death
+        ));
+        assert!(matches("NOT map = 'ERANGEL' AND time > 0", &death));
+    }
+
+    #[test]
+    fn test_in_lists() {
+        let death = death("MIRAMAR", 1500.0, "Bluezone", "Player1");
+
+        assert!(matches("killed_by in ('Bluezone', 'Falling')", &death));
+        assert!(!matches("killed_by not in ('Bluezone', 'Falling')", &death));
+        assert!(matches("time in (1, 1500)", &death));
+    }
+
+    #[test]
+    fn test_text_matching() {
+        let death = death("MIRAMAR", 1500.0, "AK47", "BotPlayer_12");
+
+        assert!(matches("killer_name starts_with 'Bot'", &death));
+        assert!(matches("killer_name ends_with '_12'", &death));
+        assert!(matches("killer_name contains 'Player'", &death));
+        assert!(!matches("killer_name contains 'player'", &death));
+    }
+
+    #[test]
+    fn test_missing_values_never_match() {
+        let death = Death::default();
+
+        assert!(!matches("time > 0", &death));
+        assert!(!matches("map in ('MIRAMAR')", &death));
+        assert!(!matches("killer_name contains 'a'", &death));
+        assert!(matches("not time > 0", &death));
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let filter = Filter::parse(
+            "map = 'O''Brien' and (time > 1200 or killed_by not in ('Bluezone')) and victim_name starts_with 'a'",
+        )
+        .unwrap();
+
+        assert_eq!(filter.to_string().parse(), Ok(filter));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |expression: &str| Filter::parse(expression).unwrap_err();
+
+        assert_eq!(error("weapon = 'AK47'").position, 1);
+        assert_eq!(error("map = 1").position, 7);
+        assert_eq!(error("time > 'late'").position, 8);
+        assert_eq!(error("time contains '1'").position, 6);
+        assert_eq!(error("map = 'MIRAMAR").position, 7);
+        assert_eq!(error("(time > 1").position, 10);
+        assert_eq!(error("time > 1 time").position, 10);
+        assert_eq!(error("time ? 1").position, 6);
+        assert_eq!(error("map in ('A' 'B')").position, 13);
+        assert_eq!(error("").message, "Unexpected end of the expression");
+        assert_eq!(
+            error("map = 1").to_string(),
+            "map must be compared with a text at position 7"
+        );
+    }
+}
@@ -1,3 +1,64 @@
+use std::collections::BTreeMap;
+
+use crate::snapshot::{Decoder, Encoder};
+
+/// Relative error of the percentiles estimated by a [`PercentileSketch`].
+const SKETCH_RELATIVE_ERROR: f64 = 0.01;
+/// Values below this one are counted as 0 by a [`PercentileSketch`].
+const SKETCH_MIN_VALUE: f64 = 1e-6;
+
+/// Sum of floats with Neumaier's compensation.
+/// The rounding error of each addition is kept apart and added back at the end, which reduces the rounding error
+/// that depends on the order in which the values are added or the partial sums are merged, e.g. on how the deaths
+/// are split between the threads. The result can still differ in the last bits from one order to another.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    /// Creates a new `CompensatedSum` instance, with a sum of 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value to the sum.
+    pub fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - sum) + value;
+        } else {
+            self.compensation += (value - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    /// Adds another partial sum to this one.
+    pub fn merge(&mut self, other: CompensatedSum) {
+        self.add(other.sum);
+        self.add(other.compensation);
+    }
+
+    /// Returns the value of the sum.
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+
+    /// Writes the sum and its compensation, so the decoded sum keeps adding without losing precision.
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.f64(self.sum);
+        encoder.f64(self.compensation);
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            sum: decoder.f64()?,
+            compensation: decoder.f64()?,
+        })
+    }
+}
+
 /// Calculate the percentage of a count in relation to a total.
 pub fn calculate_percentage(count: usize, total: usize) -> f64 {
     if total == 0 {
@@ -16,9 +77,116 @@ pub fn calculate_average(distance: f64, count: usize) -> f64 {
     }
 }
 
+/// Calculate the given percentile (from 0 to 100) of the values, using the nearest rank.
+/// The values are sorted in place.
+pub fn calculate_percentile(values: &mut [f64], percentile: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.sort_by(f64::total_cmp);
+    let rank = (percentile / 100f64 * values.len() as f64).ceil() as usize;
+    let value = values[rank.clamp(1, values.len()) - 1];
+
+    (value * 100f64).round() / 100f64
+}
+
+/// Counts of non-negative values in buckets growing geometrically, to estimate their percentiles within
+/// [`SKETCH_RELATIVE_ERROR`] of the values given by [`calculate_percentile`], and never out of their exact range.
+/// Its size depends on the range of the values, not on how many of them were added.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PercentileSketch {
+    /// Number of values of each bucket, by index. The bucket `i` has the values in `(γ^(i-1), γ^i]`.
+    counts: BTreeMap<i32, usize>,
+    /// Number of values below [`SKETCH_MIN_VALUE`].
+    zero_count: usize,
+    count: usize,
+    min: f64,
+    max: f64,
+}
+
+impl PercentileSketch {
+    /// Returns the ratio γ between the bounds of a bucket.
+    fn gamma() -> f64 {
+        (1.0 + SKETCH_RELATIVE_ERROR) / (1.0 - SKETCH_RELATIVE_ERROR)
+    }
+
+    /// Adds a value to its bucket. Negative values are counted as 0.
+    pub fn add(&mut self, value: f64) {
+        let value = value.max(0.0);
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        if value < SKETCH_MIN_VALUE {
+            self.zero_count += 1;
+        } else {
+            let index = (value.ln() / Self::gamma().ln()).ceil() as i32;
+            *self.counts.entry(index).or_default() += 1;
+        }
+    }
+
+    /// Adds the counts of another sketch to this one.
+    pub fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.zero_count += other.zero_count;
+        for (index, count) in &other.counts {
+            *self.counts.entry(*index).or_default() += count;
+        }
+    }
+
+    /// Returns the number of values added.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Estimates the given percentile (from 0 to 100) of the values, using the nearest rank.
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let rank = (percentile / 100f64 * self.count as f64).ceil() as usize;
+        let rank = rank.clamp(1, self.count);
+        if rank <= self.zero_count {
+            return 0.0;
+        }
+
+        let mut seen = self.zero_count;
+        let gamma = Self::gamma();
+        let index = self
+            .counts
+            .iter()
+            .find(|(_, count)| {
+                seen += **count;
+                seen >= rank
+            })
+            .map_or(0, |(index, _)| *index);
+        // The value the closest to every value of the bucket, relatively.
+        let value = (2.0 * gamma.powi(index) / (gamma + 1.0)).clamp(self.min, self.max);
+
+        (value * 100f64).round() / 100f64
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::float_calculations::{calculate_average, calculate_percentage};
+    use crate::float_calculations::{
+        calculate_average, calculate_percentage, calculate_percentile, CompensatedSum,
+        PercentileSketch,
+    };
 
     #[test]
     fn test_calculate_percentage() {
@@ -35,4 +203,75 @@ mod tests {
         assert_eq!(calculate_average(1.0, 1), 1.0);
         assert_eq!(calculate_average(1.0, 3), 0.33);
     }
+
+    #[test]
+    fn test_calculate_percentile() {
+        assert_eq!(calculate_percentile(&mut [], 50.0), 0.0);
+        assert_eq!(calculate_percentile(&mut [3.0, 1.0, 2.0], 50.0), 2.0);
+        assert_eq!(calculate_percentile(&mut [4.0, 1.0, 3.0, 2.0], 50.0), 2.0);
+        assert_eq!(calculate_percentile(&mut [4.0, 1.0, 3.0, 2.0], 90.0), 4.0);
+        assert_eq!(calculate_percentile(&mut [4.0, 1.0], 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_compensated_sum_keeps_small_values() {
+        let mut naive = 0.0;
+        let mut compensated = CompensatedSum::new();
+        for value in [1e16, 1.0, -1e16, 1.0] {
+            naive += value;
+            compensated.add(value);
+        }
+
+        assert_eq!(naive, 1.0);
+        assert_eq!(compensated.value(), 2.0);
+    }
+
+    #[test]
+    fn test_compensated_sum_merge_does_not_depend_on_split() {
+        let values = (1..1000).map(|i| 1.0 / i as f64).collect::<Vec<_>>();
+
+        let mut whole = CompensatedSum::new();
+        values.iter().for_each(|value| whole.add(*value));
+
+        for split in [1, 10, 333, 998] {
+            let (left_values, right_values) = values.split_at(split);
+            let mut left = CompensatedSum::new();
+            let mut right = CompensatedSum::new();
+            left_values.iter().for_each(|value| left.add(*value));
+            right_values
+                .iter()
+                .rev()
+                .for_each(|value| right.add(*value));
+            right.merge(left);
+
+            assert_eq!(right.value(), whole.value());
+        }
+    }
+
+    #[test]
+    fn test_percentile_sketch_is_within_its_relative_error() {
+        let mut values = (0..1000)
+            .map(|index| (index * index) as f64 / 7.0)
+            .collect::<Vec<_>>();
+        let mut first = PercentileSketch::default();
+        let mut second = PercentileSketch::default();
+        let (first_values, second_values) = values.split_at(300);
+        first_values.iter().for_each(|value| first.add(*value));
+        second_values.iter().for_each(|value| second.add(*value));
+        first.merge(&second);
+
+        assert_eq!(first.count(), 1000);
+        assert_eq!(first.percentile(0.0), 0.0);
+        for percentile in [10.0, 50.0, 90.0, 100.0] {
+            let exact = calculate_percentile(&mut values, percentile);
+            let estimate = first.percentile(percentile);
+            assert!(
+                (estimate - exact).abs() <= exact * 0.01,
+                "{} {}",
+                estimate,
+                exact
+            );
+        }
+        assert_eq!(PercentileSketch::default().percentile(50.0), 0.0);
+    }
 }
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use rayon::ThreadPool;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    aggregator::Aggregator,
+    deaths::Death,
+    fields::Field,
+    float_calculations::{
+        calculate_average, calculate_percentage, CompensatedSum, PercentileSketch,
+    },
+    ranking::ranked_json,
+    sorting::{ranked_elements, retain_top_elements},
+};
+
+/// Key of the groups of deaths where the grouped field is missing.
+pub const MISSING_KEY: &str = "(missing)";
+
+/// Stats of a group of deaths, with its subgroups by the next field.
+#[derive(Debug, Clone, Default)]
+pub struct Group {
+    count: usize,
+    total_distance: CompensatedSum,
+    /// Distances of the deaths of the group, to estimate its percentiles.
+    /// Empty in the root of all the deaths, which is never rendered.
+    distances: PercentileSketch,
+    groups: HashMap<String, Group>,
+}
+
+impl Group {
+    /// Returns the number of deaths in the group.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the subgroups, keyed by the value of the next field.
+    pub fn groups(&self) -> &HashMap<String, Group> {
+        &self.groups
+    }
+
+    /// Adds a death to this group and to its subgroups by the given fields.
+    fn add_death(&mut self, death: &Death, distance: Option<f64>, fields: &[Field]) {
+        self.count += 1;
+        if let Some(distance) = distance {
+            self.total_distance.add(distance);
+            self.distances.add(distance);
+        }
+
+        self.add_to_subgroups(death, distance, fields);
+    }
+
+    /// Adds a death to the subgroup of its value of the first field, and to the subgroups of that one by the rest.
+    fn add_to_subgroups(&mut self, death: &Death, distance: Option<f64>, fields: &[Field]) {
+        if let Some((field, fields)) = fields.split_first() {
+            let key = field
+                .value(death)
+                .map_or_else(|| MISSING_KEY.to_string(), |value| value.to_string());
+
+            self.groups
+                .entry(key)
+                .or_default()
+                .add_death(death, distance, fields);
+        }
+    }
+
+    fn merge(&mut self, other: Group) {
+        self.count += other.count;
+        self.total_distance.merge(other.total_distance);
+        self.distances.merge(&other.distances);
+
+        for (key, other_group) in other.groups {
+            match self.groups.get_mut(&key) {
+                Some(group) => group.merge(other_group),
+                None => {
+                    self.groups.insert(key, other_group);
+                }
+            }
+        }
+    }
+
+    /// Returns the subgroups sorted from the largest to the smallest, with ties resolved alphabetically.
+    pub fn ranked_groups(&self) -> Vec<(&String, &Group)> {
+        ranked_elements(&self.groups, compare_groups)
+    }
+
+    /// Keeps the `count` largest subgroups at every level.
+    fn filter_top(&mut self, count: usize, pool: &ThreadPool) {
+        retain_top_elements(&mut self.groups, count, compare_groups, pool);
+
+        for group in self.groups.values_mut() {
+            group.filter_top(count, pool);
+        }
+    }
+
+    /// Returns the stats of the subgroups in a JSON format, keyed by their value and ordered by rank.
+    fn render_groups(&self) -> Value {
+        let groups = self
+            .ranked_groups()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (key, group))| {
+                (
+                    key.clone(),
+                    ranked_json(index + 1, group.render(self.count)),
+                )
+            })
+            .collect::<Map<_, _>>();
+
+        Value::Object(groups)
+    }
+
+    /// Returns the stats of the group in a JSON format.
+    /// The share is the percentage of the deaths of the parent group that belong to this group.
+    fn render(&self, parent_count: usize) -> Value {
+        let mut json = json!({
+            "count": self.count,
+            "share": calculate_percentage(self.count, parent_count),
+            "average_distance": calculate_average(self.total_distance.value(), self.distances.count()),
+            "median_distance": self.distances.percentile(50.0),
+            "p90_distance": self.distances.percentile(90.0),
+        });
+
+        if !self.groups.is_empty() {
+            if let Some(obj) = json.as_object_mut() {
+                obj.insert("groups".to_string(), self.render_groups());
+            }
+        }
+
+        json
+    }
+}
+
+/// Compares groups by their number of deaths.
+fn compare_groups(group_1: &Group, group_2: &Group) -> std::cmp::Ordering {
+    group_1.count.cmp(&group_2.count)
+}
+
+/// Aggregation of the deaths in nested groups, one level per field.
+/// For example, grouping by `map` and `killed_by` gives the stats of each map and, inside each map, of each weapon.
+///
+/// Each group has its number of deaths, its share of the deaths of the parent group,
+/// and the average, median and 90th percentile of the distances of its deaths.
+/// Deaths where a field is missing are grouped under [`MISSING_KEY`].
+///
+/// The median and 90th percentile are estimated within 1% from a sketch of the distances of each group, so the
+/// memory used grows with the number of groups, not with the number of deaths.
+#[derive(Debug, Clone, Default)]
+pub struct GroupByAggregator {
+    fields: Vec<Field>,
+    root: Group,
+}
+
+impl GroupByAggregator {
+    /// Creates a new empty `GroupByAggregator` instance grouping by the given fields, in order.
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self {
+            fields,
+            root: Group::default(),
+        }
+    }
+
+    /// Returns the fields used to group the deaths.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the groups by the first field, with their subgroups.
+    pub fn groups(&self) -> &HashMap<String, Group> {
+        &self.root.groups
+    }
+
+    /// Keeps the `count` largest groups at every level.
+    /// Ties are resolved alphabetically. The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top(&mut self, count: usize, pool: &ThreadPool) {
+        self.root.filter_top(count, pool);
+    }
+}
+
+impl Aggregator for GroupByAggregator {
+    fn fold(&mut self, death: &Death) {
+        // Only the count of the root is rendered, as the total of the shares of the first level.
+        self.root.count += 1;
+        self.root
+            .add_to_subgroups(death, death.distance(), &self.fields);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.root.merge(other.root);
+    }
+
+    fn render(&self) -> Value {
+        self.root.render_groups()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_json_diff::assert_json_eq;
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(map: Option<&str>, killed_by: &str, distance: f64) -> Death {
+        Death {
+            map: map.map(|map| map.to_string()),
+            ..Death::new(
+                Some(killed_by.to_string()),
+                None,
+                None,
+                Some(0.0),
+                Some(0.0),
+                Some(distance),
+                Some(0.0),
+            )
+        }
+    }
+
+    fn aggregator(deaths: &[Death]) -> GroupByAggregator {
+        let mut first = GroupByAggregator::new(vec![Field::Map, Field::KilledBy]);
+        let mut second = GroupByAggregator::new(vec![Field::Map, Field::KilledBy]);
+
+        let (first_deaths, second_deaths) = deaths.split_at(deaths.len() / 2);
+        first_deaths.iter().for_each(|death| first.fold(death));
+        second_deaths.iter().for_each(|death| second.fold(death));
+        first.merge(second);
+
+        first
+    }
+
+    #[test]
+    fn test_nested_groups() {
+        let aggregator = aggregator(&[
+            death(Some("MIRAMAR"), "AK47", 10.0),
+            death(Some("MIRAMAR"), "AK47", 30.0),
+            death(Some("MIRAMAR"), "M4A4", 20.0),
+            death(None, "AK47", 40.0),
+        ]);
+
+        // The medians are estimated within 1%, while the single distances and the largest ones are exact.
+        assert_json_eq!(
+            aggregator.render(),
+            json!({
+                "MIRAMAR": {
+                    "rank": 1,
+                    "count": 3,
+                    "share": 75.0,
+                    "average_distance": 20.0,
+                    "median_distance": 19.89,
+                    "p90_distance": 30.0,
+                    "groups": {
+                        "AK47": {
+                            "rank": 1,
+                            "count": 2,
+                            "share": 66.67,
+                            "average_distance": 20.0,
+                            "median_distance": 10.07,
+                            "p90_distance": 30.0,
+                        },
+                        "M4A4": {
+                            "rank": 2,
+                            "count": 1,
+                            "share": 33.33,
+                            "average_distance": 20.0,
+                            "median_distance": 20.0,
+                            "p90_distance": 20.0,
+                        },
+                    },
+                },
+                MISSING_KEY: {
+                    "rank": 2,
+                    "count": 1,
+                    "share": 25.0,
+                    "average_distance": 40.0,
+                    "median_distance": 40.0,
+                    "p90_distance": 40.0,
+                    "groups": {
+                        "AK47": {
+                            "rank": 1,
+                            "count": 1,
+                            "share": 100.0,
+                            "average_distance": 40.0,
+                            "median_distance": 40.0,
+                            "p90_distance": 40.0,
+                        },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_distances_are_only_kept_in_the_groups() {
+        let mut aggregator = aggregator(&[
+            death(Some("MIRAMAR"), "AK47", 10.0),
+            death(Some("ERANGEL"), "AK47", 20.0),
+        ]);
+        aggregator.merge(aggregator.clone());
+
+        assert_eq!(aggregator.root.count(), 4);
+        assert_eq!(aggregator.root.distances.count(), 0);
+        assert_eq!(aggregator.groups()["MIRAMAR"].distances.count(), 2);
+        assert_eq!(
+            aggregator.groups()["MIRAMAR"].groups()["AK47"].distances,
+            aggregator.groups()["MIRAMAR"].distances
+        );
+    }
+
+    #[test]
+    fn test_filter_top_at_every_level() {
+        let mut aggregator = aggregator(&[
+            death(Some("MIRAMAR"), "AK47", 10.0),
+            death(Some("MIRAMAR"), "AK47", 10.0),
+            death(Some("MIRAMAR"), "M4A4", 10.0),
+            death(Some("ERANGEL"), "AK47", 10.0),
+        ]);
+
+        aggregator.filter_top(1, &pool());
+
+        let groups = aggregator.groups();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["MIRAMAR"]);
+        assert_eq!(groups["MIRAMAR"].count(), 3);
+        assert_eq!(
+            groups["MIRAMAR"].groups().keys().collect::<Vec<_>>(),
+            vec!["AK47"]
+        );
+    }
+}
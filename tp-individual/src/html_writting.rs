@@ -0,0 +1,302 @@
+use std::fmt::Write;
+
+use crate::{
+    distance_histogram::DistanceHistogram, errors::AnalyzerError, file_writting::write_atomically,
+    stats::Stats, time_tracking::Timer, PADRON,
+};
+
+const STYLE: &str =
+    "body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+td.number { text-align: right; }
+svg text { font-size: 12px; fill: #222; }
+svg rect { fill: #4a7bb7; }";
+
+/// Height of each bar of a chart, including the space between bars.
+const ROW_HEIGHT: usize = 22;
+/// Width reserved for the labels on the left of the bars.
+const LABEL_WIDTH: usize = 160;
+/// Width of the longest bar of a chart.
+const BAR_WIDTH: usize = 400;
+/// Width reserved for the values on the right of the bars.
+const VALUE_WIDTH: usize = 80;
+
+/// Save the stats as a single HTML report in the given path, see [`html_report`].
+/// Returns an error if the file cannot be written.
+pub fn save_as_html(stats: &Stats, timer: &Timer, output_path: &str) -> Result<(), AnalyzerError> {
+    write_atomically(output_path, html_report(stats, timer))?;
+    println!("Stats saved as HTML in {}", output_path);
+
+    Ok(())
+}
+
+/// Returns a self-contained HTML report of the stats, with the leaderboards, a chart of the share of each weapon,
+/// the histograms of the distances of all the deaths and of each weapon, and the laps recorded by the `timer`.
+/// The charts are inline SVG and the report has no scripts nor external resources, so it can be opened offline.
+pub fn html_report(stats: &Stats, timer: &Timer) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>PUBG Death Analyzer report</title>\n");
+    let _ = writeln!(html, "<style>\n{}\n</style>\n</head>\n<body>", STYLE);
+    html.push_str("<h1>PUBG Death Analyzer report</h1>\n");
+    let _ = writeln!(
+        html,
+        "<p>{} deaths analyzed. Padron: {}.</p>",
+        stats.total_deaths(),
+        PADRON
+    );
+
+    write_top_killers(&mut html, stats);
+    write_top_weapons(&mut html, stats);
+    write_distances(&mut html, stats);
+    write_timing(&mut html, timer);
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn write_top_killers(html: &mut String, stats: &Stats) {
+    html.push_str("<h2>Top killers</h2>\n<table>\n");
+    html.push_str("<tr><th>Rank</th><th>Player</th><th>Kills</th><th>Top weapons</th></tr>\n");
+
+    for (index, (name, player_stats)) in stats.players().ranked().into_iter().enumerate() {
+        let weapons = player_stats
+            .weapons_percentage()
+            .into_iter()
+            .map(|(weapon_name, percentage)| format!("{} ({}%)", escape(weapon_name), percentage))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"number\">{}</td><td>{}</td><td class=\"number\">{}</td><td>{}</td></tr>",
+            index + 1,
+            escape(name),
+            player_stats.kills(),
+            weapons
+        );
+    }
+
+    html.push_str("</table>\n");
+}
+
+fn write_top_weapons(html: &mut String, stats: &Stats) {
+    let total_deaths = stats.total_deaths();
+    let ranked = stats.weapons().ranked();
+
+    html.push_str("<h2>Top weapons</h2>\n");
+    let shares = ranked
+        .iter()
+        .map(|(name, weapon_stats)| {
+            (
+                name.to_string(),
+                weapon_stats.deaths_percentage(total_deaths),
+            )
+        })
+        .collect::<Vec<_>>();
+    html.push_str(&bar_chart("Share of the deaths by weapon", &shares, "%"));
+
+    html.push_str("<table>\n");
+    html.push_str("<tr><th>Rank</th><th>Weapon</th><th>Share</th><th>Average distance</th></tr>\n");
+    for (index, (name, weapon_stats)) in ranked.into_iter().enumerate() {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"number\">{}</td><td>{}</td><td class=\"number\">{}%</td><td class=\"number\">{}</td></tr>",
+            index + 1,
+            escape(name),
+            weapon_stats.deaths_percentage(total_deaths),
+            weapon_stats.rounded_average_distance()
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn write_distances(html: &mut String, stats: &Stats) {
+    html.push_str("<h2>Distances</h2>\n");
+    html.push_str(&histogram_chart(
+        "Distance of all the deaths",
+        stats.distances(),
+    ));
+
+    for (name, weapon_stats) in stats.weapons().ranked() {
+        let _ = writeln!(html, "<h3>{}</h3>", escape(name));
+        html.push_str(&histogram_chart(
+            &format!("Distance of the deaths by {}", name),
+            weapon_stats.distances(),
+        ));
+    }
+}
+
+fn write_timing(html: &mut String, timer: &Timer) {
+    html.push_str("<h2>Timing</h2>\n<table>\n<tr><th>Stage</th><th>Duration</th></tr>\n");
+    for (message, lap) in timer.laps() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"number\">{:.2?}</td></tr>",
+            escape(message),
+            lap
+        );
+    }
+    let _ = writeln!(
+        html,
+        "<tr><th>Total</th><td class=\"number\">{:.2?}</td></tr>\n</table>",
+        timer.total()
+    );
+}
+
+/// Returns a chart with a bucket of the histogram per bar, from the shortest to the longest distances.
+fn histogram_chart(title: &str, histogram: &DistanceHistogram) -> String {
+    let bars = histogram
+        .buckets()
+        .into_iter()
+        .map(|bucket| {
+            (
+                format!("{} to {}", bucket.from, bucket.to),
+                bucket.count as f64,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    bar_chart(title, &bars, "")
+}
+
+/// Returns an SVG chart with a horizontal bar per label, proportional to its value.
+/// The value is written after each bar, followed by `unit`.
+fn bar_chart(title: &str, bars: &[(String, f64)], unit: &str) -> String {
+    if bars.is_empty() {
+        return "<p>No data.</p>\n".to_string();
+    }
+
+    let max_value = bars.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    let width = LABEL_WIDTH + BAR_WIDTH + VALUE_WIDTH;
+    let height = bars.len() * ROW_HEIGHT;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" role=\"img\" aria-label=\"{}\">",
+        width,
+        height,
+        escape(title)
+    );
+
+    for (index, (label, value)) in bars.iter().enumerate() {
+        let y = index * ROW_HEIGHT;
+        let bar_width = if max_value > 0.0 {
+            value / max_value * BAR_WIDTH as f64
+        } else {
+            0.0
+        };
+
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            LABEL_WIDTH - 8,
+            y + 15,
+            escape(label)
+        );
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"16\"/>",
+            LABEL_WIDTH,
+            y + 3,
+            bar_width
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{}\">{}{}</text>",
+            LABEL_WIDTH as f64 + bar_width + 6.0,
+            y + 15,
+            value,
+            unit
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Escapes the characters with a special meaning in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPool, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(killer: &str, weapon: &str, distance: f64) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            None,
+            Some(0.0),
+            Some(0.0),
+            Some(distance),
+            Some(0.0),
+        )
+    }
+
+    fn stats() -> Stats {
+        let deaths = vec![
+            death("Player1", "AK47", 10.0),
+            death("Player1", "AK47", 30.0),
+            death("<b>Player2</b>", "M4A4", 300.0),
+        ];
+
+        Stats::from_deaths(deaths.into_par_iter(), &pool())
+    }
+
+    #[test]
+    fn test_report_sections() {
+        let mut timer = Timer::new();
+        timer.print_lap("Processing deaths");
+
+        let html = html_report(&stats(), &timer);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        for section in ["Top killers", "Top weapons", "Distances", "Timing"] {
+            assert!(html.contains(&format!("<h2>{}</h2>", section)));
+        }
+        assert!(html.contains("<td>Processing deaths</td>"));
+        assert!(html.contains("<td>AK47 (100%)</td>"));
+        assert!(html.contains(">66.67%</text>"));
+        assert!(html.contains(">20 to 50</text>"));
+    }
+
+    #[test]
+    fn test_report_is_self_contained_and_escaped() {
+        let html = html_report(&stats(), &Timer::new());
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("href="));
+        assert!(!html.contains("src="));
+        assert!(!html.contains("<b>Player2</b>"));
+        assert!(html.contains("&lt;b&gt;Player2&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn test_bar_chart_without_data() {
+        assert_eq!(bar_chart("Empty", &[], "%"), "<p>No data.</p>\n");
+    }
+}
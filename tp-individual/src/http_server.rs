@@ -0,0 +1,185 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use crate::stats_api::{handle_request, ApiResponse, SharedLeaderboards};
+
+/// Maximum size of the request line and headers of a request.
+const MAX_HEADER_SIZE: u64 = 16 * 1024;
+/// Time to wait for a client to send its request before closing the connection.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Threads answering the connections.
+const WORKERS: usize = 4;
+/// Connections accepted and waiting for a worker. Once full, no more connections are accepted until a worker is free.
+const QUEUED_CONNECTIONS: usize = 64;
+
+/// Serves the HTTP API over the shared leaderboards, see [`handle_request`], answering the connections with a fixed
+/// number of worker threads. Only GET requests are answered, and each connection is closed after its response.
+/// The errors of a connection are printed, and the server keeps accepting connections.
+pub fn serve_http(listener: TcpListener, leaderboards: Arc<SharedLeaderboards>) {
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUED_CONNECTIONS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let receiver = receiver.clone();
+        let leaderboards = leaderboards.clone();
+        std::thread::spawn(move || loop {
+            // The lock is released once a connection is received, before answering it.
+            let stream = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(poisoned) => poisoned.into_inner().recv(),
+            };
+            let Ok(stream) = stream else {
+                break;
+            };
+            if let Err(e) = handle_connection(stream, &leaderboards) {
+                eprintln!("Error answering a request: {}", e);
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if sender.send(stream).is_err() {
+                    eprintln!("Error answering the connections: no worker is left");
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Error accepting a connection: {}", e),
+        }
+    }
+}
+
+/// Reads the request of the connection, ignoring its headers and body, and writes the response of the API.
+fn handle_connection(
+    mut stream: TcpStream,
+    leaderboards: &SharedLeaderboards,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_HEADER_SIZE));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let response = match request_line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["GET", target, _] => handle_request(&leaderboards.load(), target),
+        [_, _, _] => ApiResponse::error(405, "Only GET requests are allowed"),
+        _ => ApiResponse::error(400, "Invalid request"),
+    };
+
+    write_response(&mut stream, &response)
+}
+
+fn write_response(stream: &mut TcpStream, response: &ApiResponse) -> std::io::Result<()> {
+    let body = &response.body;
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::{config::AnalysisConfig, deaths::Death, stats::Stats, stats_api::Leaderboards};
+
+    fn start_server() -> std::net::SocketAddr {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let deaths = vec![Death::new(
+            Some("AK47".to_string()),
+            Some("Player1".to_string()),
+            None,
+            Some(0.0),
+            Some(0.0),
+            Some(10.0),
+            Some(0.0),
+        )];
+        let stats = Stats::from_deaths(deaths.into_par_iter(), &pool);
+        let leaderboards = Arc::new(SharedLeaderboards::new(Leaderboards::new(
+            &stats,
+            &AnalysisConfig::new("dataset"),
+        )));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve_http(listener, leaderboards));
+        address
+    }
+
+    fn request(address: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_get_request() {
+        let address = start_server();
+
+        let response = request(
+            address,
+            "GET /weapons/AK47 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(body).unwrap()["rank"],
+            1
+        );
+    }
+
+    #[test]
+    fn test_more_connections_than_workers() {
+        let address = start_server();
+
+        let clients = (0..WORKERS * 4)
+            .map(|_| {
+                std::thread::spawn(move || request(address, "GET /top_killers HTTP/1.1\r\n\r\n"))
+            })
+            .collect::<Vec<_>>();
+
+        for client in clients {
+            assert!(client.join().unwrap().starts_with("HTTP/1.1 200 OK\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let address = start_server();
+
+        assert!(request(address, "POST /top_killers HTTP/1.1\r\n\r\n")
+            .starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(request(address, "nonsense\r\n\r\n").starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
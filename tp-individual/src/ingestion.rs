@@ -0,0 +1,449 @@
+use rayon::{prelude::*, ThreadPool};
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    aggregator::CustomAggregators,
+    columnar_reading::read_columnar_files,
+    config::AnalysisConfig,
+    dashboard::Dashboard,
+    deaths::{Death, RecordError},
+    errors::AnalyzerError,
+    file_reading::{find_input_files_in_dir, read_csv_files, split_by_format},
+    ingestion_report::IngestionReport,
+    manifest::plan_update,
+    prometheus_writting::RowCounts,
+    query::{Query, QueryResult},
+    snapshot::{
+        is_snapshot, load_snapshot, load_snapshot_with_manifest, save_snapshot_with_manifest,
+    },
+    stats::Stats,
+};
+
+/// Creates the report of the lines discarded while reading the input files, with the reject file and the maximum
+/// number of errors of the configuration. The rows of each thread are also counted when the dashboard is shown.
+/// Returns an IO error if the reject file cannot be created.
+pub(crate) fn open_report(config: &AnalysisConfig) -> Result<Arc<IngestionReport>, AnalyzerError> {
+    IngestionReport::new(config.reject_file.as_deref(), config.max_errors)
+        .map(|report| {
+            if config.tui {
+                report.with_thread_counters(config.threads)
+            } else {
+                report
+            }
+        })
+        .map(Arc::new)
+        .map_err(|e| AnalyzerError::io(config.reject_file.as_deref().unwrap_or_default(), e))
+}
+
+/// Flushes the reject file once all the deaths were read, optionally printing the summary of the discarded lines.
+/// Returns a parse error if too many lines were discarded.
+pub(crate) fn close_report(
+    config: &AnalysisConfig,
+    report: &IngestionReport,
+    print_summary: bool,
+) -> Result<(), AnalyzerError> {
+    report
+        .flush()
+        .map_err(|e| AnalyzerError::io(config.reject_file.as_deref().unwrap_or_default(), e))?;
+    if print_summary {
+        report.print_summary();
+    }
+    report.check_errors()
+}
+
+/// Returns the deaths of the input files matching the filter of the configuration,
+/// recording the discarded lines in the `report`.
+pub fn read_deaths<'a>(
+    config: &'a AnalysisConfig,
+    input_files: Vec<PathBuf>,
+    report: &Arc<IngestionReport>,
+    pool: &ThreadPool,
+) -> impl ParallelIterator<Item = Death> + 'a {
+    let (csv_files, columnar_files) = split_by_format(input_files);
+    let filter = config.filter.as_ref();
+    read_csv_files(
+        csv_files,
+        parse_death_function(config),
+        report.clone(),
+        pool,
+    )
+    .chain(read_columnar_files(
+        columnar_files,
+        config.strict,
+        report.clone(),
+        pool,
+    ))
+    .filter(move |death| filter.is_none_or(|filter| filter.matches(death)))
+}
+
+/// Returns the function parsing the deaths of the CSV lines, which discards suspicious values in strict mode.
+pub(crate) fn parse_death_function(
+    config: &AnalysisConfig,
+) -> fn(String) -> Result<Death, RecordError> {
+    if config.strict {
+        Death::from_csv_record_strict
+    } else {
+        Death::from_csv_record
+    }
+}
+
+/// Reads the deaths of the input files into stats, with the `custom` metrics,
+/// printing the summary of the discarded lines.
+/// Returns a parse error if too many lines were discarded.
+pub fn read_stats(
+    config: &AnalysisConfig,
+    input_files: Vec<PathBuf>,
+    custom: &CustomAggregators,
+    pool: &ThreadPool,
+) -> Result<(Stats, Arc<IngestionReport>), AnalyzerError> {
+    let report = open_report(config)?;
+    let stats = read_stats_with_report(config, input_files, custom, &report, pool)?;
+
+    Ok((stats, report))
+}
+
+/// Reads the deaths of the input files into stats like [`read_stats`], recording the discarded lines in a `report`
+/// that may already have some, e.g. from the previous scans of the watch mode.
+pub fn read_stats_with_report(
+    config: &AnalysisConfig,
+    input_files: Vec<PathBuf>,
+    custom: &CustomAggregators,
+    report: &Arc<IngestionReport>,
+    pool: &ThreadPool,
+) -> Result<Stats, AnalyzerError> {
+    let deaths = read_deaths(config, input_files, report, pool);
+    let stats = Stats::from_deaths_with(deaths, custom, pool);
+    close_report(config, report, true)?;
+
+    Ok(stats)
+}
+
+/// Runs the query over the deaths of the input files of the configuration, see [`Query::run`].
+/// The summary of the discarded lines is only printed if `print_summary` is `true`.
+/// Returns a parse error if too many lines were discarded.
+pub fn run_query(
+    config: &AnalysisConfig,
+    query: &Query,
+    print_summary: bool,
+    pool: &ThreadPool,
+) -> Result<QueryResult, AnalyzerError> {
+    let report = open_report(config)?;
+    let input_files = find_input_files_in_dir(&config.input_dir)?;
+    let result = query.run(read_deaths(config, input_files, &report, pool), pool);
+    close_report(config, &report, print_summary)?;
+
+    Ok(result)
+}
+
+/// Loads the stats to analyze, from a snapshot if the input is one, or else by reading the deaths of the input files.
+/// Returns them with the input files and the number of rows read and discarded.
+pub fn load_stats(
+    config: &AnalysisConfig,
+    custom: &CustomAggregators,
+    pool: &ThreadPool,
+) -> Result<(Stats, Vec<PathBuf>, RowCounts), AnalyzerError> {
+    if is_snapshot(&config.input_dir) {
+        if config.filter.is_some() || !config.group_by.is_empty() {
+            return Err(AnalyzerError::Config(
+                "The deaths of a snapshot cannot be filtered nor grouped".to_string(),
+            ));
+        }
+        let stats = load_snapshot(&config.input_dir)?;
+        return Ok((
+            stats,
+            vec![PathBuf::from(&config.input_dir)],
+            RowCounts::default(),
+        ));
+    }
+
+    let input_files = find_input_files_in_dir(&config.input_dir)?;
+    if let Some(incremental_file) = &config.incremental_file {
+        if !config.group_by.is_empty() {
+            return Err(AnalyzerError::Config(
+                "The deaths cannot be grouped in incremental runs".to_string(),
+            ));
+        }
+        return read_incremental(config, incremental_file, input_files, pool);
+    }
+
+    let (stats, report) = read_stats(config, input_files.clone(), custom, pool)?;
+
+    Ok((stats, input_files, row_counts(&report)))
+}
+
+/// Returns a configuration error if the dashboard cannot show the run: it only shows the reading of the input files,
+/// so it cannot be combined with snapshots nor with the watch and follow modes.
+pub fn check_dashboard(config: &AnalysisConfig) -> Result<(), AnalyzerError> {
+    if config.watch || config.follow {
+        return Err(AnalyzerError::Config(
+            "The dashboard cannot be combined with the watch and follow modes".to_string(),
+        ));
+    }
+    if config.incremental_file.is_some() || is_snapshot(&config.input_dir) {
+        return Err(AnalyzerError::Config(
+            "The dashboard cannot be combined with snapshot inputs nor incremental runs"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the deaths of the input files like [`load_stats`], while a [`Dashboard`] shows the progress of the reading
+/// and the top killers of the partial stats merged so far.
+pub fn read_stats_with_dashboard(
+    config: &AnalysisConfig,
+    custom: &CustomAggregators,
+    pool: &ThreadPool,
+) -> Result<(Stats, Vec<PathBuf>, RowCounts), AnalyzerError> {
+    let input_files = find_input_files_in_dir(&config.input_dir)?;
+    let report = open_report(config)?;
+    let merged = Arc::new(Mutex::new(Stats::new(custom)));
+
+    let dashboard = Dashboard::start(
+        &config.input_dir,
+        report.clone(),
+        merged.clone(),
+        input_files.len(),
+    )?;
+    merge_deaths_into(config, input_files.clone(), custom, &report, &merged, pool);
+    dashboard.stop();
+    close_report(config, &report, true)?;
+
+    let stats = match Arc::try_unwrap(merged) {
+        Ok(merged) => merged.into_inner().unwrap_or_else(|e| e.into_inner()),
+        Err(_) => unreachable!("the dashboard was stopped"),
+    };
+
+    Ok((stats, input_files, row_counts(&report)))
+}
+
+/// Reads the deaths of the input files into the shared `merged` stats, which can be read while the deaths are
+/// still being aggregated, see [`Stats::merge_deaths_into`]. The `report` is not closed.
+pub fn merge_deaths_into(
+    config: &AnalysisConfig,
+    input_files: Vec<PathBuf>,
+    custom: &CustomAggregators,
+    report: &Arc<IngestionReport>,
+    merged: &Mutex<Stats>,
+    pool: &ThreadPool,
+) {
+    let deaths = read_deaths(config, input_files, report, pool);
+    Stats::merge_deaths_into(merged, deaths, custom, pool);
+}
+
+/// Brings the snapshot of an incremental run up to date with the input files, and saves it with their manifest.
+/// Only the new files are read, unless a file was modified or removed or the settings changed,
+/// in which case the stats are rebuilt from every file.
+pub fn read_incremental(
+    config: &AnalysisConfig,
+    snapshot_file: &str,
+    input_files: Vec<PathBuf>,
+    pool: &ThreadPool,
+) -> Result<(Stats, Vec<PathBuf>, RowCounts), AnalyzerError> {
+    let previous = if Path::new(snapshot_file).exists() {
+        Some(load_snapshot_with_manifest(snapshot_file)?)
+    } else {
+        None
+    };
+    let update = plan_update(
+        previous.as_ref().map(|(_, manifest)| manifest),
+        &ingestion_settings(config),
+        &input_files,
+    )?;
+
+    let files_read = update.files_to_read.len();
+    let (new_stats, report) = read_stats(
+        config,
+        update.files_to_read,
+        &CustomAggregators::new(),
+        pool,
+    )?;
+    let stats = match (update.rebuild_reason, previous) {
+        (None, Some((mut stats, _))) => {
+            println!("{} new files merged into {}", files_read, snapshot_file);
+            stats.merge(new_stats);
+            stats
+        }
+        (reason, _) => {
+            println!(
+                "Rebuilding {} from {} files: {}",
+                snapshot_file,
+                files_read,
+                reason.unwrap_or_default()
+            );
+            new_stats
+        }
+    };
+
+    save_snapshot_with_manifest(&stats, &update.manifest, snapshot_file)?;
+
+    Ok((stats, input_files, row_counts(&report)))
+}
+
+/// Returns the settings that change which deaths are read from the files, to rebuild the snapshots made with others.
+pub fn ingestion_settings(config: &AnalysisConfig) -> String {
+    format!(
+        "strict={} filter={}",
+        config.strict,
+        config
+            .filter
+            .as_ref()
+            .map(|filter| filter.to_string())
+            .unwrap_or_default()
+    )
+}
+
+/// Returns the rows read and discarded while reading the deaths of the `report`.
+pub fn row_counts(report: &IngestionReport) -> RowCounts {
+    RowCounts {
+        read: report.rows_read(),
+        rejected: report.error_count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::ThreadPoolBuilder;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{fields::Field, filter::Filter};
+
+    const HEADER: &str = "killed_by,killer_name,killer_placement,killer_position_x,killer_position_y,map,match_id,time,victim_name,victim_placement,victim_position_x,victim_position_y";
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn write_deaths(path: &Path, killers: &[&str]) {
+        let records = killers
+            .iter()
+            .map(|killer| format!("AK47,{},1,0,0,ERANGEL,m,10,Victim,2,10,0\n", killer))
+            .collect::<String>();
+        std::fs::write(path, format!("{}\n{}", HEADER, records)).unwrap();
+    }
+
+    #[test]
+    fn test_read_stats_with_the_settings_of_the_config() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        std::fs::write(
+            &path,
+            format!(
+                "{}\nAK47,Player1,1,0,0,ERANGEL,m,10,Player2,2,10,0\nAWM,Player2,1,0,0,MIRAMAR,m,-5,Player1,2,10,0\ninvalid\n",
+                HEADER
+            ),
+        )
+        .unwrap();
+        let mut config = AnalysisConfig::new(dir.path().to_str().unwrap());
+        config.strict = true;
+        config.filter = Some(Filter::parse("map = 'ERANGEL'").unwrap());
+
+        let (stats, report) =
+            read_stats(&config, vec![path], &CustomAggregators::new(), &pool()).unwrap();
+
+        assert_eq!(stats.total_deaths(), 1);
+        assert_eq!(
+            row_counts(&report),
+            RowCounts {
+                read: 3,
+                rejected: 2
+            }
+        );
+        assert_eq!(
+            ingestion_settings(&config),
+            "strict=true filter=map = 'ERANGEL'"
+        );
+    }
+
+    #[test]
+    fn test_deaths_are_merged_into_the_shared_stats() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        write_deaths(&path, &["Player1", "Player2", "Player1"]);
+        let mut config = AnalysisConfig::new(dir.path().to_str().unwrap());
+        config.tui = true;
+        config.threads = 1;
+        let custom = CustomAggregators::new();
+        let report = open_report(&config).unwrap();
+        let merged = Mutex::new(Stats::new(&custom));
+
+        merge_deaths_into(&config, vec![path], &custom, &report, &merged, &pool());
+
+        assert_eq!(merged.lock().unwrap().total_deaths(), 3);
+        assert_eq!(report.rows_read_by_thread(), vec![3]);
+        assert_eq!(report.files_read(), 1);
+    }
+
+    #[test]
+    fn test_dashboard_only_shows_the_reading_of_input_files() {
+        let dir = TempDir::new().unwrap();
+        let config = AnalysisConfig::new(dir.path().to_str().unwrap());
+        assert!(check_dashboard(&config).is_ok());
+
+        let mut watched = config.clone();
+        watched.follow = true;
+        let mut incremental = config.clone();
+        incremental.incremental_file = Some("deaths.snapshot".to_string());
+        let snapshot = AnalysisConfig::new("deaths.snapshot");
+
+        for config in [watched, incremental, snapshot] {
+            assert!(matches!(
+                check_dashboard(&config),
+                Err(AnalyzerError::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_incremental_runs_merge_new_files_and_rebuild_modified_ones() {
+        let dir = TempDir::new().unwrap();
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir(&input_dir).unwrap();
+        let snapshot_path = dir.path().join("deaths.snapshot");
+        let mut config = AnalysisConfig::new(input_dir.to_str().unwrap());
+        config.incremental_file = Some(snapshot_path.to_str().unwrap().to_string());
+        let custom = CustomAggregators::new();
+        let run = || load_stats(&config, &custom, &pool()).unwrap();
+
+        let day_1 = input_dir.join("day-1.csv");
+        write_deaths(&day_1, &["Player1", "Player2"]);
+        let (stats, input_files, rows) = run();
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(input_files, vec![day_1.clone()]);
+        assert_eq!(rows.read, 2);
+
+        write_deaths(&input_dir.join("day-2.csv"), &["Player3"]);
+        let (stats, input_files, rows) = run();
+        assert_eq!(stats.total_deaths(), 3);
+        assert_eq!(input_files.len(), 2);
+        assert_eq!(rows.read, 1);
+
+        write_deaths(&day_1, &["Player1"]);
+        let (stats, _, rows) = run();
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(rows.read, 2);
+
+        let snapshot = AnalysisConfig::new(snapshot_path.to_str().unwrap());
+        let (stats, input_files, _) = load_stats(&snapshot, &custom, &pool()).unwrap();
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(input_files, vec![snapshot_path]);
+    }
+
+    #[test]
+    fn test_incremental_runs_cannot_be_grouped() {
+        let mut config = AnalysisConfig::new("dataset");
+        config.incremental_file = Some("dataset.snapshot".to_string());
+        config.group_by = vec![Field::Map];
+
+        assert!(matches!(
+            load_stats(&config, &CustomAggregators::new(), &pool()),
+            Err(AnalyzerError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{deaths::RecordError, errors::AnalyzerError};
+
+/// Counters of the lines that could not be processed in a single file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileReport {
+    pub read_errors: usize,
+    pub invalid_field_count: usize,
+    pub invalid_numbers: usize,
+    pub missing_values: usize,
+    pub negative_values: usize,
+    pub out_of_bounds: usize,
+}
+
+impl FileReport {
+    /// Returns the total number of discarded lines of the file.
+    pub fn total(&self) -> usize {
+        self.read_errors
+            + self.invalid_field_count
+            + self.invalid_numbers
+            + self.missing_values
+            + self.negative_values
+            + self.out_of_bounds
+    }
+}
+
+/// Keeps track of the rows read and the lines discarded while reading the input files.
+/// It can be shared between the threads that process the files.
+/// Optionally, each discarded line is written to a reject file and the run is aborted after too many errors.
+pub struct IngestionReport {
+    files: Mutex<HashMap<PathBuf, FileReport>>,
+    rows_read: AtomicUsize,
+    /// Rows read by each thread of the pool, only counted when enabled with [`IngestionReport::with_thread_counters`].
+    rows_read_by_thread: Vec<AtomicUsize>,
+    files_read: AtomicUsize,
+    error_count: AtomicUsize,
+    max_errors: Option<usize>,
+    aborted: AtomicBool,
+    /// First input file that could not be opened or read, with the reason, which fails the run.
+    open_error: Mutex<Option<(PathBuf, std::io::Error)>>,
+    reject_writer: Option<Mutex<BufWriter<File>>>,
+}
+
+impl IngestionReport {
+    /// Creates a new `IngestionReport` instance.
+    /// If `reject_file` is provided, the file is created and every discarded line will be written to it.
+    /// If `max_errors` is provided, the run is aborted once the number of errors exceeds it.
+    pub fn new(reject_file: Option<&str>, max_errors: Option<usize>) -> std::io::Result<Self> {
+        let reject_writer = match reject_file {
+            Some(path) => Some(Mutex::new(BufWriter::new(File::create(path)?))),
+            None => None,
+        };
+
+        Ok(Self {
+            files: Mutex::new(HashMap::new()),
+            rows_read: AtomicUsize::new(0),
+            rows_read_by_thread: Vec::new(),
+            files_read: AtomicUsize::new(0),
+            error_count: AtomicUsize::new(0),
+            max_errors,
+            aborted: AtomicBool::new(false),
+            open_error: Mutex::new(None),
+            reject_writer,
+        })
+    }
+
+    /// Also counts the rows read by each of the `threads` threads of the pool reading the files,
+    /// e.g. to show the progress of each thread while the files are read.
+    pub fn with_thread_counters(mut self, threads: usize) -> Self {
+        self.rows_read_by_thread = (0..threads).map(|_| AtomicUsize::new(0)).collect();
+        self
+    }
+
+    /// Records a line that could not be read from the file.
+    pub fn add_read_error(&self, file: &Path, line_number: usize, error: &std::io::Error) {
+        self.add_error(file, line_number, &error.to_string(), "", |report| {
+            report.read_errors += 1
+        });
+    }
+
+    /// Records a file, or a part of it, that could not be read at all.
+    pub fn add_file_error(&self, file: &Path, reason: &str) {
+        self.add_error(file, 0, reason, "", |report| report.read_errors += 1);
+    }
+
+    /// Records an input file that could not be opened or read, which aborts the run: unlike the discarded lines,
+    /// it is not allowed by the maximum number of errors, see [`IngestionReport::check_errors`].
+    pub fn add_unreadable_file(&self, file: &Path, error: std::io::Error) {
+        if let Ok(mut open_error) = self.open_error.lock() {
+            open_error.get_or_insert_with(|| (file.to_path_buf(), error));
+        }
+        self.aborted.store(true, Ordering::Relaxed);
+    }
+
+    /// Records a line that could not be parsed.
+    pub fn add_record_error(
+        &self,
+        file: &Path,
+        line_number: usize,
+        line: &str,
+        error: &RecordError,
+    ) {
+        self.add_error(
+            file,
+            line_number,
+            &error.to_string(),
+            line,
+            |report| match error {
+                RecordError::InvalidFieldCount(_) => report.invalid_field_count += 1,
+                RecordError::InvalidNumber { .. } => report.invalid_numbers += 1,
+                RecordError::MissingValue { .. } => report.missing_values += 1,
+                RecordError::NegativeValue { .. } => report.negative_values += 1,
+                RecordError::OutOfBounds { .. } => report.out_of_bounds += 1,
+            },
+        );
+    }
+
+    fn add_error(
+        &self,
+        file: &Path,
+        line_number: usize,
+        reason: &str,
+        line: &str,
+        count: impl FnOnce(&mut FileReport),
+    ) {
+        if let Ok(mut files) = self.files.lock() {
+            count(files.entry(file.to_path_buf()).or_default());
+        }
+
+        if let Some(writer) = &self.reject_writer {
+            if let Ok(mut writer) = writer.lock() {
+                if let Err(e) = writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    file.display(),
+                    line_number,
+                    reason,
+                    line
+                ) {
+                    eprintln!("Error writing reject file: {}", e);
+                }
+            }
+        }
+
+        let error_count = self.error_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(max_errors) = self.max_errors {
+            if error_count > max_errors {
+                self.aborted.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns `true` if the discarded lines are written to a reject file.
+    pub fn writes_rejects(&self) -> bool {
+        self.reject_writer.is_some()
+    }
+
+    /// Returns `true` if the number of errors exceeded the maximum allowed, or an input file could not be read.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    /// Returns an IO error if an input file could not be opened or read,
+    /// or a parse error if the number of errors exceeded the maximum allowed.
+    pub fn check_errors(&self) -> Result<(), AnalyzerError> {
+        if let Some((file, error)) = self
+            .open_error
+            .lock()
+            .ok()
+            .as_deref()
+            .and_then(Option::as_ref)
+        {
+            return Err(AnalyzerError::io(
+                file,
+                std::io::Error::new(error.kind(), error.to_string()),
+            ));
+        }
+
+        match self.max_errors {
+            Some(max_errors) if self.is_aborted() => Err(AnalyzerError::Parse(format!(
+                "more than {} lines were discarded",
+                max_errors
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts rows read from the input files, whether they are discarded or not.
+    pub fn add_rows_read(&self, count: usize) {
+        self.rows_read.fetch_add(count, Ordering::Relaxed);
+
+        if let Some(thread_rows) = rayon::current_thread_index()
+            .and_then(|thread_index| self.rows_read_by_thread.get(thread_index))
+        {
+            thread_rows.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of rows read from the input files, including the discarded ones.
+    pub fn rows_read(&self) -> usize {
+        self.rows_read.load(Ordering::Relaxed)
+    }
+
+    /// Returns the rows read by each thread of the pool, or an empty list if they are not counted.
+    pub fn rows_read_by_thread(&self) -> Vec<usize> {
+        self.rows_read_by_thread
+            .iter()
+            .map(|rows| rows.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Counts an input file read until its end, whether its lines were discarded or not.
+    pub fn add_file_read(&self) {
+        self.files_read.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of input files read until their end.
+    pub fn files_read(&self) -> usize {
+        self.files_read.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of discarded lines.
+    pub fn error_count(&self) -> usize {
+        self.error_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the counters of each file that had at least one discarded line, sorted by path.
+    pub fn file_reports(&self) -> Vec<(PathBuf, FileReport)> {
+        let mut reports = match self.files.lock() {
+            Ok(files) => files
+                .iter()
+                .map(|(path, report)| (path.clone(), report.clone()))
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+
+        reports.sort_by(|(path_1, _), (path_2, _)| path_1.cmp(path_2));
+        reports
+    }
+
+    /// Flushes the reject file, if any.
+    pub fn flush(&self) -> std::io::Result<()> {
+        if let Some(writer) = &self.reject_writer {
+            if let Ok(mut writer) = writer.lock() {
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints a summary of the discarded lines of each file.
+    pub fn print_summary(&self) {
+        let reports = self.file_reports();
+
+        if reports.is_empty() {
+            println!("Discarded lines: 0");
+            return;
+        }
+
+        println!("Discarded lines: {}", self.error_count());
+        for (path, report) in reports {
+            println!(
+                "  {}: {} (read errors: {}, wrong field count: {}, invalid numbers: {}, missing values: {}, negative values: {}, out of bounds: {})",
+                path.display(),
+                report.total(),
+                report.read_errors,
+                report.invalid_field_count,
+                report.invalid_numbers,
+                report.missing_values,
+                report.negative_values,
+                report.out_of_bounds
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    const FILE: &str = "deaths.csv";
+
+    fn invalid_number() -> RecordError {
+        RecordError::InvalidNumber {
+            field: "killer_position_x",
+            value: "abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_counts_errors_by_file_and_reason() {
+        let report = IngestionReport::new(None, None).unwrap();
+
+        report.add_record_error(Path::new(FILE), 2, "", &RecordError::InvalidFieldCount(3));
+        report.add_record_error(Path::new(FILE), 3, "", &invalid_number());
+        report.add_record_error(Path::new("other.csv"), 2, "", &invalid_number());
+
+        assert_eq!(report.error_count(), 3);
+        assert_eq!(
+            report.file_reports(),
+            vec![
+                (
+                    PathBuf::from(FILE),
+                    FileReport {
+                        read_errors: 0,
+                        invalid_field_count: 1,
+                        invalid_numbers: 1,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    PathBuf::from("other.csv"),
+                    FileReport {
+                        read_errors: 0,
+                        invalid_field_count: 0,
+                        invalid_numbers: 1,
+                        ..Default::default()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aborts_after_max_errors() {
+        let report = IngestionReport::new(None, Some(1)).unwrap();
+
+        report.add_record_error(Path::new(FILE), 2, "", &invalid_number());
+        assert!(!report.is_aborted());
+
+        report.add_record_error(Path::new(FILE), 3, "", &invalid_number());
+        assert!(report.is_aborted());
+        assert!(matches!(
+            report.check_errors(),
+            Err(AnalyzerError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_counts_rows_of_each_thread() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let report = IngestionReport::new(None, None)
+            .unwrap()
+            .with_thread_counters(2);
+
+        pool.broadcast(|context| report.add_rows_read(context.index() + 1));
+        report.add_rows_read(10);
+
+        assert_eq!(report.rows_read(), 13);
+        assert_eq!(report.rows_read_by_thread(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_writes_reject_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let reject_path = temp_file.path().to_str().unwrap();
+
+        let report = IngestionReport::new(Some(reject_path), None).unwrap();
+        report.add_record_error(
+            Path::new(FILE),
+            2,
+            "a,b,c",
+            &RecordError::InvalidFieldCount(3),
+        );
+        report.flush().unwrap();
+
+        let content = std::fs::read_to_string(reject_path).unwrap();
+
+        assert_eq!(
+            content,
+            "deaths.csv\t2\tInvalid number of fields: 3\ta,b,c\n"
+        );
+    }
+}
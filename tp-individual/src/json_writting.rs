@@ -1,9 +1,11 @@
 use serde_json::json;
 
-use crate::{stats::Stats, PADRON};
+use crate::{errors::AnalyzerError, file_writting::write_atomically, stats::Stats, PADRON};
 
-/// Save the stats as a JSON file in the given path.
-pub fn save_as_json(stats: Stats, output_path: &str) {
+/// Save the stats as a JSON file in the given path, replacing it atomically.
+/// The elements of the leaderboards are written in order of rank, so the file only depends on the stats.
+/// Returns an error if the stats cannot be serialized or the file cannot be written.
+pub fn save_as_json(stats: Stats, output_path: &str) -> Result<(), AnalyzerError> {
     let mut json_stats = stats.json_display();
 
     match json_stats.as_object_mut() {
@@ -19,16 +21,10 @@ pub fn save_as_json(stats: Stats, output_path: &str) {
         }
     }
 
-    let json_str = match serde_json::to_string_pretty(&json_stats) {
-        Ok(json_str) => json_str,
-        Err(err) => {
-            eprintln!("Failed to serialize JSON: {}", err);
-            return;
-        }
-    };
+    let json_str = serde_json::to_string_pretty(&json_stats)?;
 
-    match std::fs::write(output_path, json_str) {
-        Ok(_) => println!("Stats saved as JSON in {}", output_path),
-        Err(err) => eprintln!("Failed to save stats as JSON: {}", err),
-    }
+    write_atomically(output_path, json_str)?;
+    println!("Stats saved as JSON in {}", output_path);
+
+    Ok(())
 }
@@ -0,0 +1,144 @@
+//! PUBG Death Analyzer library
+//!
+//! Reads the deaths of PUBG matches from CSV, Parquet or Arrow IPC files and aggregates them into statistics
+//! about the players and the weapons. All the processing is parallelized with Rayon, using the thread pool given
+//! by the caller.
+//!
+//! The usual pipeline is:
+//!
+//! 1. Find the input files with [`find_input_files_in_dir`] and split them with [`split_by_format`].
+//! 2. Read them as a parallel iterator of [`Death`] with [`read_csv_files`] and [`read_columnar_files`],
+//!    recording the discarded lines in an [`IngestionReport`]. Optionally, keep only the deaths matching a [`Filter`].
+//! 3. Aggregate the deaths with [`Stats::from_deaths`], or with [`Stats::from_deaths_with`] to compute custom
+//!    metrics implementing [`Aggregator`] in the same pass. [`CustomAggregators::group_by`] also groups the
+//!    deaths by any combination of [`Field`]s into nested groups.
+//! 4. Keep the top elements with [`Stats::filter_top_killers`] and [`Stats::filter_top_weapons`], or rank them
+//!    by another [`RankingMetric`] with [`Stats::filter_top_killers_by`] and [`Stats::filter_top_weapons_by`].
+//!    The largest groups at every level are kept with [`Stats::filter_top_groups`].
+//! 5. Render the results with [`Stats::json_display`] or save them with [`save_as_json`], as CSV files with
+//!    [`save_as_csv`], as an HTML report with charts with [`save_as_html`], or as a Markdown summary with
+//!    [`save_as_markdown`]. The metrics of the run can be saved in the Prometheus text format with
+//!    [`save_as_prometheus`].
+//!
+//! Instead of steps 3 to 5, ad-hoc questions can be answered by running a [`Query`] over the deaths with [`Query::run`].
+//!
+//! The same pipeline can be driven by an [`AnalysisConfig`]: [`read_stats`] reads the deaths of the input files with
+//! its settings, [`write_output`] saves the leaderboards in its output, and an [`InputWatcher`] keeps the stats of
+//! an input directory up to date while new files are added or rows are appended to them.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use rayon::{iter::ParallelIterator, ThreadPoolBuilder};
+//! use tp_individual::{
+//!     find_input_files_in_dir, read_columnar_files, read_csv_files, split_by_format, Death,
+//!     IngestionReport, Stats,
+//! };
+//!
+//! # fn main() -> Result<(), tp_individual::AnalyzerError> {
+//! let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+//! let report = Arc::new(IngestionReport::new(None, None).unwrap());
+//!
+//! let (csv_files, columnar_files) = split_by_format(find_input_files_in_dir("dataset/")?);
+//! let deaths = read_csv_files(csv_files, Death::from_csv_record, report.clone(), &pool)
+//!     .chain(read_columnar_files(columnar_files, false, report.clone(), &pool));
+//!
+//! let mut stats = Stats::from_deaths(deaths, &pool);
+//! stats.filter_top_killers(10, 3, &pool);
+//! stats.filter_top_weapons(10, &pool);
+//!
+//! println!("{}", stats.json_display());
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod aggregator;
+pub mod columnar_reading;
+pub mod config;
+pub mod csv_tools;
+pub mod csv_writting;
+pub mod dashboard;
+pub mod deaths;
+pub mod distance_histogram;
+pub mod errors;
+pub mod fields;
+pub mod file_reading;
+pub mod file_writting;
+pub mod filter;
+mod float_calculations;
+pub mod group_by;
+pub mod html_writting;
+pub mod http_server;
+pub mod ingestion;
+pub mod ingestion_report;
+pub mod json_writting;
+pub mod manifest;
+pub mod markdown_writting;
+pub mod output_diff;
+pub mod output_format;
+pub mod output_writting;
+pub mod player_stats;
+pub mod prometheus_writting;
+pub mod query;
+pub mod ranking;
+mod record_validation;
+pub mod snapshot;
+pub mod sorting;
+pub mod stats;
+pub mod stats_api;
+pub mod stats_shell;
+pub mod tail_reading;
+pub mod time_tracking;
+pub mod watching;
+pub mod weapon_stats;
+
+pub use aggregator::{Aggregator, CustomAggregators};
+pub use columnar_reading::read_columnar_files;
+pub use config::{read_config_file, AnalysisConfig};
+pub use csv_tools::{merge_csv_files, sample_csv_files};
+pub use csv_writting::save_as_csv;
+pub use dashboard::{render_progress, Dashboard, Progress};
+pub use deaths::{Death, RecordError};
+pub use distance_histogram::{DistanceBucket, DistanceHistogram};
+pub use errors::AnalyzerError;
+pub use fields::{Field, FieldKind, FieldValue};
+pub use file_reading::{find_input_files_in_dir, read_csv_files, split_by_format, InputFormat};
+pub use file_writting::write_atomically;
+pub use filter::{Filter, FilterError};
+pub use group_by::GroupByAggregator;
+pub use html_writting::{html_report, save_as_html};
+pub use http_server::serve_http;
+pub use ingestion::{
+    check_dashboard, ingestion_settings, load_stats, merge_deaths_into, read_deaths,
+    read_incremental, read_stats, read_stats_with_dashboard, read_stats_with_report, row_counts,
+    run_query,
+};
+pub use ingestion_report::{FileReport, IngestionReport};
+pub use json_writting::save_as_json;
+pub use manifest::{plan_update, FileEntry, Manifest, ManifestUpdate};
+pub use markdown_writting::{markdown_summary, save_as_markdown, RunInfo};
+pub use output_diff::{diff_outputs, read_output, OutputChange};
+pub use output_format::OutputFormat;
+pub use output_writting::write_output;
+pub use player_stats::{PlayerStats, PlayersAggregator};
+pub use prometheus_writting::{
+    prometheus_metrics, save_as_prometheus, AggregateMetrics, RowCounts, PROMETHEUS_CONTENT_TYPE,
+};
+pub use query::{Query, QueryError, QueryResult, QueryValue};
+pub use ranking::RankingMetric;
+pub use snapshot::{
+    is_snapshot, load_snapshot, load_snapshot_with_manifest, save_snapshot,
+    save_snapshot_with_manifest,
+};
+pub use stats::Stats;
+pub use stats_api::{handle_request, ApiResponse, Leaderboards, SharedLeaderboards};
+pub use stats_shell::{ExploreSession, ShellCommand, ShellHelper, SHELL_HELP};
+pub use tail_reading::{AppendedLines, CsvFollower};
+pub use time_tracking::Timer;
+pub use watching::InputWatcher;
+pub use weapon_stats::{WeaponStats, WeaponsAggregator};
+
+/// Student id included in the JSON output.
+pub const PADRON: usize = 110119;
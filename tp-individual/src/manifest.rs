@@ -0,0 +1,385 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{
+    errors::AnalyzerError,
+    snapshot::{Decoder, Encoder},
+    tail_reading::file_identity,
+};
+
+/// Offset basis of the 64 bits FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+/// Prime of the 64 bits FNV-1a hash.
+const FNV_PRIME: u64 = 0x100000001b3;
+/// Size of the chunks in which the files are read to hash them.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+/// Files modified this recently are hashed, as they can still be written to without changing their modification
+/// time on the file systems with coarse timestamps.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// State of an input file when it was folded into a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// Size of the file, in bytes.
+    pub size: u64,
+    /// Last modification time, in nanoseconds since the Unix epoch.
+    pub modified: u64,
+    /// Inode of the file, which changes when it is replaced, on the platforms that have them.
+    pub inode: Option<u64>,
+    /// FNV-1a hash of the content of the file, only when its metadata cannot tell whether it changed later.
+    pub hash: Option<u64>,
+}
+
+impl FileEntry {
+    /// Reads the size, modification time and inode of the file.
+    /// Its content is only hashed if it was modified within the last 2 seconds, see [`plan_update`].
+    pub fn read(path: &Path) -> Result<Self, AnalyzerError> {
+        let metadata = std::fs::metadata(path).map_err(|e| AnalyzerError::io(path, e))?;
+        let modified = metadata.modified().ok();
+        let recently_modified = modified
+            .and_then(|modified| modified.elapsed().ok())
+            .is_none_or(|elapsed| elapsed < RACY_WINDOW);
+
+        Ok(Self {
+            size: metadata.len(),
+            modified: modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_nanos() as u64),
+            inode: file_identity(&metadata),
+            hash: if recently_modified {
+                Some(hash_file(path)?)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Returns `true` if the file of the `current` entry has the content of this one.
+    /// Files with a hash are compared by content, so touching them does not change them, and the others by metadata.
+    fn is_unchanged(&self, current: &FileEntry, path: &Path) -> Result<bool, AnalyzerError> {
+        if self.size != current.size {
+            return Ok(false);
+        }
+
+        match self.hash {
+            Some(hash) => Ok(current.hash.map_or_else(|| hash_file(path), Ok)? == hash),
+            None => Ok(self.modified == current.modified && self.inode == current.inode),
+        }
+    }
+}
+
+/// Manifest of the input files folded into a snapshot, keyed by their name, with the settings used to read them.
+/// It tells which files have to be read to bring the snapshot up to date, see [`plan_update`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    settings: String,
+    files: HashMap<String, FileEntry>,
+}
+
+impl Manifest {
+    /// Creates a new empty manifest of the files read with the given settings.
+    pub fn new(settings: &str) -> Self {
+        Self {
+            settings: settings.to_string(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Returns the settings used to read the files, such as the filter applied to the deaths.
+    pub fn settings(&self) -> &str {
+        &self.settings
+    }
+
+    /// Returns the entry of each file, keyed by its name.
+    pub fn files(&self) -> &HashMap<String, FileEntry> {
+        &self.files
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(&self.settings);
+        encoder.map(&self.files, |encoder, entry| {
+            encoder.u64(entry.size);
+            encoder.u64(entry.modified);
+            encoder.optional_u64(entry.inode);
+            encoder.optional_u64(entry.hash);
+        });
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            settings: decoder.string()?,
+            files: decoder.map(|decoder| {
+                Ok(FileEntry {
+                    size: decoder.u64()?,
+                    modified: decoder.u64()?,
+                    inode: decoder.optional_u64()?,
+                    hash: decoder.optional_u64()?,
+                })
+            })?,
+        })
+    }
+}
+
+/// Files to read to bring a snapshot up to date, see [`plan_update`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestUpdate {
+    /// The new files, or every file when the snapshot has to be rebuilt.
+    pub files_to_read: Vec<PathBuf>,
+    /// Why the snapshot has to be rebuilt from scratch, if it has to.
+    pub rebuild_reason: Option<String>,
+    /// Manifest of the current files, to save along with the updated stats.
+    pub manifest: Manifest,
+}
+
+/// Compares the manifest of a snapshot, if any, with the current input files and settings.
+///
+/// New files can be read and merged into the stats of the snapshot. If a file of the manifest was modified or removed,
+/// or the settings changed, the stats have to be rebuilt from every file. A file is unchanged if it has the same size,
+/// modification time and inode, so the files are not read twice to hash them. Only the files modified too recently
+/// for their metadata to be trusted are hashed, and compared by content the next time.
+/// Returns an error if the metadata of a file cannot be read, or a file cannot be hashed.
+pub fn plan_update(
+    previous: Option<&Manifest>,
+    settings: &str,
+    input_files: &[PathBuf],
+) -> Result<ManifestUpdate, AnalyzerError> {
+    let mut manifest = Manifest::new(settings);
+    let mut new_files = Vec::new();
+    let mut rebuild_reason = match previous {
+        None => Some("there is no previous snapshot".to_string()),
+        Some(previous) if previous.settings != settings => Some("the settings changed".to_string()),
+        Some(_) => None,
+    };
+
+    for path in input_files {
+        let name = file_key(path);
+        let previous_entry = previous.and_then(|previous| previous.files.get(&name));
+
+        let entry = FileEntry::read(path)?;
+        match previous_entry {
+            Some(previous_entry) => {
+                if !previous_entry.is_unchanged(&entry, path)? {
+                    rebuild_reason.get_or_insert_with(|| format!("{} was modified", name));
+                }
+            }
+            None => new_files.push(path.clone()),
+        }
+
+        manifest.files.insert(name, entry);
+    }
+
+    if let Some(previous) = previous {
+        let mut removed = previous
+            .files
+            .keys()
+            .filter(|name| !manifest.files.contains_key(*name))
+            .collect::<Vec<_>>();
+        removed.sort();
+        if let Some(name) = removed.first() {
+            rebuild_reason.get_or_insert_with(|| format!("{} was removed", name));
+        }
+    }
+
+    let files_to_read = match rebuild_reason {
+        Some(_) => input_files.to_vec(),
+        None => new_files,
+    };
+
+    Ok(ManifestUpdate {
+        files_to_read,
+        rebuild_reason,
+        manifest,
+    })
+}
+
+/// Returns the key of a file in the manifest: its name, as the input files are all in the same directory.
+fn file_key(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the 64 bits FNV-1a hash of the content of the file.
+fn hash_file(path: &Path) -> Result<u64, AnalyzerError> {
+    let mut file = File::open(path).map_err(|e| AnalyzerError::io(path, e))?;
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    let mut hash = FNV_OFFSET_BASIS;
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| AnalyzerError::io(path, e))?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for byte in &buffer[..read] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const SETTINGS: &str = "strict=false";
+
+    fn write_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let dir = TempDir::new().unwrap();
+
+        // Reference values of the 64 bits FNV-1a hash.
+        assert_eq!(
+            hash_file(&write_file(&dir, "empty.csv", "")).unwrap(),
+            0xcbf29ce484222325
+        );
+        assert_eq!(
+            hash_file(&write_file(&dir, "a.csv", "a")).unwrap(),
+            0xaf63dc4c8601ec8c
+        );
+    }
+
+    #[test]
+    fn test_without_previous_manifest_every_file_is_read() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            write_file(&dir, "1.csv", "a"),
+            write_file(&dir, "2.csv", "b"),
+        ];
+
+        let update = plan_update(None, SETTINGS, &files).unwrap();
+
+        assert_eq!(update.files_to_read, files);
+        assert!(update.rebuild_reason.is_some());
+        assert_eq!(update.manifest.files().len(), 2);
+        assert_eq!(update.manifest.settings(), SETTINGS);
+    }
+
+    #[test]
+    fn test_only_new_files_are_read() {
+        let dir = TempDir::new().unwrap();
+        let first = write_file(&dir, "1.csv", "a");
+        let previous = plan_update(None, SETTINGS, std::slice::from_ref(&first))
+            .unwrap()
+            .manifest;
+        let second = write_file(&dir, "2.csv", "b");
+
+        let update = plan_update(Some(&previous), SETTINGS, &[first, second.clone()]).unwrap();
+
+        assert_eq!(update.files_to_read, vec![second]);
+        assert_eq!(update.rebuild_reason, None);
+        assert_eq!(update.manifest.files().len(), 2);
+    }
+
+    /// Writes a file modified long enough ago for its metadata to be trusted.
+    fn write_old_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = write_file(dir, name, content);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_only_recent_files_are_hashed() {
+        let dir = TempDir::new().unwrap();
+        let old = write_old_file(&dir, "old.csv", "a");
+        let recent = write_file(&dir, "recent.csv", "a");
+
+        assert_eq!(FileEntry::read(&old).unwrap().hash, None);
+        assert_eq!(
+            FileEntry::read(&recent).unwrap().hash,
+            Some(hash_file(&recent).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_touched_files_are_not_read_once_hashed() {
+        let dir = TempDir::new().unwrap();
+        let file = write_file(&dir, "1.csv", "a");
+        let mut previous = plan_update(None, SETTINGS, std::slice::from_ref(&file))
+            .unwrap()
+            .manifest;
+        previous.files.get_mut("1.csv").unwrap().modified = 0;
+
+        let update = plan_update(Some(&previous), SETTINGS, &[file]).unwrap();
+
+        assert!(update.files_to_read.is_empty());
+        assert_eq!(update.rebuild_reason, None);
+    }
+
+    #[test]
+    fn test_files_without_hash_are_compared_by_metadata() {
+        let dir = TempDir::new().unwrap();
+        let file = write_old_file(&dir, "1.csv", "a");
+        let previous = plan_update(None, SETTINGS, std::slice::from_ref(&file))
+            .unwrap()
+            .manifest;
+
+        let unchanged =
+            plan_update(Some(&previous), SETTINGS, std::slice::from_ref(&file)).unwrap();
+        let mut touched_previous = previous.clone();
+        touched_previous.files.get_mut("1.csv").unwrap().modified = 0;
+        let touched = plan_update(Some(&touched_previous), SETTINGS, &[file]).unwrap();
+
+        assert_eq!(unchanged.rebuild_reason, None);
+        assert_eq!(unchanged.manifest, previous);
+        assert_eq!(
+            touched.rebuild_reason.as_deref(),
+            Some("1.csv was modified")
+        );
+    }
+
+    #[test]
+    fn test_rebuild_reasons() {
+        let dir = TempDir::new().unwrap();
+        let first = write_file(&dir, "1.csv", "a");
+        let second = write_file(&dir, "2.csv", "b");
+        let previous = plan_update(None, SETTINGS, &[first.clone(), second.clone()])
+            .unwrap()
+            .manifest;
+
+        let settings_changed = plan_update(
+            Some(&previous),
+            "strict=true",
+            &[first.clone(), second.clone()],
+        )
+        .unwrap();
+        let removed = plan_update(Some(&previous), SETTINGS, std::slice::from_ref(&first)).unwrap();
+        std::fs::write(&second, "c").unwrap();
+        let mut modified_previous = previous.clone();
+        modified_previous.files.get_mut("2.csv").unwrap().modified = 0;
+        let modified =
+            plan_update(Some(&modified_previous), SETTINGS, &[first.clone(), second]).unwrap();
+
+        assert_eq!(
+            settings_changed.rebuild_reason.as_deref(),
+            Some("the settings changed")
+        );
+        assert_eq!(removed.rebuild_reason.as_deref(), Some("2.csv was removed"));
+        assert_eq!(removed.files_to_read, vec![first]);
+        assert_eq!(
+            modified.rebuild_reason.as_deref(),
+            Some("2.csv was modified")
+        );
+        assert_eq!(modified.files_to_read.len(), 2);
+    }
+}
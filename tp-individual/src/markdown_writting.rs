@@ -0,0 +1,158 @@
+use std::{fmt::Write, path::PathBuf, time::Duration};
+
+use crate::{errors::AnalyzerError, file_writting::write_atomically, stats::Stats};
+
+/// Information about the run that produced the stats, shown in the header of the Markdown summary.
+#[derive(Debug, Clone, Default)]
+pub struct RunInfo {
+    pub input_files: Vec<PathBuf>,
+    pub discarded_lines: usize,
+    pub threads: usize,
+    pub elapsed: Duration,
+}
+
+/// Save the stats as a Markdown summary in the given path, see [`markdown_summary`].
+/// Returns an error if the file cannot be written.
+pub fn save_as_markdown(
+    stats: &Stats,
+    run: &RunInfo,
+    output_path: &str,
+) -> Result<(), AnalyzerError> {
+    write_atomically(output_path, markdown_summary(stats, run))?;
+    println!("Stats saved as Markdown in {}", output_path);
+
+    Ok(())
+}
+
+/// Returns a Markdown summary of the stats, to paste into wikis or comments.
+/// It starts with the run information and has a table for the top killers, with their top weapons, and another one
+/// for the top weapons. The rows are in order of rank, with the same values as [`Stats::json_display`].
+pub fn markdown_summary(stats: &Stats, run: &RunInfo) -> String {
+    let mut markdown = String::from("# PUBG Death Analyzer summary\n\n");
+
+    let mut input_files = run.input_files.clone();
+    input_files.sort();
+    let _ = writeln!(markdown, "- Input files: {}", input_files.len());
+    for file in &input_files {
+        let _ = writeln!(markdown, "  - `{}`", file.display());
+    }
+    let _ = writeln!(markdown, "- Deaths aggregated: {}", stats.total_deaths());
+    let _ = writeln!(markdown, "- Discarded lines: {}", run.discarded_lines);
+    let _ = writeln!(markdown, "- Threads: {}", run.threads);
+    let _ = writeln!(markdown, "- Elapsed time: {:.2?}", run.elapsed);
+
+    markdown.push_str("\n## Top killers\n\n");
+    markdown.push_str("| Rank | Player | Kills | Top weapons |\n|---:|---|---:|---|\n");
+    for (index, (name, player_stats)) in stats.players().ranked().into_iter().enumerate() {
+        let weapons = player_stats
+            .weapons_percentage()
+            .into_iter()
+            .map(|(weapon_name, percentage)| {
+                format!("{} ({}%)", escape_cell(weapon_name), percentage)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let _ = writeln!(
+            markdown,
+            "| {} | {} | {} | {} |",
+            index + 1,
+            escape_cell(name),
+            player_stats.kills(),
+            weapons
+        );
+    }
+
+    let total_deaths = stats.total_deaths();
+    markdown.push_str("\n## Top weapons\n\n");
+    markdown.push_str("| Rank | Weapon | Share | Average distance |\n|---:|---|---:|---:|\n");
+    for (index, (name, weapon_stats)) in stats.weapons().ranked().into_iter().enumerate() {
+        let _ = writeln!(
+            markdown,
+            "| {} | {} | {}% | {} |",
+            index + 1,
+            escape_cell(name),
+            weapon_stats.deaths_percentage(total_deaths),
+            weapon_stats.rounded_average_distance()
+        );
+    }
+
+    markdown
+}
+
+/// Escapes the pipes of a table cell and replaces its line breaks, which would end the row.
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn death(killer: &str, weapon: &str, distance: f64) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            None,
+            Some(0.0),
+            Some(0.0),
+            Some(distance),
+            Some(0.0),
+        )
+    }
+
+    #[test]
+    fn test_markdown_summary() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let deaths = vec![
+            death("Player1", "AK47", 10.0),
+            death("Player1", "AK47", 20.0),
+            death("Player1", "M4A4", 30.0),
+            death("Player|2", "M4A4", 40.0),
+        ];
+        let mut stats = Stats::from_deaths(deaths.into_par_iter(), &pool);
+        stats.filter_top_killers(10, 3, &pool);
+        stats.filter_top_weapons(10, &pool);
+
+        let run = RunInfo {
+            input_files: vec![
+                PathBuf::from("dataset/b.csv"),
+                PathBuf::from("dataset/a.csv"),
+            ],
+            discarded_lines: 2,
+            threads: 4,
+            elapsed: Duration::from_millis(1500),
+        };
+
+        assert_eq!(
+            markdown_summary(&stats, &run),
+            "# PUBG Death Analyzer summary
+
+- Input files: 2
+  - `dataset/a.csv`
+  - `dataset/b.csv`
+- Deaths aggregated: 4
+- Discarded lines: 2
+- Threads: 4
+- Elapsed time: 1.50s
+
+## Top killers
+
+| Rank | Player | Kills | Top weapons |
+|---:|---|---:|---|
+| 1 | Player1 | 3 | AK47 (66.67%), M4A4 (33.33%) |
+| 2 | Player\\|2 | 1 | M4A4 (100%) |
+
+## Top weapons
+
+| Rank | Weapon | Share | Average distance |
+|---:|---|---:|---:|
+| 1 | AK47 | 50% | 15 |
+| 2 | M4A4 | 50% | 35 |
+"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::errors::AnalyzerError;
+
+/// A difference between two JSON outputs, at the given path of keys joined by dots.
+#[derive(Debug, PartialEq)]
+pub enum OutputChange {
+    /// The value is only present in the new output.
+    Added { path: String, value: Value },
+    /// The value is only present in the old output.
+    Removed { path: String, value: Value },
+    /// The value is present in both outputs, but it is different.
+    Changed {
+        path: String,
+        old_value: Value,
+        new_value: Value,
+    },
+}
+
+impl fmt::Display for OutputChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            Self::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            Self::Changed {
+                path,
+                old_value,
+                new_value,
+            } => write!(f, "~ {}: {} -> {}", path, old_value, new_value),
+        }
+    }
+}
+
+/// Reads a JSON output from the given path.
+/// Returns an IO error if the file cannot be read, or a parse error if it is not valid JSON.
+pub fn read_output(path: &str) -> Result<Value, AnalyzerError> {
+    let content = std::fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
+
+    serde_json::from_str(&content)
+        .map_err(|e| AnalyzerError::Parse(format!("{} is not valid JSON: {}", path, e)))
+}
+
+/// Compares two JSON outputs and returns their differences, sorted by path.
+/// Objects are compared key by key, any other values are compared as a whole.
+pub fn diff_outputs(old_output: &Value, new_output: &Value) -> Vec<OutputChange> {
+    let mut changes = Vec::new();
+    diff_values("", old_output, new_output, &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, old_value: &Value, new_value: &Value, changes: &mut Vec<OutputChange>) {
+    let (Value::Object(old_object), Value::Object(new_object)) = (old_value, new_value) else {
+        if old_value != new_value {
+            changes.push(OutputChange::Changed {
+                path: path.to_string(),
+                old_value: old_value.clone(),
+                new_value: new_value.clone(),
+            });
+        }
+        return;
+    };
+
+    let mut keys = old_object
+        .keys()
+        .chain(new_object.keys())
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let key_path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        };
+
+        match (old_object.get(key), new_object.get(key)) {
+            (Some(old_value), Some(new_value)) => {
+                diff_values(&key_path, old_value, new_value, changes)
+            }
+            (Some(old_value), None) => changes.push(OutputChange::Removed {
+                path: key_path,
+                value: old_value.clone(),
+            }),
+            (None, Some(new_value)) => changes.push(OutputChange::Added {
+                path: key_path,
+                value: new_value.clone(),
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_equal_outputs_have_no_changes() {
+        let output = json!({"top_killers": {"Player1": {"deaths": 2}}});
+
+        assert!(diff_outputs(&output, &output).is_empty());
+    }
+
+    #[test]
+    fn test_diff_outputs() {
+        let old_output = json!({
+            "top_killers": {
+                "Player1": {"deaths": 2},
+                "Player2": {"deaths": 1},
+            },
+        });
+        let new_output = json!({
+            "top_killers": {
+                "Player1": {"deaths": 3},
+                "Player3": {"deaths": 1},
+            },
+        });
+
+        let changes = diff_outputs(&old_output, &new_output)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            changes,
+            vec![
+                "~ top_killers.Player1.deaths: 2 -> 3",
+                "- top_killers.Player2: {\"deaths\":1}",
+                "+ top_killers.Player3: {\"deaths\":1}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_invalid_output_is_parse_error() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "{").unwrap();
+
+        let result = read_output(file.path().to_str().unwrap());
+
+        assert!(matches!(result, Err(AnalyzerError::Parse(_))));
+    }
+}
@@ -0,0 +1,84 @@
+use std::{fmt, str::FromStr};
+
+/// Formats in which the results of an analysis can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The stats as a JSON object, see [`crate::save_as_json`].
+    #[default]
+    Json,
+    /// Tables with a header line: the leaderboards as `top_killers.csv` and `top_weapons.csv`,
+    /// see [`crate::save_as_csv`], or the rows of a [`crate::QueryResult`].
+    Csv,
+    /// A self-contained report with charts, only supported by the leaderboards, see [`crate::save_as_html`].
+    Html,
+    /// A summary with the run information and the leaderboards as tables, see [`crate::save_as_markdown`].
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Returns where the leaderboards are written when no output is given:
+    /// a file for JSON, and the current directory for the CSV files.
+    pub fn default_output(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "output.json",
+            OutputFormat::Csv => ".",
+            OutputFormat::Html => "report.html",
+            OutputFormat::Markdown => "summary.md",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "html" => Ok(OutputFormat::Html),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(format!(
+                "Invalid output format: {} (expected json, csv, html or markdown)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Html => "html",
+            OutputFormat::Markdown => "markdown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("json".parse(), Ok(OutputFormat::Json));
+        assert_eq!("csv".parse(), Ok(OutputFormat::Csv));
+        assert_eq!("html".parse(), Ok(OutputFormat::Html));
+        assert_eq!("markdown".parse(), Ok(OutputFormat::Markdown));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::Csv,
+            OutputFormat::Html,
+            OutputFormat::Markdown,
+        ] {
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use rayon::ThreadPool;
+
+use std::path::PathBuf;
+
+use crate::{
+    config::AnalysisConfig,
+    csv_writting::save_as_csv,
+    errors::AnalyzerError,
+    html_writting::save_as_html,
+    json_writting::save_as_json,
+    markdown_writting::{save_as_markdown, RunInfo},
+    output_format::OutputFormat,
+    prometheus_writting::{save_as_prometheus, AggregateMetrics, RowCounts},
+    stats::Stats,
+    time_tracking::Timer,
+};
+
+/// Filters the top elements of the stats and saves them in the output of the configuration,
+/// and then the metrics of the run if the configuration has a metrics file.
+/// The `input_files` and `rows` of the run are only written to the Markdown summary and the metrics.
+pub fn write_output(
+    config: &AnalysisConfig,
+    mut stats: Stats,
+    input_files: Vec<PathBuf>,
+    rows: RowCounts,
+    timer: &mut Timer,
+    pool: &ThreadPool,
+) -> Result<(), AnalyzerError> {
+    let aggregates = config
+        .metrics_file
+        .as_ref()
+        .map(|_| AggregateMetrics::new(&stats));
+
+    // GET TOP KILLERS AND ITS BEST WEAPONS
+
+    stats.filter_top_killers_by(
+        config.top_players_count,
+        config.top_weapons_of_player_count,
+        config.players_ranking,
+        pool,
+    );
+    timer.print_lap("Filtering top killers");
+
+    // GET TOP WEAPONS
+
+    stats.filter_top_weapons_by(config.top_weapons_count, config.weapons_ranking, pool);
+    timer.print_lap("Filtering top weapons");
+
+    // GET TOP GROUPS
+
+    if !config.group_by.is_empty() {
+        stats.filter_top_groups(config.top_groups_count, pool);
+        timer.print_lap("Filtering top groups");
+    }
+
+    // SAVE THE OUTPUT
+
+    match config.format {
+        OutputFormat::Json => save_as_json(stats, &config.output_file)?,
+        OutputFormat::Csv => save_as_csv(&stats, &config.output_file)?,
+        OutputFormat::Html => save_as_html(&stats, timer, &config.output_file)?,
+        OutputFormat::Markdown => {
+            let run = RunInfo {
+                input_files,
+                discarded_lines: rows.rejected,
+                threads: config.threads,
+                elapsed: timer.total(),
+            };
+            save_as_markdown(&stats, &run, &config.output_file)?
+        }
+    }
+    timer.print_lap("Saving output");
+
+    if let (Some(metrics_file), Some(aggregates)) = (&config.metrics_file, aggregates) {
+        save_as_prometheus(&aggregates, rows, timer.laps(), metrics_file)?;
+    }
+
+    Ok(())
+}
@@ -2,26 +2,30 @@ use std::collections::HashMap;
 
 use rayon::ThreadPool;
 
-use crate::{float_calculations::calculate_percentage, sorting::retain_top_elements};
+use crate::{
+    aggregator::Aggregator,
+    deaths::Death,
+    float_calculations::{calculate_percentage, CompensatedSum},
+    ranking::{player_comparator, ranked_json, RankingMetric},
+    snapshot::{Decoder, Encoder},
+    sorting::{element_rank, ranked_elements, retain_top_elements, top_elements},
+};
 
 pub type PlayerWeaponStats = HashMap<String, usize>;
 
 /// Struct to store the stats of a player.
-#[derive(Eq, PartialEq)]
+#[derive(Clone)]
 pub struct PlayerStats {
     deaths_count: usize,
+    deaths_count_with_distance: usize,
+    total_distance: CompensatedSum,
+    times_killed: usize,
     weapons: PlayerWeaponStats,
 }
 
-impl PartialOrd for PlayerStats {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for PlayerStats {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.deaths_count.cmp(&other.deaths_count)
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -30,22 +34,64 @@ impl PlayerStats {
     pub fn new() -> Self {
         Self {
             deaths_count: 0,
+            deaths_count_with_distance: 0,
+            total_distance: CompensatedSum::new(),
+            times_killed: 0,
             weapons: HashMap::new(),
         }
     }
 
     /// Increments the death count of the player.
     /// If the weapon is provided, it also increments the death count of that weapon.
-    pub fn add_death(&mut self, weapon: Option<String>) {
+    /// If the distance is provided, it is added to the total distance of the kills of the player.
+    pub fn add_death(&mut self, weapon: Option<String>, distance: Option<f64>) {
         self.deaths_count += 1;
         if let Some(weapon) = weapon {
             *self.weapons.entry(weapon).or_insert(0) += 1;
         }
+        if let Some(distance) = distance {
+            self.deaths_count_with_distance += 1;
+            self.total_distance.add(distance);
+        }
+    }
+
+    /// Sets the number of times the player was killed, used to calculate its kill/death ratio.
+    pub fn set_times_killed(&mut self, times_killed: usize) {
+        self.times_killed = times_killed;
+    }
+
+    /// Returns the number of times the player was killed, once it was set by ranking the players.
+    pub fn times_killed(&self) -> usize {
+        self.times_killed
+    }
+
+    /// Returns the number of deaths caused by the player.
+    pub fn kills(&self) -> usize {
+        self.deaths_count
+    }
+
+    /// Returns the average distance of the kills of the player, without rounding.
+    /// Kills without distance are not taken into account.
+    pub fn average_distance(&self) -> f64 {
+        if self.deaths_count_with_distance == 0 {
+            0.0
+        } else {
+            self.total_distance.value() / self.deaths_count_with_distance as f64
+        }
+    }
+
+    /// Returns the number of kills of the player per time it was killed.
+    /// If the player was never killed, the ratio is its number of kills.
+    pub fn kill_death_ratio(&self) -> f64 {
+        self.deaths_count as f64 / self.times_killed.max(1) as f64
     }
 
     /// Merges the stats of another `PlayerStats` instance into this one.
     pub fn merge(&mut self, other: Self) {
         self.deaths_count += other.deaths_count;
+        self.deaths_count_with_distance += other.deaths_count_with_distance;
+        self.total_distance.merge(other.total_distance);
+        self.times_killed += other.times_killed;
         for (weapon, count) in other.weapons.into_iter() {
             *self.weapons.entry(weapon).or_insert(0) += count;
         }
@@ -58,21 +104,55 @@ impl PlayerStats {
     /// If there is a tie, the weapons will be resolved alphabetically.
     /// The operation is parallelized using the given thread pool.
     pub fn filter_top_weapons(&mut self, weapon_count: usize, pool: &ThreadPool) {
-        retain_top_elements(&mut self.weapons, weapon_count, pool);
+        retain_top_elements(&mut self.weapons, weapon_count, Ord::cmp, pool);
     }
 
-    /// Returns the stats of the player in a JSON format.
-    pub fn json_display(&self) -> serde_json::Value {
-        let weapon_stats = self
-            .weapons
-            .iter()
+    /// Returns the weapons of the player with their death count, from the most to the least used.
+    /// Ties are resolved alphabetically.
+    pub fn ranked_weapons(&self) -> Vec<(&String, &usize)> {
+        ranked_elements(&self.weapons, Ord::cmp)
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.usize(self.deaths_count);
+        encoder.usize(self.deaths_count_with_distance);
+        self.total_distance.encode(encoder);
+        encoder.usize(self.times_killed);
+        encoder.map(&self.weapons, |encoder, count| encoder.usize(*count));
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            deaths_count: decoder.usize()?,
+            deaths_count_with_distance: decoder.usize()?,
+            total_distance: CompensatedSum::decode(decoder)?,
+            times_killed: decoder.usize()?,
+            weapons: decoder.map(Decoder::usize)?,
+        })
+    }
+
+    /// Returns the weapons of the player with the percentage of its kills made with them, rounded to 2 decimals.
+    /// The weapons are ordered from the most to the least used.
+    pub fn weapons_percentage(&self) -> Vec<(&String, f64)> {
+        self.ranked_weapons()
+            .into_iter()
             .map(|(weapon_name, weapon_death_count)| {
                 (
                     weapon_name,
                     calculate_percentage(*weapon_death_count, self.deaths_count),
                 )
             })
-            .collect::<HashMap<_, _>>();
+            .collect()
+    }
+
+    /// Returns the stats of the player in a JSON format.
+    /// The weapons are ordered from the most to the least used.
+    pub fn json_display(&self) -> serde_json::Value {
+        let weapon_stats = self
+            .weapons_percentage()
+            .into_iter()
+            .map(|(weapon_name, percentage)| (weapon_name.clone(), serde_json::json!(percentage)))
+            .collect::<serde_json::Map<_, _>>();
 
         serde_json::json!({
             "deaths": self.deaths_count,
@@ -81,6 +161,166 @@ impl PlayerStats {
     }
 }
 
+/// Aggregation of the stats of each killer, keyed by the name of the player.
+/// It also counts how many times each player was killed, to calculate the kill/death ratios.
+#[derive(Clone, Default)]
+pub struct PlayersAggregator {
+    players: HashMap<String, PlayerStats>,
+    times_killed: HashMap<String, usize>,
+    /// Metric of the last filtering, used to order the players. Kills until they are filtered.
+    ranking: RankingMetric,
+    total_deaths: usize,
+}
+
+impl PlayersAggregator {
+    /// Creates a new empty `PlayersAggregator` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the stats of each player.
+    pub fn as_map(&self) -> &HashMap<String, PlayerStats> {
+        &self.players
+    }
+
+    /// Returns the players sorted by rank, from the highest to the lowest.
+    /// They are ranked by the metric of the last filtering, or by kills if they were not filtered.
+    /// Ties are resolved alphabetically.
+    pub fn ranked(&self) -> Vec<(&String, &PlayerStats)> {
+        ranked_elements(
+            &self.players,
+            player_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Returns the top `player_count` players sorted by rank, from the highest to the lowest, as in [`Self::ranked`].
+    pub fn top(&self, player_count: usize) -> Vec<(&String, &PlayerStats)> {
+        top_elements(
+            &self.players,
+            player_count,
+            player_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Returns the rank of the given player, starting at 1, as in [`Self::ranked`], or `None` if it caused no deaths.
+    pub fn rank_of(&self, name: &str) -> Option<usize> {
+        element_rank(
+            &self.players,
+            name,
+            player_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Ranks the players by the given metric without filtering them, setting the times each one was killed.
+    /// The `total_deaths` are used to calculate the kill share.
+    pub fn rank_by(&mut self, metric: RankingMetric, total_deaths: usize) {
+        for (name, player_stats) in self.players.iter_mut() {
+            player_stats.set_times_killed(self.times_killed.get(name).copied().unwrap_or(0));
+        }
+        self.ranking = metric;
+        self.total_deaths = total_deaths;
+    }
+
+    /// Writes the stats of each player and the times each player was killed. The ranking is not kept.
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.map(&self.players, |encoder, player_stats| {
+            player_stats.encode(encoder)
+        });
+        encoder.map(&self.times_killed, |encoder, count| encoder.usize(*count));
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            players: decoder.map(PlayerStats::decode)?,
+            times_killed: decoder.map(Decoder::usize)?,
+            ..Self::default()
+        })
+    }
+
+    /// Filters the top `player_count` players, ranked by the given metric, and the top `weapon_count` weapons of each player.
+    /// The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top(
+        &mut self,
+        player_count: usize,
+        weapon_count: usize,
+        metric: RankingMetric,
+        total_deaths: usize,
+        pool: &ThreadPool,
+    ) {
+        self.rank_by(metric, total_deaths);
+
+        retain_top_elements(
+            &mut self.players,
+            player_count,
+            player_comparator(metric, total_deaths),
+            pool,
+        );
+
+        self.players.iter_mut().for_each(|(_, player_stats)| {
+            player_stats.filter_top_weapons(weapon_count, pool);
+        });
+    }
+}
+
+impl Aggregator for PlayersAggregator {
+    /// Adds the death to the stats of its killer, if any.
+    fn fold(&mut self, death: &Death) {
+        if let Some(killer_name) = &death.killer_name {
+            let killed_by = death.killed_by.clone();
+            let distance = death.distance();
+
+            match self.players.get_mut(killer_name) {
+                Some(player_stats) => player_stats.add_death(killed_by, distance),
+                None => {
+                    let mut player_stats = PlayerStats::new();
+                    player_stats.add_death(killed_by, distance);
+                    self.players.insert(killer_name.clone(), player_stats);
+                }
+            }
+        }
+
+        if let Some(victim_name) = &death.victim_name {
+            match self.times_killed.get_mut(victim_name) {
+                Some(times_killed) => *times_killed += 1,
+                None => {
+                    self.times_killed.insert(victim_name.clone(), 1);
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        for (name, times_killed) in other.times_killed {
+            *self.times_killed.entry(name).or_insert(0) += times_killed;
+        }
+
+        for (name, other_player_stats) in other.players {
+            if let Some(player_stats) = self.players.get_mut(&name) {
+                player_stats.merge(other_player_stats);
+            } else {
+                self.players.insert(name, other_player_stats);
+            }
+        }
+    }
+
+    /// Returns the players in order of rank, each with its rank.
+    fn render(&self) -> serde_json::Value {
+        let players = self
+            .ranked()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (player_name, player_stats))| {
+                (
+                    player_name.clone(),
+                    ranked_json(index + 1, player_stats.json_display()),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::Value::Object(players)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,7 +350,7 @@ mod tests {
     fn test_add_death() {
         let mut player_stats = PlayerStats::new();
 
-        player_stats.add_death(Some(WEAPON_1.to_string()));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
 
         assert_eq!(player_stats.deaths_count, 1);
         assert_eq!(player_stats.weapons.len(), 1);
@@ -120,8 +360,8 @@ mod tests {
     fn test_add_two_deaths_same_weapon() {
         let mut player_stats = PlayerStats::new();
 
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_1.to_string()));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
 
         assert_eq!(player_stats.deaths_count, 2);
         assert_eq!(player_stats.weapons.len(), 1);
@@ -132,8 +372,8 @@ mod tests {
     fn test_add_two_deaths_different_weapons() {
         let mut player_stats = PlayerStats::new();
 
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_2.to_string()));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_2.to_string()), None);
 
         assert_eq!(player_stats.deaths_count, 2);
         assert_eq!(player_stats.weapons.len(), 2);
@@ -145,7 +385,7 @@ mod tests {
     fn test_add_death_no_weapon() {
         let mut player_stats = PlayerStats::new();
 
-        player_stats.add_death(None);
+        player_stats.add_death(None, None);
 
         assert_eq!(player_stats.deaths_count, 1);
         assert!(player_stats.weapons.is_empty());
@@ -154,12 +394,12 @@ mod tests {
     #[test]
     fn test_merge() {
         let mut player_stats_1 = PlayerStats::new();
-        player_stats_1.add_death(Some(WEAPON_1.to_string()));
-        player_stats_1.add_death(Some(WEAPON_2.to_string()));
+        player_stats_1.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats_1.add_death(Some(WEAPON_2.to_string()), None);
 
         let mut player_stats_2 = PlayerStats::new();
-        player_stats_2.add_death(Some(WEAPON_1.to_string()));
-        player_stats_2.add_death(Some(WEAPON_1.to_string()));
+        player_stats_2.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats_2.add_death(Some(WEAPON_1.to_string()), None);
 
         player_stats_1.merge(player_stats_2);
 
@@ -172,9 +412,9 @@ mod tests {
     #[test]
     fn test_filter_top_weapons() {
         let mut player_stats = PlayerStats::new();
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_2.to_string()));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_2.to_string()), None);
 
         player_stats.filter_top_weapons(1, &pool());
 
@@ -185,8 +425,8 @@ mod tests {
     #[test]
     fn test_filter_on_weapons_tie_resolve_alphabetically() {
         let mut player_stats = PlayerStats::new();
-        player_stats.add_death(Some(WEAPON_2.to_string()));
-        player_stats.add_death(Some(WEAPON_1.to_string()));
+        player_stats.add_death(Some(WEAPON_2.to_string()), None);
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
 
         player_stats.filter_top_weapons(1, &pool());
 
@@ -194,12 +434,38 @@ mod tests {
         assert_eq!(player_stats.weapons.get(WEAPON_1), Some(&1));
     }
 
+    #[test]
+    fn test_add_death_with_distance() {
+        let mut player_stats = PlayerStats::new();
+
+        player_stats.add_death(Some(WEAPON_1.to_string()), Some(100.0));
+        player_stats.add_death(Some(WEAPON_1.to_string()), Some(200.0));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+
+        assert_eq!(player_stats.kills(), 3);
+        assert_eq!(player_stats.average_distance(), 150.0);
+    }
+
+    #[test]
+    fn test_kill_death_ratio() {
+        let mut player_stats = PlayerStats::new();
+        player_stats.add_death(None, None);
+        player_stats.add_death(None, None);
+        player_stats.add_death(None, None);
+
+        assert_eq!(player_stats.kill_death_ratio(), 3.0);
+
+        player_stats.set_times_killed(2);
+
+        assert_eq!(player_stats.kill_death_ratio(), 1.5);
+    }
+
     #[test]
     fn test_json_display() {
         let mut player_stats = PlayerStats::new();
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_1.to_string()));
-        player_stats.add_death(Some(WEAPON_2.to_string()));
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_1.to_string()), None);
+        player_stats.add_death(Some(WEAPON_2.to_string()), None);
 
         let json = player_stats.json_display();
 
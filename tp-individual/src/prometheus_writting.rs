@@ -0,0 +1,207 @@
+use std::{fmt::Write, time::Duration};
+
+use crate::{errors::AnalyzerError, file_writting::write_atomically, stats::Stats};
+
+/// Content type of the Prometheus text exposition format.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Rows read by a run, see [`crate::IngestionReport::rows_read`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    /// Rows read from the input files, including the rejected ones.
+    pub read: usize,
+    /// Rows discarded because they could not be read or parsed.
+    pub rejected: usize,
+}
+
+/// Headline aggregates of the stats exposed as metrics.
+/// They are taken before the leaderboards are filtered, so they count every player and weapon.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AggregateMetrics {
+    total_deaths: usize,
+    distinct_players: usize,
+    /// Kills of each weapon, sorted by name.
+    weapon_kills: Vec<(String, usize)>,
+}
+
+impl AggregateMetrics {
+    /// Takes the aggregates of the stats, which should not be filtered yet.
+    pub fn new(stats: &Stats) -> Self {
+        let mut weapon_kills = stats
+            .weapons()
+            .as_map()
+            .iter()
+            .map(|(name, weapon_stats)| (name.clone(), weapon_stats.kills()))
+            .collect::<Vec<_>>();
+        weapon_kills.sort();
+
+        Self {
+            total_deaths: stats.total_deaths(),
+            distinct_players: stats.players().as_map().len(),
+            weapon_kills,
+        }
+    }
+}
+
+/// Save the metrics of a run in the Prometheus text format in the given path, see [`prometheus_metrics`].
+/// Returns an error if the file cannot be written.
+pub fn save_as_prometheus(
+    aggregates: &AggregateMetrics,
+    rows: RowCounts,
+    laps: &[(String, Duration)],
+    output_path: &str,
+) -> Result<(), AnalyzerError> {
+    write_atomically(output_path, prometheus_metrics(aggregates, rows, laps))?;
+    println!("Metrics saved in Prometheus format in {}", output_path);
+
+    Ok(())
+}
+
+/// Returns the metrics of a run in the Prometheus text exposition format: the rows read and rejected,
+/// the duration of each stage recorded by the timer in `laps`, and the headline aggregates of the stats.
+pub fn prometheus_metrics(
+    aggregates: &AggregateMetrics,
+    rows: RowCounts,
+    laps: &[(String, Duration)],
+) -> String {
+    let mut metrics = String::new();
+
+    write_header(
+        &mut metrics,
+        "pubg_rows_read_total",
+        "counter",
+        "Rows read from the input files, including the rejected ones.",
+    );
+    let _ = writeln!(metrics, "pubg_rows_read_total {}", rows.read);
+    write_header(
+        &mut metrics,
+        "pubg_rows_rejected_total",
+        "counter",
+        "Rows discarded because they could not be read or parsed.",
+    );
+    let _ = writeln!(metrics, "pubg_rows_rejected_total {}", rows.rejected);
+
+    write_header(
+        &mut metrics,
+        "pubg_stage_duration_seconds",
+        "gauge",
+        "Duration of each stage of the last run.",
+    );
+    for (stage, duration) in laps {
+        let _ = writeln!(
+            metrics,
+            "pubg_stage_duration_seconds{{stage=\"{}\"}} {}",
+            escape_label(stage),
+            duration.as_secs_f64()
+        );
+    }
+
+    write_header(
+        &mut metrics,
+        "pubg_deaths",
+        "gauge",
+        "Deaths aggregated, with or without killer.",
+    );
+    let _ = writeln!(metrics, "pubg_deaths {}", aggregates.total_deaths);
+    write_header(
+        &mut metrics,
+        "pubg_players",
+        "gauge",
+        "Distinct players that killed at least once.",
+    );
+    let _ = writeln!(metrics, "pubg_players {}", aggregates.distinct_players);
+    write_header(
+        &mut metrics,
+        "pubg_weapon_kills",
+        "gauge",
+        "Deaths caused by each weapon.",
+    );
+    for (weapon, kills) in &aggregates.weapon_kills {
+        let _ = writeln!(
+            metrics,
+            "pubg_weapon_kills{{weapon=\"{}\"}} {}",
+            escape_label(weapon),
+            kills
+        );
+    }
+
+    metrics
+}
+
+fn write_header(metrics: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(metrics, "# HELP {} {}", name, help);
+    let _ = writeln!(metrics, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes the backslashes, quotes and line breaks of a label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn death(killer: &str, weapon: &str) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            None,
+            Some(0.0),
+            Some(0.0),
+            Some(10.0),
+            Some(0.0),
+        )
+    }
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let deaths = vec![
+            death("Player1", "M4A4"),
+            death("Player1", "AK47"),
+            death("Player2", "Mk\"14\""),
+        ];
+        let mut stats = Stats::from_deaths(deaths.into_par_iter(), &pool);
+        let aggregates = AggregateMetrics::new(&stats);
+        stats.filter_top_killers(1, 1, &pool);
+        stats.filter_top_weapons(1, &pool);
+
+        let rows = RowCounts {
+            read: 4,
+            rejected: 1,
+        };
+        let laps = vec![("Processing deaths".to_string(), Duration::from_millis(1500))];
+
+        assert_eq!(
+            prometheus_metrics(&aggregates, rows, &laps),
+            "# HELP pubg_rows_read_total Rows read from the input files, including the rejected ones.
+# TYPE pubg_rows_read_total counter
+pubg_rows_read_total 4
+# HELP pubg_rows_rejected_total Rows discarded because they could not be read or parsed.
+# TYPE pubg_rows_rejected_total counter
+pubg_rows_rejected_total 1
+# HELP pubg_stage_duration_seconds Duration of each stage of the last run.
+# TYPE pubg_stage_duration_seconds gauge
+pubg_stage_duration_seconds{stage=\"Processing deaths\"} 1.5
+# HELP pubg_deaths Deaths aggregated, with or without killer.
+# TYPE pubg_deaths gauge
+pubg_deaths 3
+# HELP pubg_players Distinct players that killed at least once.
+# TYPE pubg_players gauge
+pubg_players 2
+# HELP pubg_weapon_kills Deaths caused by each weapon.
+# TYPE pubg_weapon_kills gauge
+pubg_weapon_kills{weapon=\"AK47\"} 1
+pubg_weapon_kills{weapon=\"M4A4\"} 1
+pubg_weapon_kills{weapon=\"Mk\\\"14\\\"\"} 1
+"
+        );
+    }
+}
@@ -0,0 +1,947 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    io::Write,
+    str::FromStr,
+};
+
+use rayon::{prelude::*, ThreadPool};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    csv_tools::{csv_line, open_output, write_line},
+    deaths::Death,
+    errors::AnalyzerError,
+    fields::{Field, FieldKind, FieldValue},
+    filter::{Filter, FilterError, Parser, Token},
+    float_calculations::{calculate_average, calculate_percentile, CompensatedSum},
+    output_format::OutputFormat,
+    sorting::retain_top_elements,
+};
+
+/// Name of the table of deaths, the only one that can be queried.
+const TABLE_NAME: &str = "deaths";
+
+const FUNCTIONS: [&str; 6] = ["count", "sum", "avg", "min", "max", "percentile"];
+
+/// Error found while parsing a query, with its position.
+pub type QueryError = FilterError;
+
+/// Functions that aggregate the values of a field over the deaths of a group.
+/// Missing values are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    /// Number of deaths, or of deaths where the field is not missing.
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// Value below which the given percentage of the values fall, from 0 to 100.
+    Percentile(f64),
+}
+
+/// Expression of a selected column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// Value of one of the fields the deaths are grouped by.
+    Field(Field),
+    /// Aggregate over a field, or over the deaths themselves for `count(*)`.
+    Aggregate {
+        function: AggregateFunction,
+        field: Option<Field>,
+    },
+}
+
+/// Selected column, with the name given by `as`, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectItem {
+    pub expression: Expression,
+    pub alias: Option<String>,
+}
+
+/// Sorting of the rows by one of the selected columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderBy {
+    /// Index of the column in the selection.
+    pub column: usize,
+    pub descending: bool,
+}
+
+/// A SQL-like query over the deaths:
+///
+/// ```text
+/// SELECT map, count(*) AS deaths, percentile(distance, 90)
+/// FROM deaths
+/// WHERE time > 1200
+/// GROUP BY map
+/// ORDER BY deaths DESC
+/// LIMIT 5
+/// ```
+///
+/// The `WHERE` clause takes a [`Filter`] expression. The selected fields must be in the `GROUP BY` clause,
+/// and the other columns must be aggregates: `count(*)`, `count(field)`, `sum(field)`, `avg(field)`,
+/// `min(field)`, `max(field)` or `percentile(field, p)`, all but `count` over number fields.
+/// Without `ORDER BY`, the rows are sorted by the grouped fields. Keywords are case insensitive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    select: Vec<SelectItem>,
+    filter: Option<Filter>,
+    group_by: Vec<Field>,
+    order_by: Vec<OrderBy>,
+    limit: Option<usize>,
+}
+
+/// Value of a cell of the result of a query.
+/// Values of different kinds are sorted with the missing values first, then the numbers and then the texts.
+#[derive(Debug, Clone)]
+pub enum QueryValue {
+    Missing,
+    Number(f64),
+    Text(String),
+}
+
+/// Result of a query: the names of the columns and the rows, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<QueryValue>>,
+}
+
+impl Query {
+    /// Parses a query.
+    /// Returns an error with its position if the query is malformed, references an unknown field or function,
+    /// or selects a field that is not grouped.
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser::new(query)?;
+
+        parser.expect_keyword("select")?;
+        let mut select = vec![parse_select_item(&mut parser)?];
+        while parser.accept(&Token::Comma) {
+            select.push(parse_select_item(&mut parser)?);
+        }
+
+        parser.expect_keyword("from")?;
+        let (table, position) = parser.advance()?;
+        if !matches!(&table, Token::Word(name) if name.eq_ignore_ascii_case(TABLE_NAME)) {
+            return Err(error(format!("Expected '{}'", TABLE_NAME), position));
+        }
+
+        let filter = if parser.accept_keyword("where") {
+            Some(parser.parse_or()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if parser.accept_keyword("group") {
+            parser.expect_keyword("by")?;
+            group_by.push(parse_field(&mut parser)?);
+            while parser.accept(&Token::Comma) {
+                group_by.push(parse_field(&mut parser)?);
+            }
+        }
+
+        let mut order_by = Vec::new();
+        if parser.accept_keyword("order") {
+            parser.expect_keyword("by")?;
+            order_by.push(parse_order_by(&mut parser, &select)?);
+            while parser.accept(&Token::Comma) {
+                order_by.push(parse_order_by(&mut parser, &select)?);
+            }
+        }
+
+        let limit = if parser.accept_keyword("limit") {
+            Some(parse_count(&mut parser)?)
+        } else {
+            None
+        };
+
+        if parser.peek().is_some() {
+            return Err(error("Unexpected token", parser.position()));
+        }
+
+        let select = select
+            .into_iter()
+            .map(|(item, position)| match item.expression {
+                Expression::Field(field) if !group_by.contains(&field) => Err(error(
+                    format!("{} must be in the GROUP BY clause", field),
+                    position,
+                )),
+                _ => Ok(item),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            select,
+            filter,
+            group_by,
+            order_by,
+            limit,
+        })
+    }
+
+    /// Returns the names of the columns of the result.
+    pub fn columns(&self) -> Vec<String> {
+        self.select.iter().map(SelectItem::name).collect()
+    }
+
+    /// Runs the query over a parallel iterator of `Death` instances.
+    /// The `pool` parameter is used to parallelize the processing of the deaths and the sorting of the rows.
+    pub fn run(
+        &self,
+        deaths: impl ParallelIterator<Item = Death>,
+        pool: &ThreadPool,
+    ) -> QueryResult {
+        let groups = pool.install(|| {
+            deaths
+                .fold(
+                    || QueryGroups::new(self),
+                    |mut acc, death| {
+                        acc.add_death(&death);
+                        acc
+                    },
+                )
+                .reduce(
+                    || QueryGroups::new(self),
+                    |mut acc1, acc2| {
+                        acc1.merge(acc2);
+                        acc1
+                    },
+                )
+        });
+
+        groups.into_result(pool)
+    }
+
+    /// Returns the aggregates of the selection, in order.
+    fn aggregates(&self) -> impl Iterator<Item = (AggregateFunction, Option<Field>)> + '_ {
+        self.select.iter().filter_map(|item| match item.expression {
+            Expression::Aggregate { function, field } => Some((function, field)),
+            Expression::Field(_) => None,
+        })
+    }
+
+    /// Returns an empty accumulator for each aggregate of the selection.
+    fn accumulators(&self) -> Vec<Accumulator> {
+        self.aggregates()
+            .map(|(function, _)| Accumulator::new(function))
+            .collect()
+    }
+
+    /// Compares two rows by the `ORDER BY` columns.
+    fn compare_rows(&self, row_1: &[QueryValue], row_2: &[QueryValue]) -> Ordering {
+        self.order_by
+            .iter()
+            .map(|order| {
+                let ordering = row_1[order.column].cmp(&row_2[order.column]);
+                if order.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl SelectItem {
+    /// Returns the name of the column: its alias, or its expression.
+    pub fn name(&self) -> String {
+        self.alias
+            .clone()
+            .unwrap_or_else(|| self.expression.to_string())
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Percentile(_) => "percentile",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Field(field) => write!(f, "{}", field),
+            Expression::Aggregate {
+                function: AggregateFunction::Percentile(percentile),
+                field: Some(field),
+            } => write!(f, "percentile({}, {})", field, percentile),
+            Expression::Aggregate {
+                function,
+                field: Some(field),
+            } => write!(f, "{}({})", function, field),
+            Expression::Aggregate {
+                function,
+                field: None,
+            } => write!(f, "{}(*)", function),
+        }
+    }
+}
+
+fn error(message: impl Into<String>, position: usize) -> QueryError {
+    QueryError {
+        message: message.into(),
+        position,
+    }
+}
+
+/// Parses a selected column, returning its position too.
+fn parse_select_item(parser: &mut Parser) -> Result<(SelectItem, usize), QueryError> {
+    let position = parser.position();
+    let expression = parse_expression(parser)?;
+
+    let alias = if parser.accept_keyword("as") {
+        match parser.advance()? {
+            (Token::Word(alias), _) => Some(alias),
+            (_, position) => return Err(error("Expected a column name", position)),
+        }
+    } else {
+        None
+    };
+
+    Ok((SelectItem { expression, alias }, position))
+}
+
+/// Parses a field or an aggregate of a field.
+fn parse_expression(parser: &mut Parser) -> Result<Expression, QueryError> {
+    let (token, position) = parser.advance()?;
+    let Token::Word(name) = token else {
+        return Err(error("Expected a field or a function", position));
+    };
+
+    if !parser.accept(&Token::LeftParen) {
+        return name
+            .parse()
+            .map(Expression::Field)
+            .map_err(|message| error(message, position));
+    }
+
+    let name = name.to_ascii_lowercase();
+    if !FUNCTIONS.contains(&name.as_str()) {
+        return Err(error(format!("Unknown function: {}", name), position));
+    }
+
+    if name == "count" && parser.accept(&Token::Star) {
+        parser.expect(Token::RightParen, "')'")?;
+        return Ok(Expression::Aggregate {
+            function: AggregateFunction::Count,
+            field: None,
+        });
+    }
+
+    let field_position = parser.position();
+    let field = parse_field(parser)?;
+    let function = match name.as_str() {
+        "count" => AggregateFunction::Count,
+        "sum" => AggregateFunction::Sum,
+        "avg" => AggregateFunction::Avg,
+        "min" => AggregateFunction::Min,
+        "max" => AggregateFunction::Max,
+        _ => {
+            parser.expect(Token::Comma, "','")?;
+            AggregateFunction::Percentile(parse_percentile(parser)?)
+        }
+    };
+    parser.expect(Token::RightParen, "')'")?;
+
+    if function != AggregateFunction::Count && field.kind() != FieldKind::Number {
+        return Err(error(
+            format!("{} can only be used with number fields", function),
+            field_position,
+        ));
+    }
+
+    Ok(Expression::Aggregate {
+        function,
+        field: Some(field),
+    })
+}
+
+fn parse_field(parser: &mut Parser) -> Result<Field, QueryError> {
+    match parser.advance()? {
+        (Token::Word(name), position) => name.parse().map_err(|message| error(message, position)),
+        (_, position) => Err(error("Expected a field", position)),
+    }
+}
+
+/// Parses a column of the `ORDER BY` clause, given by its name or its expression, which must be selected.
+fn parse_order_by(
+    parser: &mut Parser,
+    select: &[(SelectItem, usize)],
+) -> Result<OrderBy, QueryError> {
+    let position = parser.position();
+
+    let alias = match parser.peek() {
+        Some((Token::Word(name), _)) => select
+            .iter()
+            .position(|(item, _)| item.alias.as_ref() == Some(name)),
+        _ => None,
+    };
+    let column = match alias {
+        Some(column) => {
+            parser.advance()?;
+            column
+        }
+        None => {
+            let expression = parse_expression(parser)?;
+            select
+                .iter()
+                .position(|(item, _)| item.expression == expression)
+                .ok_or_else(|| error(format!("{} is not selected", expression), position))?
+        }
+    };
+
+    let descending = parser.accept_keyword("desc");
+    if !descending {
+        parser.accept_keyword("asc");
+    }
+
+    Ok(OrderBy { column, descending })
+}
+
+fn parse_count(parser: &mut Parser) -> Result<usize, QueryError> {
+    match parser.advance()? {
+        (Token::Number(count), _) if count >= 0.0 && count.fract() == 0.0 => Ok(count as usize),
+        (_, position) => Err(error("Expected a number of rows", position)),
+    }
+}
+
+fn parse_percentile(parser: &mut Parser) -> Result<f64, QueryError> {
+    match parser.advance()? {
+        (Token::Number(percentile), _) if (0.0..=100.0).contains(&percentile) => Ok(percentile),
+        (_, position) => Err(error("Expected a percentile between 0 and 100", position)),
+    }
+}
+
+/// Partial result of an aggregate over the deaths of a group.
+#[derive(Debug)]
+enum Accumulator {
+    Count(usize),
+    Total { sum: CompensatedSum, count: usize },
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Values(Vec<f64>),
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum | AggregateFunction::Avg => Accumulator::Total {
+                sum: CompensatedSum::new(),
+                count: 0,
+            },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+            AggregateFunction::Percentile(_) => Accumulator::Values(Vec::new()),
+        }
+    }
+
+    /// Adds a value of the aggregated field. Only `count` accepts texts.
+    fn add(&mut self, value: FieldValue) {
+        if let Accumulator::Count(count) = self {
+            *count += 1;
+            return;
+        }
+        let FieldValue::Number(value) = value else {
+            return;
+        };
+
+        match self {
+            Accumulator::Count(_) => {}
+            Accumulator::Total { sum, count } => {
+                sum.add(value);
+                *count += 1;
+            }
+            Accumulator::Min(min) => *min = Some(min.map_or(value, |min| min.min(value))),
+            Accumulator::Max(max) => *max = Some(max.map_or(value, |max| max.max(value))),
+            Accumulator::Values(values) => values.push(value),
+        }
+    }
+
+    fn merge(&mut self, other: Accumulator) {
+        match (self, other) {
+            (Accumulator::Count(count), Accumulator::Count(other)) => *count += other,
+            (
+                Accumulator::Total { sum, count },
+                Accumulator::Total {
+                    sum: other_sum,
+                    count: other_count,
+                },
+            ) => {
+                sum.merge(other_sum);
+                *count += other_count;
+            }
+            (Accumulator::Min(min), Accumulator::Min(Some(other))) => {
+                *min = Some(min.map_or(other, |min| min.min(other)))
+            }
+            (Accumulator::Max(max), Accumulator::Max(Some(other))) => {
+                *max = Some(max.map_or(other, |max| max.max(other)))
+            }
+            (Accumulator::Values(values), Accumulator::Values(other)) => values.extend(other),
+            _ => {}
+        }
+    }
+
+    /// Returns the value of the aggregate. It is missing when there were no values, except for `count`.
+    fn finish(self, function: AggregateFunction) -> QueryValue {
+        let value = match (self, function) {
+            (Accumulator::Count(count), _) => Some(count as f64),
+            (Accumulator::Total { sum, count }, AggregateFunction::Avg) => {
+                (count > 0).then(|| calculate_average(sum.value(), count))
+            }
+            (Accumulator::Total { sum, count }, _) => (count > 0).then(|| sum.value()),
+            (Accumulator::Min(min), _) => min,
+            (Accumulator::Max(max), _) => max,
+            (Accumulator::Values(mut values), AggregateFunction::Percentile(percentile)) => {
+                (!values.is_empty()).then(|| calculate_percentile(&mut values, percentile))
+            }
+            (Accumulator::Values(_), _) => None,
+        };
+
+        value.map_or(QueryValue::Missing, QueryValue::Number)
+    }
+}
+
+/// Aggregates of the deaths matching the query, grouped by the values of the `GROUP BY` fields.
+struct QueryGroups<'a> {
+    query: &'a Query,
+    groups: HashMap<Vec<QueryValue>, Vec<Accumulator>>,
+}
+
+impl<'a> QueryGroups<'a> {
+    fn new(query: &'a Query) -> Self {
+        Self {
+            query,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Adds a death to its group, if it matches the filter of the query.
+    fn add_death(&mut self, death: &Death) {
+        let query = self.query;
+        if query
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(death))
+        {
+            return;
+        }
+
+        let key = query
+            .group_by
+            .iter()
+            .map(|field| QueryValue::from(field.value(death)))
+            .collect::<Vec<_>>();
+        let accumulators = self
+            .groups
+            .entry(key)
+            .or_insert_with(|| query.accumulators());
+
+        for ((_, field), accumulator) in query.aggregates().zip(accumulators.iter_mut()) {
+            match field {
+                Some(field) => {
+                    if let Some(value) = field.value(death) {
+                        accumulator.add(value);
+                    }
+                }
+                None => accumulator.add(FieldValue::Number(1.0)),
+            }
+        }
+    }
+
+    fn merge(&mut self, other: QueryGroups) {
+        for (key, other_accumulators) in other.groups {
+            match self.groups.get_mut(&key) {
+                Some(accumulators) => {
+                    for (accumulator, other) in accumulators.iter_mut().zip(other_accumulators) {
+                        accumulator.merge(other);
+                    }
+                }
+                None => {
+                    self.groups.insert(key, other_accumulators);
+                }
+            }
+        }
+    }
+
+    /// Returns the rows of the result, sorted and limited as the query says.
+    /// Without `GROUP BY`, there is a single row even if no death matched.
+    fn into_result(mut self, pool: &ThreadPool) -> QueryResult {
+        let query = self.query;
+        if query.group_by.is_empty() && self.groups.is_empty() {
+            self.groups.insert(Vec::new(), query.accumulators());
+        }
+
+        let mut rows = self
+            .groups
+            .into_iter()
+            .map(|(key, accumulators)| {
+                let row = row(query, &key, accumulators);
+                (key, row)
+            })
+            .collect::<HashMap<_, _>>();
+
+        // The first rows in order rank the highest
+        if let Some(limit) = query.limit {
+            retain_top_elements(
+                &mut rows,
+                limit,
+                |row_1: &Vec<QueryValue>, row_2: &Vec<QueryValue>| query.compare_rows(row_2, row_1),
+                pool,
+            );
+        }
+
+        let mut rows = rows.into_iter().collect::<Vec<_>>();
+        pool.install(|| {
+            rows.par_sort_by(|(key_1, row_1), (key_2, row_2)| {
+                query
+                    .compare_rows(row_1, row_2)
+                    .then_with(|| key_1.cmp(key_2))
+            })
+        });
+
+        QueryResult {
+            columns: query.columns(),
+            rows: rows.into_iter().map(|(_, row)| row).collect(),
+        }
+    }
+}
+
+/// Returns the selected columns of a group.
+fn row(query: &Query, key: &[QueryValue], accumulators: Vec<Accumulator>) -> Vec<QueryValue> {
+    let mut aggregates = query.aggregates().zip(accumulators);
+
+    query
+        .select
+        .iter()
+        .map(|item| match &item.expression {
+            Expression::Field(field) => query
+                .group_by
+                .iter()
+                .position(|grouped| grouped == field)
+                .map_or(QueryValue::Missing, |index| key[index].clone()),
+            Expression::Aggregate { .. } => aggregates
+                .next()
+                .map_or(QueryValue::Missing, |((function, _), accumulator)| {
+                    accumulator.finish(function)
+                }),
+        })
+        .collect()
+}
+
+impl QueryValue {
+    /// Returns the value in a JSON format. Whole numbers are written without decimals.
+    pub fn json_display(&self) -> Value {
+        match self {
+            QueryValue::Missing => Value::Null,
+            QueryValue::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+                json!(*value as i64)
+            }
+            QueryValue::Number(value) => json!(value),
+            QueryValue::Text(value) => json!(value),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            QueryValue::Missing => 0,
+            QueryValue::Number(_) => 1,
+            QueryValue::Text(_) => 2,
+        }
+    }
+}
+
+impl From<Option<FieldValue<'_>>> for QueryValue {
+    fn from(value: Option<FieldValue>) -> Self {
+        match value {
+            None => QueryValue::Missing,
+            Some(FieldValue::Number(value)) => QueryValue::Number(value),
+            Some(FieldValue::Text(value)) => QueryValue::Text(value.to_string()),
+        }
+    }
+}
+
+impl Ord for QueryValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (QueryValue::Number(a), QueryValue::Number(b)) => a.total_cmp(b),
+            (QueryValue::Text(a), QueryValue::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for QueryValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueryValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for QueryValue {}
+
+impl Hash for QueryValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            QueryValue::Missing => {}
+            QueryValue::Number(value) => value.to_bits().hash(state),
+            QueryValue::Text(value) => value.hash(state),
+        }
+    }
+}
+
+/// Writes the value as a CSV field, where missing values are empty.
+impl fmt::Display for QueryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryValue::Missing => Ok(()),
+            QueryValue::Number(value) => write!(f, "{}", value),
+            QueryValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl QueryResult {
+    /// Returns the names of the columns.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Returns the rows, each with a value per column.
+    pub fn rows(&self) -> &[Vec<QueryValue>] {
+        &self.rows
+    }
+
+    /// Returns the rows in a JSON format, as an array of objects keyed by the names of the columns.
+    pub fn json_display(&self) -> Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let row = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| (column.clone(), value.json_display()))
+                    .collect::<Map<_, _>>();
+                Value::Object(row)
+            })
+            .collect();
+
+        Value::Array(rows)
+    }
+
+    /// Saves the result in the given format into `output_path`, or into the standard output if no path is given.
+    /// As CSV, the first line has the names of the columns and missing values are empty.
+    /// Returns an error if the result cannot be serialized or written, or if the format is not JSON nor CSV.
+    pub fn save(
+        &self,
+        output_path: Option<&str>,
+        format: OutputFormat,
+    ) -> Result<(), AnalyzerError> {
+        if matches!(format, OutputFormat::Html | OutputFormat::Markdown) {
+            return Err(AnalyzerError::Config(format!(
+                "Query results cannot be saved as {}",
+                format
+            )));
+        }
+        let (mut writer, output_name) = open_output(output_path)?;
+
+        match format {
+            OutputFormat::Json => {
+                let json_str = serde_json::to_string_pretty(&self.json_display())?;
+                write_line(&mut writer, &json_str, &output_name)?;
+            }
+            OutputFormat::Csv => {
+                write_line(&mut writer, &csv_line(&self.columns), &output_name)?;
+                for row in &self.rows {
+                    let fields = row.iter().map(|value| value.to_string());
+                    write_line(&mut writer, &csv_line(fields), &output_name)?;
+                }
+            }
+            OutputFormat::Html | OutputFormat::Markdown => {
+                unreachable!("checked before opening the output")
+            }
+        }
+
+        writer
+            .flush()
+            .map_err(|e| AnalyzerError::io(&output_name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_json_diff::assert_json_eq;
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(map: Option<&str>, killed_by: &str, distance: f64, time: f64) -> Death {
+        Death {
+            map: map.map(|map| map.to_string()),
+            time: Some(time),
+            ..Death::new(
+                Some(killed_by.to_string()),
+                None,
+                None,
+                Some(0.0),
+                Some(0.0),
+                Some(distance),
+                Some(0.0),
+            )
+        }
+    }
+
+    fn deaths() -> Vec<Death> {
+        vec![
+            death(Some("MIRAMAR"), "AK47", 10.0, 100.0),
+            death(Some("MIRAMAR"), "AK47", 30.0, 1500.0),
+            death(Some("MIRAMAR"), "M4A4", 20.0, 1500.0),
+            death(Some("ERANGEL"), "AK47", 40.0, 1500.0),
+            death(None, "Bluezone", 5.0, 1500.0),
+        ]
+    }
+
+    fn run(query: &str) -> Value {
+        Query::parse(query)
+            .unwrap()
+            .run(deaths().into_par_iter(), &pool())
+            .json_display()
+    }
+
+    #[test]
+    fn test_aggregates_without_group_by() {
+        assert_json_eq!(
+            run("SELECT count(*), sum(distance), avg(distance), min(time), max(time), percentile(distance, 50) FROM deaths"),
+            json!([{
+                "count(*)": 5,
+                "sum(distance)": 105,
+                "avg(distance)": 21,
+                "min(time)": 100,
+                "max(time)": 1500,
+                "percentile(distance, 50)": 20,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_group_by_with_where_order_and_limit() {
+        // The missing map sorts before ERANGEL, which is left out by the limit
+        assert_json_eq!(
+            run("select map, count(*) as deaths, avg(distance) from deaths where time > 1000 group by map order by deaths desc, map limit 2"),
+            json!([
+                {"map": "MIRAMAR", "deaths": 2, "avg(distance)": 25},
+                {"map": null, "deaths": 1, "avg(distance)": 5},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_default_order_is_by_group_with_missing_first() {
+        let result = Query::parse(
+            "SELECT map, killed_by, count(killed_by) FROM deaths GROUP BY map, killed_by",
+        )
+        .unwrap()
+        .run(deaths().into_par_iter(), &pool());
+
+        let keys = result
+            .rows()
+            .iter()
+            .map(|row| format!("{}/{}", row[0], row[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec!["/Bluezone", "ERANGEL/AK47", "MIRAMAR/AK47", "MIRAMAR/M4A4"]
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let query = Query::parse("SELECT count(*), avg(distance) FROM deaths").unwrap();
+        let result = query.run(Vec::<Death>::new().into_par_iter(), &pool());
+
+        assert_json_eq!(
+            result.json_display(),
+            json!([{"count(*)": 0, "avg(distance)": null}])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |query: &str| Query::parse(query).unwrap_err();
+
+        assert_eq!(error("count(*) FROM deaths").position, 1);
+        assert_eq!(error("SELECT map FROM deaths").position, 8);
+        assert_eq!(error("SELECT sum(map) FROM deaths").position, 12);
+        assert_eq!(error("SELECT median(time) FROM deaths").position, 8);
+        assert_eq!(error("SELECT count(*) FROM players").position, 22);
+        assert_eq!(
+            error("SELECT count(*) FROM deaths WHERE map = 1").position,
+            41
+        );
+        assert_eq!(
+            error("SELECT count(*) FROM deaths ORDER BY sum(time)").position,
+            38
+        );
+        assert_eq!(
+            error("SELECT percentile(time, 101) FROM deaths").position,
+            25
+        );
+        assert_eq!(error("SELECT count(*) FROM deaths LIMIT -1").position, 35);
+        assert_eq!(
+            error("SELECT map FROM deaths").message,
+            "map must be in the GROUP BY clause"
+        );
+    }
+
+    #[test]
+    fn test_csv_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("result.csv");
+        let path = path.to_str().unwrap();
+
+        Query::parse("SELECT map, count(*) FROM deaths GROUP BY map ORDER BY map DESC LIMIT 2")
+            .map(|query| query.run(deaths().into_par_iter(), &pool()))
+            .unwrap()
+            .save(Some(path), OutputFormat::Csv)
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "map,count(*)\nMIRAMAR,3\nERANGEL,1\n"
+        );
+    }
+}
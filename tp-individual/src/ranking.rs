@@ -0,0 +1,167 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::{player_stats::PlayerStats, weapon_stats::WeaponStats};
+
+/// Metrics that can be used to rank the players and the weapons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankingMetric {
+    /// Number of deaths caused.
+    #[default]
+    Kills,
+    /// Average distance of the deaths caused.
+    AverageDistance,
+    /// Percentage of all the deaths that were caused.
+    KillShare,
+    /// Kills per time killed. Only available for players.
+    KillDeathRatio,
+}
+
+impl RankingMetric {
+    /// Returns `true` if the metric can be used to rank weapons.
+    pub fn applies_to_weapons(&self) -> bool {
+        *self != RankingMetric::KillDeathRatio
+    }
+}
+
+impl FromStr for RankingMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kills" => Ok(RankingMetric::Kills),
+            "distance" => Ok(RankingMetric::AverageDistance),
+            "share" => Ok(RankingMetric::KillShare),
+            "kd" => Ok(RankingMetric::KillDeathRatio),
+            _ => Err(format!(
+                "Invalid ranking metric: {} (expected kills, distance, share or kd)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RankingMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RankingMetric::Kills => "kills",
+            RankingMetric::AverageDistance => "distance",
+            RankingMetric::KillShare => "share",
+            RankingMetric::KillDeathRatio => "kd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns the JSON object of a ranked element, with its `rank` before its stats.
+/// Ranks start at 1 for the highest ranked element.
+pub(crate) fn ranked_json(rank: usize, stats: serde_json::Value) -> serde_json::Value {
+    let mut json = serde_json::json!({ "rank": rank });
+
+    if let (Some(obj), serde_json::Value::Object(stats)) = (json.as_object_mut(), stats) {
+        obj.extend(stats);
+    }
+
+    json
+}
+
+/// Returns the fraction of all the deaths that were caused, without rounding.
+fn kill_share(kills: usize, total_deaths: usize) -> f64 {
+    if total_deaths == 0 {
+        0.0
+    } else {
+        kills as f64 / total_deaths as f64
+    }
+}
+
+/// Returns a comparator of players by the given metric, where the greater player ranks higher.
+/// The `total_deaths` are used to calculate the kill share.
+pub fn player_comparator(
+    metric: RankingMetric,
+    total_deaths: usize,
+) -> impl Fn(&PlayerStats, &PlayerStats) -> Ordering + Sync {
+    move |player_1, player_2| match metric {
+        RankingMetric::Kills => player_1.kills().cmp(&player_2.kills()),
+        RankingMetric::AverageDistance => player_1
+            .average_distance()
+            .total_cmp(&player_2.average_distance()),
+        RankingMetric::KillShare => kill_share(player_1.kills(), total_deaths)
+            .total_cmp(&kill_share(player_2.kills(), total_deaths)),
+        RankingMetric::KillDeathRatio => player_1
+            .kill_death_ratio()
+            .total_cmp(&player_2.kill_death_ratio()),
+    }
+}
+
+/// Returns a comparator of weapons by the given metric, where the greater weapon ranks higher.
+/// The `total_deaths` are used to calculate the kill share.
+/// Weapons are ranked by kills when the metric does not apply to them.
+pub fn weapon_comparator(
+    metric: RankingMetric,
+    total_deaths: usize,
+) -> impl Fn(&WeaponStats, &WeaponStats) -> Ordering + Sync {
+    move |weapon_1, weapon_2| match metric {
+        RankingMetric::AverageDistance => weapon_1
+            .average_distance()
+            .total_cmp(&weapon_2.average_distance()),
+        RankingMetric::KillShare => kill_share(weapon_1.kills(), total_deaths)
+            .total_cmp(&kill_share(weapon_2.kills(), total_deaths)),
+        RankingMetric::Kills | RankingMetric::KillDeathRatio => {
+            weapon_1.kills().cmp(&weapon_2.kills())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(kills: usize, distance: f64, times_killed: usize) -> PlayerStats {
+        let mut player_stats = PlayerStats::new();
+        for _ in 0..kills {
+            player_stats.add_death(None, Some(distance));
+        }
+        player_stats.set_times_killed(times_killed);
+        player_stats
+    }
+
+    #[test]
+    fn test_parse_metric() {
+        assert_eq!("kills".parse(), Ok(RankingMetric::Kills));
+        assert_eq!("distance".parse(), Ok(RankingMetric::AverageDistance));
+        assert_eq!("share".parse(), Ok(RankingMetric::KillShare));
+        assert_eq!("kd".parse(), Ok(RankingMetric::KillDeathRatio));
+        assert!("deaths".parse::<RankingMetric>().is_err());
+    }
+
+    #[test]
+    fn test_player_comparators() {
+        let sniper = player(2, 500.0, 4);
+        let brawler = player(4, 10.0, 1);
+
+        let by_kills = player_comparator(RankingMetric::Kills, 10);
+        let by_distance = player_comparator(RankingMetric::AverageDistance, 10);
+        let by_kill_death = player_comparator(RankingMetric::KillDeathRatio, 10);
+
+        assert_eq!(by_kills(&sniper, &brawler), Ordering::Less);
+        assert_eq!(by_distance(&sniper, &brawler), Ordering::Greater);
+        assert_eq!(by_kill_death(&sniper, &brawler), Ordering::Less);
+    }
+
+    #[test]
+    fn test_weapon_comparators() {
+        let mut rifle = WeaponStats::new();
+        rifle.add_death(Some(300.0));
+
+        let mut shotgun = WeaponStats::new();
+        shotgun.add_death(Some(5.0));
+        shotgun.add_death(Some(5.0));
+
+        let by_kills = weapon_comparator(RankingMetric::Kills, 3);
+        let by_distance = weapon_comparator(RankingMetric::AverageDistance, 3);
+        let by_share = weapon_comparator(RankingMetric::KillShare, 3);
+
+        assert_eq!(by_kills(&rifle, &shotgun), Ordering::Less);
+        assert_eq!(by_distance(&rifle, &shotgun), Ordering::Greater);
+        assert_eq!(by_share(&rifle, &shotgun), Ordering::Less);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::deaths::{Death, RecordError};
+
+/// Size of the side of each known map, in the same units as the positions of the dataset (centimeters).
+/// Every coordinate of a map must lie between 0 and its size.
+const MAP_SIZES: [(&str, f64); 4] = [
+    ("ERANGEL", 800_000.0),
+    ("MIRAMAR", 800_000.0),
+    ("VIKENDI", 600_000.0),
+    ("SANHOK", 400_000.0),
+];
+
+/// Returns the size of the given map, if it is known.
+fn map_size(map: &str) -> Option<f64> {
+    MAP_SIZES
+        .iter()
+        .find(|(name, _)| *name == map)
+        .map(|(_, size)| *size)
+}
+
+/// Checks that a numeric value, if present, is not negative.
+fn check_non_negative(field: &'static str, value: Option<f64>) -> Result<(), RecordError> {
+    match value {
+        Some(value) if value < 0.0 => Err(RecordError::NegativeValue { field, value }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a coordinate lies within the bounds of the map.
+/// Coordinates of unknown maps are not checked.
+fn check_bounds(
+    field: &'static str,
+    value: Option<f64>,
+    map: Option<&str>,
+) -> Result<(), RecordError> {
+    let (Some(value), Some(map), Some(size)) = (value, map, map.and_then(map_size)) else {
+        return Ok(());
+    };
+
+    if (0.0..=size).contains(&value) {
+        Ok(())
+    } else {
+        Err(RecordError::OutOfBounds {
+            field,
+            value,
+            map: map.to_string(),
+        })
+    }
+}
+
+/// Validates the values of a death in strict mode, whatever the format of the file it was read from.
+///
+/// - The time is required and cannot be negative.
+/// - The placements cannot be negative.
+/// - The positions must lie within the bounds of the map, if the map is known.
+pub fn validate_death(death: &Death) -> Result<(), RecordError> {
+    if death.time.is_none() {
+        return Err(RecordError::MissingValue { field: "time" });
+    }
+    check_non_negative("time", death.time)?;
+
+    check_non_negative("killer_placement", death.killer_placement)?;
+    check_non_negative("victim_placement", death.victim_placement)?;
+
+    let map = death.map.as_deref();
+    check_bounds("killer_position_x", death.killer_position_x, map)?;
+    check_bounds("killer_position_y", death.killer_position_y, map)?;
+    check_bounds("victim_position_x", death.victim_position_x, map)?;
+    check_bounds("victim_position_y", death.victim_position_y, map)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(record: &str) -> Result<(), RecordError> {
+        validate_death(&Death::from_csv_record(record.to_string())?)
+    }
+
+    #[test]
+    fn test_valid_record() {
+        let record =
+            "AK47,Player1,1.0,1000.0,2000.0,ERANGEL,match-id,123,Player2,2.0,1100.0,2000.0";
+
+        assert_eq!(validate(record), Ok(()));
+    }
+
+    #[test]
+    fn test_empty_placements_and_positions_are_valid() {
+        let record = "Bluezone,,,,,MIRAMAR,match-id,1505,Player2,7.0,495367.2,419491.3";
+
+        assert_eq!(validate(record), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_time() {
+        let record = "AK47,Player1,1.0,0.0,0.0,ERANGEL,match-id,,Player2,1.0,100.0,0.0";
+
+        assert_eq!(
+            validate(record),
+            Err(RecordError::MissingValue { field: "time" })
+        );
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        let record = "AK47,Player1,1.0,0.0,0.0,ERANGEL,match-id,12a,Player2,1.0,100.0,0.0";
+
+        assert_eq!(
+            validate(record),
+            Err(RecordError::InvalidNumber {
+                field: "time",
+                value: "12a".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_negative_time() {
+        let record = "AK47,Player1,1.0,0.0,0.0,ERANGEL,match-id,-5,Player2,1.0,100.0,0.0";
+
+        assert_eq!(
+            validate(record),
+            Err(RecordError::NegativeValue {
+                field: "time",
+                value: -5.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_negative_placement() {
+        let record = "AK47,Player1,1.0,0.0,0.0,ERANGEL,match-id,123,Player2,-2.0,100.0,0.0";
+
+        assert_eq!(
+            validate(record),
+            Err(RecordError::NegativeValue {
+                field: "victim_placement",
+                value: -2.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_position_out_of_bounds() {
+        let record = "AK47,Player1,1.0,0.0,0.0,SANHOK,match-id,123,Player2,1.0,500000.0,0.0";
+
+        assert_eq!(
+            validate(record),
+            Err(RecordError::OutOfBounds {
+                field: "victim_position_x",
+                value: 500000.0,
+                map: "SANHOK".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_map_is_not_bounded() {
+        let record = "AK47,Player1,1.0,0.0,0.0,UNKNOWN,match-id,123,Player2,1.0,5000000.0,0.0";
+
+        assert_eq!(validate(record), Ok(()));
+    }
+}
@@ -0,0 +1,367 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    errors::AnalyzerError, file_writting::write_atomically, manifest::Manifest, stats::Stats,
+};
+
+/// Extension of the snapshot files.
+pub const SNAPSHOT_EXTENSION: &str = "snapshot";
+/// Version of the snapshot format written by this build.
+/// Version 2 added the manifest of the input files. Snapshots of version 1 are read with an empty manifest,
+/// and snapshots of newer versions cannot be read.
+pub const SNAPSHOT_VERSION: u64 = 2;
+
+/// Bytes at the start of every snapshot, to tell them apart from other files.
+const MAGIC: &[u8] = b"PUBGSNAP";
+
+/// Save the stats as a binary snapshot in the given path, to merge them later with [`load_snapshot`] and [`Stats::merge`].
+/// The snapshot keeps the stats of every player and weapon and the distance histograms, but not the groups nor the
+/// custom metrics, so it should be written before filtering the top elements.
+/// The same stats always give the same bytes, and the file is replaced atomically, see [`write_atomically`].
+/// Returns an error if the file cannot be written.
+pub fn save_snapshot(stats: &Stats, output_path: &str) -> Result<(), AnalyzerError> {
+    save_snapshot_with_manifest(stats, &Manifest::default(), output_path)
+}
+
+/// Save the stats as a binary snapshot, like [`save_snapshot`], along with the manifest of the files they come from.
+pub fn save_snapshot_with_manifest(
+    stats: &Stats,
+    manifest: &Manifest,
+    output_path: &str,
+) -> Result<(), AnalyzerError> {
+    let mut encoder = Encoder::new();
+    encoder.bytes.extend_from_slice(MAGIC);
+    encoder.u64(SNAPSHOT_VERSION);
+    stats.encode(&mut encoder);
+    manifest.encode(&mut encoder);
+
+    write_atomically(output_path, encoder.bytes)
+}
+
+/// Loads the stats from a binary snapshot written by [`save_snapshot`].
+/// Returns a parse error if the file is not a snapshot, it was written by a newer version or it is corrupted.
+pub fn load_snapshot(input_path: &str) -> Result<Stats, AnalyzerError> {
+    load_snapshot_with_manifest(input_path).map(|(stats, _)| stats)
+}
+
+/// Loads the stats from a binary snapshot, like [`load_snapshot`], along with the manifest of the files they come from.
+pub fn load_snapshot_with_manifest(input_path: &str) -> Result<(Stats, Manifest), AnalyzerError> {
+    let bytes = std::fs::read(input_path).map_err(|e| AnalyzerError::io(input_path, e))?;
+    let invalid = |reason: String| {
+        AnalyzerError::Parse(format!("Invalid snapshot {}: {}", input_path, reason))
+    };
+
+    let Some(content) = bytes.strip_prefix(MAGIC) else {
+        return Err(invalid("not a snapshot".to_string()));
+    };
+    let mut decoder = Decoder::new(content);
+    let version = decoder.u64().map_err(invalid)?;
+    if !(1..=SNAPSHOT_VERSION).contains(&version) {
+        return Err(invalid(format!(
+            "unsupported version {} (expected up to {})",
+            version, SNAPSHOT_VERSION
+        )));
+    }
+
+    let stats = Stats::decode(&mut decoder).map_err(invalid)?;
+    let manifest = match version {
+        1 => Manifest::default(),
+        _ => Manifest::decode(&mut decoder).map_err(invalid)?,
+    };
+    if !decoder.is_at_end() {
+        return Err(invalid("unexpected data at the end".to_string()));
+    }
+
+    Ok((stats, manifest))
+}
+
+/// Returns `true` if the path has the extension of the snapshots.
+pub fn is_snapshot(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension == SNAPSHOT_EXTENSION)
+}
+
+/// Writes the values of a snapshot. Integers are written as variable length numbers (LEB128),
+/// floats as their 8 little endian bytes and strings as their length followed by their UTF-8 bytes.
+pub(crate) struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    pub(crate) fn u64(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    /// Writes whether there is a value, and then the value if there is one.
+    pub(crate) fn optional_u64(&mut self, value: Option<u64>) {
+        self.u64(value.is_some() as u64);
+        if let Some(value) = value {
+            self.u64(value);
+        }
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn str(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// Writes the entries of the map sorted by key, so the bytes do not depend on the order of the map.
+    pub(crate) fn map<V>(&mut self, map: &HashMap<String, V>, encode: impl Fn(&mut Self, &V)) {
+        let mut entries = map.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+
+        self.usize(entries.len());
+        for (key, value) in entries {
+            self.str(key);
+            encode(self, value);
+        }
+    }
+}
+
+/// Reads the values written by an [`Encoder`], in the same order.
+/// Each method returns the reason if the bytes do not hold a value of the expected kind.
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.take(1)?[0];
+            if shift >= u64::BITS || (byte & 0x7f) as u64 > u64::MAX >> shift {
+                return Err("number too large".to_string());
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|_| "number too large".to_string())
+    }
+
+    pub(crate) fn optional_u64(&mut self) -> Result<Option<u64>, String> {
+        match self.u64()? {
+            0 => Ok(None),
+            1 => self.u64().map(Some),
+            _ => Err("invalid optional value".to_string()),
+        }
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, String> {
+        let bytes = self.take(8)?;
+        let mut array = [0u8; 8];
+        array.copy_from_slice(bytes);
+        Ok(f64::from_le_bytes(array))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, String> {
+        let length = self.usize()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "invalid UTF-8 string".to_string())
+    }
+
+    pub(crate) fn map<V>(
+        &mut self,
+        decode: impl Fn(&mut Self) -> Result<V, String>,
+    ) -> Result<HashMap<String, V>, String> {
+        let length = self.usize()?;
+        // Every entry takes at least one byte, this avoids allocating a huge map for a corrupted length.
+        if length > self.bytes.len() - self.position {
+            return Err("unexpected end of file".to_string());
+        }
+
+        let mut map = HashMap::with_capacity(length);
+        for _ in 0..length {
+            let key = self.string()?;
+            map.insert(key, decode(self)?);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPool, ThreadPoolBuilder};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(killer: &str, weapon: &str, victim: &str, distance: f64) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            Some(victim.to_string()),
+            Some(0.0),
+            Some(0.0),
+            Some(distance),
+            Some(0.0),
+        )
+    }
+
+    fn stats(deaths: Vec<Death>) -> Stats {
+        Stats::from_deaths(deaths.into_par_iter(), &pool())
+    }
+
+    fn path(dir: &TempDir, name: &str) -> String {
+        dir.path().join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_numbers_roundtrip() {
+        let mut encoder = Encoder::new();
+        for value in [0, 1, 127, 128, 300, usize::MAX] {
+            encoder.usize(value);
+        }
+        encoder.f64(0.1);
+        encoder.str("Jugador ñ");
+
+        let mut decoder = Decoder::new(&encoder.bytes);
+        for value in [0, 1, 127, 128, 300, usize::MAX] {
+            assert_eq!(decoder.usize(), Ok(value));
+        }
+        assert_eq!(decoder.f64(), Ok(0.1));
+        assert_eq!(decoder.string(), Ok("Jugador ñ".to_string()));
+        assert!(decoder.is_at_end());
+        assert!(decoder.usize().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let snapshot = path(&dir, "day.snapshot");
+        let original = stats(vec![
+            death("Player1", "AK47", "Player2", 10.0),
+            death("Player2", "M4A4", "Player1", 25.5),
+            death("Player1", "AK47", "Player3", 0.1),
+        ]);
+
+        save_snapshot(&original, &snapshot).unwrap();
+        let loaded = load_snapshot(&snapshot).unwrap();
+
+        assert_eq!(loaded.json_display(), original.json_display());
+        assert_eq!(loaded.distances(), original.distances());
+        assert_eq!(loaded.total_deaths(), 3);
+    }
+
+    #[test]
+    fn test_merged_snapshots_equal_a_single_run() {
+        let dir = TempDir::new().unwrap();
+        let deaths = vec![
+            death("Player1", "AK47", "Player2", 10.0),
+            death("Player2", "M4A4", "Player1", 25.5),
+            death("Player1", "AK47", "Player3", 7.25),
+            death("Player3", "AK47", "Player1", 100.0),
+        ];
+        save_snapshot(&stats(deaths[..2].to_vec()), &path(&dir, "1.snapshot")).unwrap();
+        save_snapshot(&stats(deaths[2..].to_vec()), &path(&dir, "2.snapshot")).unwrap();
+
+        let mut merged = load_snapshot(&path(&dir, "1.snapshot")).unwrap();
+        merged.merge(load_snapshot(&path(&dir, "2.snapshot")).unwrap());
+        let mut single = stats(deaths);
+
+        let pool = pool();
+        for stats in [&mut merged, &mut single] {
+            stats.filter_top_killers_by(10, 3, crate::RankingMetric::KillDeathRatio, &pool);
+        }
+        assert_eq!(merged.json_display(), single.json_display());
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        let dir = TempDir::new().unwrap();
+        let snapshot = path(&dir, "day.snapshot");
+        save_snapshot(
+            &stats(vec![death("Player1", "AK47", "Player2", 10.0)]),
+            &snapshot,
+        )
+        .unwrap();
+        let bytes = std::fs::read(&snapshot).unwrap();
+
+        let not_a_snapshot = path(&dir, "deaths.snapshot");
+        std::fs::write(&not_a_snapshot, "killed_by,killer_name").unwrap();
+        let truncated = path(&dir, "truncated.snapshot");
+        std::fs::write(&truncated, &bytes[..bytes.len() - 3]).unwrap();
+        let other_version = path(&dir, "other_version.snapshot");
+        let mut other_version_bytes = bytes.clone();
+        other_version_bytes[MAGIC.len()] = 99;
+        std::fs::write(&other_version, other_version_bytes).unwrap();
+
+        for invalid in [not_a_snapshot, truncated, other_version] {
+            assert!(matches!(
+                load_snapshot(&invalid),
+                Err(AnalyzerError::Parse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_version_1_has_an_empty_manifest() {
+        let dir = TempDir::new().unwrap();
+        let snapshot = path(&dir, "old.snapshot");
+        let mut encoder = Encoder::new();
+        encoder.bytes.extend_from_slice(MAGIC);
+        encoder.u64(1);
+        stats(vec![death("Player1", "AK47", "Player2", 10.0)]).encode(&mut encoder);
+        std::fs::write(&snapshot, encoder.bytes).unwrap();
+
+        let (stats, manifest) = load_snapshot_with_manifest(&snapshot).unwrap();
+
+        assert_eq!(stats.total_deaths(), 1);
+        assert_eq!(manifest, Manifest::default());
+    }
+
+    #[test]
+    fn test_is_snapshot() {
+        assert!(is_snapshot("week.snapshot"));
+        assert!(!is_snapshot("deaths.csv"));
+        assert!(!is_snapshot("snapshot"));
+    }
+}
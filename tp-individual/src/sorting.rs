@@ -1,48 +1,110 @@
 use rayon::{prelude::*, ThreadPool};
 use std::{
-    cmp::Reverse,
+    borrow::Borrow,
+    cmp::Ordering,
     collections::{BinaryHeap, HashMap},
     hash::Hash,
 };
 
-/// A capped min heap map.
-/// It will keep the top `capacity` elements.
-struct CappedMinHeapMap<K, V> {
-    heap: BinaryHeap<(Reverse<V>, K)>,
+/// Elements preallocated by a [`CappedRankedMap`] at most, since its capacity comes from the user
+/// and each fold of a thread pool has its own map. Larger maps grow on demand.
+const MAX_PREALLOCATED_ELEMENTS: usize = 1024;
+
+/// A key-value pair of a [`CappedRankedMap`], ordered by rank: the greatest value first, and the smallest key
+/// on ties. The lowest ranked element is the greatest, so that it is at the top of the heap.
+struct Ranked<'a, K, V, C> {
+    key: K,
+    value: V,
+    compare: &'a C,
+}
+
+impl<K, V, C> Ord for Ranked<'_, K, V, C>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.compare)(&other.value, &self.value).then_with(|| self.key.cmp(&other.key))
+    }
+}
+
+impl<K, V, C> PartialOrd for Ranked<'_, K, V, C>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, V, C> PartialEq for Ranked<'_, K, V, C>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K, V, C> Eq for Ranked<'_, K, V, C>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+}
+
+/// A capped map sorted by rank.
+/// It will keep the top `capacity` elements, according to the given comparator.
+/// Ties are resolved by key, keeping the smallest keys.
+struct CappedRankedMap<'a, K, V, C> {
+    heap: BinaryHeap<Ranked<'a, K, V, C>>,
     capacity: usize,
+    compare: &'a C,
 }
 
-impl<K, V> CappedMinHeapMap<K, V>
+impl<'a, K, V, C> CappedRankedMap<'a, K, V, C>
 where
     K: Ord,
-    V: Ord,
+    C: Fn(&V, &V) -> Ordering,
 {
-    /// Creates a new `CappedMinHeapMap` instance.
-    fn new(capacity: usize) -> Self {
+    /// Creates a new `CappedRankedMap` instance.
+    fn new(capacity: usize, compare: &'a C) -> Self {
         Self {
-            heap: BinaryHeap::new(),
+            heap: BinaryHeap::with_capacity(capacity.min(MAX_PREALLOCATED_ELEMENTS)),
             capacity,
+            compare,
         }
     }
 
-    /// Pushes a key-value pair into the heap.
-    /// If the heap is full, it will remove the smallest element.
+    /// Pushes a key-value pair into the map.
+    /// If the map is full, it will replace the lowest ranked element, if the new one ranks higher.
     fn push(&mut self, key: K, value: V) {
-        self.heap.push((Reverse(value), key));
+        let element = Ranked {
+            key,
+            value,
+            compare: self.compare,
+        };
 
-        if self.heap.len() > self.capacity {
-            self.heap.pop();
+        if self.heap.len() < self.capacity {
+            self.heap.push(element);
+        } else if let Some(mut lowest) = self.heap.peek_mut() {
+            if element < *lowest {
+                *lowest = element;
+            }
         }
     }
 
-    /// Consumes the heap and returns an iterator with the key-value pairs.
-    fn into_iter(self) -> impl Iterator<Item = (K, V)> {
+    /// Consumes the map and returns an iterator with the key-value pairs, from the highest to the lowest ranked.
+    fn into_iter(self) -> impl Iterator<Item = (K, V)> + use<'a, K, V, C> {
         self.heap
+            .into_sorted_vec()
             .into_iter()
-            .map(|(Reverse(value), key)| (key, value))
+            .map(|element| (element.key, element.value))
     }
 
-    /// Merges another `CappedMinHeapMap` into this one.
+    /// Merges another `CappedRankedMap` into this one.
     fn merge(&mut self, other: Self) {
         for (key, value) in other.into_iter() {
             self.push(key, value);
@@ -54,29 +116,35 @@ where
 /// The map will be modified in place.
 /// If the map has less elements than `top_count`, all elements will be kept.
 /// If the map is empty, it will remain empty.
-/// The elements are retained based on their `Ord` implementation.
+/// The elements are ranked with the `compare` function, where a greater value ranks higher.
+/// If there is a tie, the elements with the smallest keys are kept.
 /// The operation is parallelized using the given thread pool.
-pub fn retain_top_elements<K, V>(elements: &mut HashMap<K, V>, top_count: usize, pool: &ThreadPool)
-where
+pub fn retain_top_elements<K, V, C>(
+    elements: &mut HashMap<K, V>,
+    top_count: usize,
+    compare: C,
+    pool: &ThreadPool,
+) where
     K: Hash + Ord + Send,
-    V: Ord + Send,
+    V: Send,
+    C: Fn(&V, &V) -> Ordering + Sync,
 {
     pool.install(|| {
         let top_elements = elements
             .drain()
             .par_bridge()
             .fold(
-                || CappedMinHeapMap::new(top_count),
-                |mut acc_heap, (key, value)| {
-                    acc_heap.push(key, value);
-                    acc_heap
+                || CappedRankedMap::new(top_count, &compare),
+                |mut acc_map, (key, value)| {
+                    acc_map.push(key, value);
+                    acc_map
                 },
             )
             .reduce(
-                || CappedMinHeapMap::new(top_count),
-                |mut acc_heap, local_heap| {
-                    acc_heap.merge(local_heap);
-                    acc_heap
+                || CappedRankedMap::new(top_count, &compare),
+                |mut acc_map, local_map| {
+                    acc_map.merge(local_map);
+                    acc_map
                 },
             );
 
@@ -84,6 +152,60 @@ where
     })
 }
 
+/// Returns the elements of the map sorted by rank, from the highest to the lowest.
+/// The elements are ranked with the `compare` function, where a greater value ranks higher.
+/// Ties are resolved by key, the smallest first, as in [`retain_top_elements`].
+pub fn ranked_elements<K, V, C>(elements: &HashMap<K, V>, compare: C) -> Vec<(&K, &V)>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+    let mut ranked = elements.iter().collect::<Vec<_>>();
+    ranked.sort_by(|(key_1, value_1), (key_2, value_2)| {
+        compare(value_2, value_1).then_with(|| key_1.cmp(key_2))
+    });
+    ranked
+}
+
+/// Returns the top `top_count` elements of the map sorted by rank, from the highest to the lowest,
+/// without sorting the rest of them. The elements are ranked as in [`ranked_elements`].
+pub fn top_elements<K, V, C>(
+    elements: &HashMap<K, V>,
+    top_count: usize,
+    compare: C,
+) -> Vec<(&K, &V)>
+where
+    K: Ord,
+    C: Fn(&V, &V) -> Ordering,
+{
+    let compare = |value_1: &&V, value_2: &&V| compare(value_1, value_2);
+    let mut top = CappedRankedMap::new(top_count.min(elements.len()), &compare);
+    for (key, value) in elements {
+        top.push(key, value);
+    }
+    top.into_iter().collect()
+}
+
+/// Returns the rank of the element with the given key, starting at 1 for the highest ranked element,
+/// or `None` if the map does not have it. The elements are ranked as in [`ranked_elements`].
+pub fn element_rank<K, V, C, Q>(elements: &HashMap<K, V>, key: &Q, compare: C) -> Option<usize>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Ord + ?Sized,
+    C: Fn(&V, &V) -> Ordering,
+{
+    let value = elements.get(key)?;
+    let higher_ranked = elements
+        .iter()
+        .filter(|(other_key, other_value)| {
+            compare(other_value, value).then_with(|| key.cmp((*other_key).borrow()))
+                == Ordering::Greater
+        })
+        .count();
+
+    Some(higher_ranked + 1)
+}
+
 #[cfg(test)]
 mod tests {
     use rayon::ThreadPoolBuilder;
@@ -103,7 +225,7 @@ mod tests {
             .into_iter()
             .collect();
 
-        retain_top_elements(&mut elements, 3, &pool());
+        retain_top_elements(&mut elements, 3, Ord::cmp, &pool());
 
         assert_eq!(elements.len(), 3);
         assert_eq!(elements.get(&3), Some(&3));
@@ -115,7 +237,7 @@ mod tests {
     fn test_retain_empty_map() {
         let mut elements: HashMap<usize, usize> = HashMap::new();
 
-        retain_top_elements(&mut elements, 3, &pool());
+        retain_top_elements(&mut elements, 3, Ord::cmp, &pool());
 
         assert!(elements.is_empty());
     }
@@ -124,10 +246,91 @@ mod tests {
     fn test_retain_less_elements_than_capacity() {
         let mut elements = vec![(1, 1), (2, 2)].into_iter().collect();
 
-        retain_top_elements(&mut elements, 3, &pool());
+        retain_top_elements(&mut elements, 3, Ord::cmp, &pool());
 
         assert_eq!(elements.len(), 2);
         assert_eq!(elements.get(&1), Some(&1));
         assert_eq!(elements.get(&2), Some(&2));
     }
+
+    #[test]
+    fn test_retain_with_comparator() {
+        let mut elements = vec![(1, 1), (2, 2), (3, 3)].into_iter().collect();
+
+        retain_top_elements(&mut elements, 2, |a: &i32, b: &i32| b.cmp(a), &pool());
+
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements.get(&1), Some(&1));
+        assert_eq!(elements.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_retain_ties_keep_smallest_keys() {
+        let mut elements = vec![(3, 1), (1, 1), (2, 1)].into_iter().collect();
+
+        retain_top_elements(&mut elements, 2, Ord::cmp, &pool());
+
+        assert_eq!(elements.len(), 2);
+        assert!(elements.contains_key(&1));
+        assert!(elements.contains_key(&2));
+    }
+
+    #[test]
+    fn test_retain_zero_elements() {
+        let mut elements: HashMap<usize, usize> = vec![(1, 1)].into_iter().collect();
+
+        retain_top_elements(&mut elements, 0, Ord::cmp, &pool());
+
+        assert!(elements.is_empty());
+    }
+
+    #[test]
+    fn test_retain_with_maximum_capacity() {
+        let mut elements = vec![(1, 1), (2, 2)].into_iter().collect();
+
+        retain_top_elements(&mut elements, usize::MAX, Ord::cmp, &pool());
+
+        assert_eq!(elements.len(), 2);
+    }
+
+    #[test]
+    fn test_ranked_elements_ties_by_key() {
+        let elements: HashMap<&str, usize> =
+            vec![("c", 1), ("b", 2), ("a", 1)].into_iter().collect();
+
+        let ranked = ranked_elements(&elements, Ord::cmp);
+
+        assert_eq!(ranked, vec![(&"b", &2), (&"a", &1), (&"c", &1)]);
+    }
+
+    #[test]
+    fn test_top_elements_and_ranks() {
+        let elements: HashMap<&str, usize> = vec![("a", 1), ("b", 3), ("c", 3), ("d", 2)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            top_elements(&elements, 3, Ord::cmp),
+            vec![(&"b", &3), (&"c", &3), (&"d", &2)]
+        );
+        assert_eq!(top_elements(&elements, usize::MAX, Ord::cmp).len(), 4);
+        assert_eq!(element_rank(&elements, &"b", Ord::cmp), Some(1));
+        assert_eq!(element_rank(&elements, &"c", Ord::cmp), Some(2));
+        assert_eq!(element_rank(&elements, &"a", Ord::cmp), Some(4));
+        assert_eq!(element_rank(&elements, &"e", Ord::cmp), None);
+    }
+
+    #[test]
+    fn test_top_elements_match_the_ranked_elements() {
+        let elements: HashMap<usize, usize> = (0..1000).map(|key| (key, key * 7 % 31)).collect();
+
+        let ranked = ranked_elements(&elements, Ord::cmp);
+
+        assert_eq!(top_elements(&elements, 50, Ord::cmp), ranked[..50]);
+        let mut retained = elements.clone();
+        retain_top_elements(&mut retained, 50, Ord::cmp, &pool());
+        assert!(ranked[..50]
+            .iter()
+            .all(|(key, value)| retained.get(key) == Some(value)));
+    }
 }
@@ -1,88 +1,194 @@
-use std::collections::HashMap;
-
 use rayon::{prelude::*, ThreadPool};
+use std::sync::Mutex;
 
 use crate::{
-    deaths::Death, player_stats::PlayerStats, sorting::retain_top_elements,
-    weapon_stats::WeaponStats,
+    aggregator::{Aggregator, CustomAggregators, DynAggregator},
+    deaths::Death,
+    distance_histogram::DistanceHistogram,
+    group_by::GroupByAggregator,
+    player_stats::PlayersAggregator,
+    ranking::RankingMetric,
+    snapshot::{Decoder, Encoder},
+    weapon_stats::WeaponsAggregator,
 };
 
+/// Deaths aggregated by a thread before merging them into the shared stats, see [`Stats::merge_deaths_into`].
+const PARTIAL_STATS_DEATHS: usize = 10_000;
+
 /// A struct that holds the stats of the game.
 pub struct Stats {
-    total_deaths: usize,
-    players: HashMap<String, PlayerStats>,
-    weapons: HashMap<String, WeaponStats>,
+    players: PlayersAggregator,
+    weapons: WeaponsAggregator,
+    distances: DistanceHistogram,
+    groups: Option<GroupByAggregator>,
+    custom: Vec<(String, Box<dyn DynAggregator>)>,
 }
 
 impl Stats {
-    /// Creates a new empty `Stats` instance.
-    fn new() -> Self {
+    /// Creates a new empty `Stats` instance, with an empty instance of each custom metric.
+    pub fn new(custom: &CustomAggregators) -> Self {
+        let group_by = custom.group_by_fields();
+
         Self {
-            total_deaths: 0,
-            players: HashMap::new(),
-            weapons: HashMap::new(),
+            players: PlayersAggregator::new(),
+            weapons: WeaponsAggregator::new(),
+            distances: DistanceHistogram::new(),
+            groups: (!group_by.is_empty()).then(|| GroupByAggregator::new(group_by.to_vec())),
+            custom: custom.instantiate(),
         }
     }
 
     /// Creates a new `Stats` instance from a parallel iterator of `Death` instances.
     /// The `pool` parameter is used to parallelize the processing of the deaths.
     pub fn from_deaths(deaths: impl ParallelIterator<Item = Death>, pool: &ThreadPool) -> Self {
+        Self::from_deaths_with(deaths, &CustomAggregators::new(), pool)
+    }
+
+    /// Creates a new `Stats` instance from a parallel iterator of `Death` instances,
+    /// computing the given custom metrics in the same pass as the players and weapons stats.
+    /// The `pool` parameter is used to parallelize the processing of the deaths.
+    pub fn from_deaths_with(
+        deaths: impl ParallelIterator<Item = Death>,
+        custom: &CustomAggregators,
+        pool: &ThreadPool,
+    ) -> Self {
         pool.install(|| {
             deaths
-                .fold(Stats::new, |mut acc, death| {
-                    acc.total_deaths += 1;
-
-                    let death_distance = death.distance();
-                    let killer_name = death.killer_name;
-                    let killed_by = death.killed_by;
-                    let killed_by_clone = killed_by.clone();
-
-                    if let Some(killer_name) = killer_name {
-                        acc.players
-                            .entry(killer_name)
-                            .or_insert_with(PlayerStats::new)
-                            .add_death(killed_by_clone);
-                    }
+                .fold(
+                    || Stats::new(custom),
+                    |mut acc, death| {
+                        acc.fold(&death);
+                        acc
+                    },
+                )
+                .reduce(
+                    || Stats::new(custom),
+                    |mut acc1, acc2| {
+                        acc1.merge(acc2);
+                        acc1
+                    },
+                )
+        })
+    }
 
-                    if let Some(killed_by) = killed_by {
-                        acc.weapons
-                            .entry(killed_by)
-                            .or_insert_with(WeaponStats::new)
-                            .add_death(death_distance);
-                    }
+    /// Aggregates the deaths into the shared `merged` stats, which must have been created with the same `custom`
+    /// metrics. Each thread merges its partial stats every few thousand deaths, so the shared stats can be read
+    /// while the deaths are still being aggregated, e.g. to show a live leaderboard.
+    pub fn merge_deaths_into(
+        merged: &Mutex<Stats>,
+        deaths: impl ParallelIterator<Item = Death>,
+        custom: &CustomAggregators,
+        pool: &ThreadPool,
+    ) {
+        let merge = |partial: Stats| match merged.lock() {
+            Ok(mut merged) => merged.merge(partial),
+            Err(poisoned) => poisoned.into_inner().merge(partial),
+        };
 
-                    acc
-                })
-                .reduce(Stats::new, |mut acc1, acc2| {
-                    acc1.merge(acc2);
-                    acc1
-                })
+        pool.install(|| {
+            deaths
+                .fold(
+                    || (Stats::new(custom), 0),
+                    |(mut acc, count), death| {
+                        acc.fold(&death);
+                        if count + 1 < PARTIAL_STATS_DEATHS {
+                            return (acc, count + 1);
+                        }
+                        merge(acc);
+                        (Stats::new(custom), 0)
+                    },
+                )
+                .for_each(|(acc, _)| merge(acc))
         })
     }
 
-    /// Merges another `Stats` instance into this one.
-    fn merge(&mut self, other: Stats) {
-        self.total_deaths += other.total_deaths;
+    /// Adds a death to the stats.
+    fn fold(&mut self, death: &Death) {
+        self.players.fold(death);
+        self.weapons.fold(death);
+        self.distances.fold(death);
 
-        for (name, other_player_stats) in other.players {
-            if let Some(player_stats) = self.players.get_mut(&name) {
-                player_stats.merge(other_player_stats);
-            } else {
-                self.players.insert(name, other_player_stats);
-            }
+        if let Some(groups) = self.groups.as_mut() {
+            groups.fold(death);
+        }
+
+        for (_, aggregator) in self.custom.iter_mut() {
+            aggregator.fold_death(death);
+        }
+    }
+
+    /// Merges another `Stats` instance into this one, e.g. the stats of another batch loaded from a snapshot.
+    /// The groups are only merged if both instances have them, and the custom metrics if they were created with the
+    /// same [`CustomAggregators`]. The stats should be merged before filtering the top elements.
+    pub fn merge(&mut self, other: Stats) {
+        self.players.merge(other.players);
+        self.weapons.merge(other.weapons);
+        self.distances.merge(&other.distances);
+
+        if let (Some(groups), Some(other_groups)) = (self.groups.as_mut(), other.groups) {
+            groups.merge(other_groups);
         }
 
-        for (name, other_weapon_stats) in other.weapons {
-            self.weapons
-                .entry(name)
-                .and_modify(|main_weapon_stats| {
-                    main_weapon_stats.merge(&other_weapon_stats);
-                })
-                .or_insert(other_weapon_stats);
+        for ((_, aggregator), (_, other_aggregator)) in self.custom.iter_mut().zip(other.custom) {
+            aggregator.merge_boxed(other_aggregator);
         }
     }
 
-    /// Filters the top `player_count` players and the top `weapon_count` weapons of each player.
+    /// Returns a copy of the players, weapons, distances and groups, without the custom metrics,
+    /// which cannot be cloned. Used to filter the top elements while keeping the unfiltered stats.
+    pub fn clone_without_custom(&self) -> Stats {
+        Self {
+            players: self.players.clone(),
+            weapons: self.weapons.clone(),
+            distances: self.distances.clone(),
+            groups: self.groups.clone(),
+            custom: Vec::new(),
+        }
+    }
+
+    /// Writes the players, weapons and distances, see [`crate::save_snapshot`].
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        self.players.encode(encoder);
+        self.weapons.encode(encoder);
+        self.distances.encode(encoder);
+    }
+
+    /// Reads the stats written by [`Stats::encode`], without groups nor custom metrics.
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            players: PlayersAggregator::decode(decoder)?,
+            weapons: WeaponsAggregator::decode(decoder)?,
+            distances: DistanceHistogram::decode(decoder)?,
+            ..Self::new(&CustomAggregators::new())
+        })
+    }
+
+    /// Returns the total number of deaths processed.
+    pub fn total_deaths(&self) -> usize {
+        self.weapons.total_deaths()
+    }
+
+    /// Returns the aggregated stats of the players.
+    pub fn players(&self) -> &PlayersAggregator {
+        &self.players
+    }
+
+    /// Returns the aggregated stats of the weapons.
+    pub fn weapons(&self) -> &WeaponsAggregator {
+        &self.weapons
+    }
+
+    /// Returns the histogram of the distances of every death, with or without weapon.
+    pub fn distances(&self) -> &DistanceHistogram {
+        &self.distances
+    }
+
+    /// Returns the nested groups of deaths, if the deaths were grouped by some fields.
+    pub fn groups(&self) -> Option<&GroupByAggregator> {
+        self.groups.as_ref()
+    }
+
+    /// Filters the top `player_count` players by kills and the top `weapon_count` weapons of each player.
     /// The filtering is done in parallel using the `pool` parameter.
     pub fn filter_top_killers(
         &mut self,
@@ -90,45 +196,74 @@ impl Stats {
         weapon_count: usize,
         pool: &ThreadPool,
     ) {
-        retain_top_elements(&mut self.players, player_count, pool);
+        self.filter_top_killers_by(player_count, weapon_count, RankingMetric::Kills, pool);
+    }
 
-        self.players.iter_mut().for_each(|(_, player_stats)| {
-            player_stats.filter_top_weapons(weapon_count, pool);
-        });
+    /// Filters the top `player_count` players ranked by `metric`, and the top `weapon_count` weapons of each player by kills.
+    /// The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top_killers_by(
+        &mut self,
+        player_count: usize,
+        weapon_count: usize,
+        metric: RankingMetric,
+        pool: &ThreadPool,
+    ) {
+        let total_deaths = self.total_deaths();
+        self.players
+            .filter_top(player_count, weapon_count, metric, total_deaths, pool);
     }
 
-    /// Filters the top `weapon_count` weapons.
+    /// Filters the top `weapon_count` weapons by kills.
     /// The filtering is done in parallel using the `pool` parameter.
     pub fn filter_top_weapons(&mut self, weapon_count: usize, pool: &ThreadPool) {
-        retain_top_elements(&mut self.weapons, weapon_count, pool);
+        self.filter_top_weapons_by(weapon_count, RankingMetric::Kills, pool);
+    }
+
+    /// Filters the top `weapon_count` weapons ranked by `metric`.
+    /// The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top_weapons_by(
+        &mut self,
+        weapon_count: usize,
+        metric: RankingMetric,
+        pool: &ThreadPool,
+    ) {
+        self.weapons.filter_top(weapon_count, metric, pool);
+    }
+
+    /// Filters the top `group_count` groups by number of deaths, at every level of the grouping.
+    /// The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top_groups(&mut self, group_count: usize, pool: &ThreadPool) {
+        if let Some(groups) = self.groups.as_mut() {
+            groups.filter_top(group_count, pool);
+        }
     }
 
     /// Returns the stats of the game in a JSON format.
+    /// The groups are included under the `groups` key, and each custom metric with its registered name as key.
     pub fn json_display(&self) -> serde_json::Value {
-        let top_killers = self
-            .players
-            .iter()
-            .map(|(player_name, player_stats)| (player_name, player_stats.json_display()))
-            .collect::<HashMap<_, _>>();
-
-        let top_weapons = self
-            .weapons
-            .iter()
-            .map(|(weapon_name, weapon_stats)| {
-                (weapon_name, weapon_stats.json_display(self.total_deaths))
-            })
-            .collect::<HashMap<_, _>>();
+        let mut json = serde_json::json!({
+            "top_killers": self.players.render(),
+            "top_weapons": self.weapons.render(),
+        });
 
-        serde_json::json!({
-            "top_killers": top_killers,
-            "top_weapons": top_weapons,
-        })
+        if let Some(obj) = json.as_object_mut() {
+            if let Some(groups) = &self.groups {
+                obj.insert("groups".to_string(), groups.render());
+            }
+
+            for (name, aggregator) in &self.custom {
+                obj.insert(name.clone(), aggregator.render_json());
+            }
+        }
+
+        json
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fields::Field;
     use assert_json_diff::assert_json_eq;
     use rayon::ThreadPoolBuilder;
     use serde_json::json;
@@ -153,36 +288,36 @@ mod tests {
     fn test_stats_from_deaths() {
         let stats = stats_from_deaths(vec![DEATH_RECORD_1]);
 
-        assert_eq!(stats.total_deaths, 1);
-        assert_eq!(stats.players.len(), 1);
-        assert_eq!(stats.weapons.len(), 1);
+        assert_eq!(stats.total_deaths(), 1);
+        assert_eq!(stats.players.as_map().len(), 1);
+        assert_eq!(stats.weapons.as_map().len(), 1);
     }
 
     #[test]
     fn test_stats_from_multiple_deaths() {
         let stats = stats_from_deaths(vec![DEATH_RECORD_1, DEATH_RECORD_1]);
 
-        assert_eq!(stats.total_deaths, 2);
-        assert_eq!(stats.players.len(), 1);
-        assert_eq!(stats.weapons.len(), 1);
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(stats.players.as_map().len(), 1);
+        assert_eq!(stats.weapons.as_map().len(), 1);
     }
 
     #[test]
     fn test_stats_from_multiple_players() {
         let stats = stats_from_deaths(vec![DEATH_RECORD_1, DEATH_RECORD_2]);
 
-        assert_eq!(stats.total_deaths, 2);
-        assert_eq!(stats.players.len(), 2);
-        assert_eq!(stats.weapons.len(), 1);
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(stats.players.as_map().len(), 2);
+        assert_eq!(stats.weapons.as_map().len(), 1);
     }
 
     #[test]
     fn test_stats_from_multiple_weapons() {
         let stats = stats_from_deaths(vec![DEATH_RECORD_1, DEATH_RECORD_3]);
 
-        assert_eq!(stats.total_deaths, 2);
-        assert_eq!(stats.players.len(), 1);
-        assert_eq!(stats.weapons.len(), 2);
+        assert_eq!(stats.total_deaths(), 2);
+        assert_eq!(stats.players.as_map().len(), 1);
+        assert_eq!(stats.weapons.as_map().len(), 2);
     }
 
     #[test]
@@ -192,9 +327,33 @@ mod tests {
 
         stats_1.merge(stats_2);
 
-        assert_eq!(stats_1.total_deaths, 3);
-        assert_eq!(stats_1.players.len(), 2);
-        assert_eq!(stats_1.weapons.len(), 2);
+        assert_eq!(stats_1.total_deaths(), 3);
+        assert_eq!(stats_1.players.as_map().len(), 2);
+        assert_eq!(stats_1.weapons.as_map().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_deaths_into_matches_from_deaths() {
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let deaths = (0..PARTIAL_STATS_DEATHS * 2 + 5)
+            .map(|i| {
+                let record = [DEATH_RECORD_1, DEATH_RECORD_2, DEATH_RECORD_3][i % 3];
+                Death::from_csv_record(record.to_string()).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let merged = Mutex::new(Stats::new(&CustomAggregators::new()));
+
+        Stats::merge_deaths_into(
+            &merged,
+            deaths.clone().into_par_iter(),
+            &CustomAggregators::new(),
+            &pool,
+        );
+
+        let merged = merged.into_inner().unwrap();
+        let stats = Stats::from_deaths(deaths.into_par_iter(), &pool);
+        assert_eq!(merged.total_deaths(), PARTIAL_STATS_DEATHS * 2 + 5);
+        assert_eq!(merged.json_display(), stats.json_display());
     }
 
     #[test]
@@ -203,8 +362,8 @@ mod tests {
 
         stats.filter_top_killers(1, 1, &pool());
 
-        assert_eq!(stats.players.len(), 1);
-        assert_eq!(stats.weapons.len(), 1);
+        assert_eq!(stats.players.as_map().len(), 1);
+        assert_eq!(stats.weapons.as_map().len(), 1);
     }
 
     #[test]
@@ -213,7 +372,7 @@ mod tests {
 
         stats.filter_top_weapons(1, &pool());
 
-        assert_eq!(stats.weapons.len(), 1);
+        assert_eq!(stats.weapons.as_map().len(), 1);
     }
 
     #[test]
@@ -222,9 +381,9 @@ mod tests {
 
         stats.filter_top_killers(1, 1, &pool());
 
-        assert_eq!(stats.players.len(), 1);
-        assert!(stats.players.contains_key("Player1"));
-        assert!(!stats.players.contains_key("Player2"));
+        assert_eq!(stats.players.as_map().len(), 1);
+        assert!(stats.players.as_map().contains_key("Player1"));
+        assert!(!stats.players.as_map().contains_key("Player2"));
     }
 
     #[test]
@@ -233,8 +392,8 @@ mod tests {
 
         stats.filter_top_weapons(1, &pool());
 
-        assert_eq!(stats.weapons.len(), 1);
-        assert!(stats.weapons.contains_key("AK47"));
+        assert_eq!(stats.weapons.as_map().len(), 1);
+        assert!(stats.weapons.as_map().contains_key("AK47"));
     }
 
     #[test]
@@ -246,6 +405,7 @@ mod tests {
         let expected_json = json!({
             "top_killers": {
                 "Player1": {
+                    "rank": 1,
                     "deaths": 2,
                     "weapons_percentage": {
                         "AK47": 50.0,
@@ -255,10 +415,12 @@ mod tests {
             },
             "top_weapons": {
                 "AK47": {
+                    "rank": 1,
                     "deaths_percentage": 50.0,
                     "average_distance": 100.0
                 },
                 "M4A4": {
+                    "rank": 2,
                     "deaths_percentage": 50.0,
                     "average_distance": 100.0
                 }
@@ -267,4 +429,182 @@ mod tests {
 
         assert_json_eq!(expected_json, json_stats);
     }
+
+    #[test]
+    fn test_json_display_is_ordered_by_rank() {
+        let mut stats = stats_from_deaths(vec![
+            DEATH_RECORD_2,
+            DEATH_RECORD_3,
+            DEATH_RECORD_1,
+            DEATH_RECORD_3,
+        ]);
+        stats.filter_top_killers(10, 10, &pool());
+        stats.filter_top_weapons(10, &pool());
+
+        let json_stats = stats.json_display();
+        let keys = |value: &serde_json::Value| {
+            value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(&json_stats["top_killers"]), vec!["Player1", "Player2"]);
+        assert_eq!(
+            keys(&json_stats["top_killers"]["Player1"]["weapons_percentage"]),
+            vec!["M4A4", "AK47"]
+        );
+        assert_eq!(keys(&json_stats["top_weapons"]), vec!["AK47", "M4A4"]);
+        assert_eq!(json_stats["top_weapons"]["M4A4"]["rank"], json!(2));
+    }
+
+    #[derive(Default)]
+    struct DeathsWithoutKiller(usize);
+
+    impl Aggregator for DeathsWithoutKiller {
+        fn fold(&mut self, death: &Death) {
+            if death.killer_name.is_none() {
+                self.0 += 1;
+            }
+        }
+
+        fn merge(&mut self, other: Self) {
+            self.0 += other.0;
+        }
+
+        fn render(&self) -> serde_json::Value {
+            json!(self.0)
+        }
+    }
+
+    #[test]
+    fn test_custom_aggregator_in_json_display() {
+        const NO_KILLER_RECORD: &str = "Bluezone,,,,,map,match-id,123,Player2,1.0,100.0,0.0";
+
+        let mut custom = CustomAggregators::new();
+        custom.register("deaths_without_killer", DeathsWithoutKiller::default);
+
+        let deaths = vec![DEATH_RECORD_1, NO_KILLER_RECORD, NO_KILLER_RECORD]
+            .into_par_iter()
+            .map(|record| Death::from_csv_record(record.to_string()).unwrap());
+        let stats = Stats::from_deaths_with(deaths, &custom, &pool());
+
+        assert_eq!(stats.total_deaths(), 3);
+        assert_eq!(stats.json_display()["deaths_without_killer"], json!(2));
+    }
+
+    #[test]
+    fn test_groups_in_json_display() {
+        const OTHER_MAP_RECORD: &str =
+            "AK47,Player1,1.0,0.0,0.0,other-map,match-id,123,Player2,1.0,100.0,0.0";
+
+        let mut custom = CustomAggregators::new();
+        custom.group_by(vec![Field::Map, Field::KilledBy]);
+
+        let deaths = vec![
+            DEATH_RECORD_1,
+            DEATH_RECORD_3,
+            DEATH_RECORD_3,
+            OTHER_MAP_RECORD,
+        ]
+        .into_par_iter()
+        .map(|record| Death::from_csv_record(record.to_string()).unwrap());
+        let mut stats = Stats::from_deaths_with(deaths, &custom, &pool());
+        stats.filter_top_groups(1, &pool());
+
+        let groups = &stats.json_display()["groups"];
+        assert_eq!(groups.as_object().unwrap().len(), 1);
+        assert_eq!(groups["map"]["count"], json!(3));
+        assert_eq!(groups["map"]["share"], json!(75.0));
+        assert_eq!(groups["map"]["groups"]["M4A4"]["count"], json!(2));
+        assert!(groups["map"]["groups"].get("AK47").is_none());
+    }
+
+    #[test]
+    fn test_json_display_does_not_depend_on_number_of_threads() {
+        // Distances with many decimals, whose naive sums change with the order of the additions
+        let deaths = (0..5000u64)
+            .map(|i| {
+                let x = (i * 7919 % 10007) as f64 / 7.0;
+                let y = (i * 104729 % 1009) as f64 * 0.1;
+                Death::new(
+                    Some(["AK47", "M4A4", "AWM"][i as usize % 3].to_string()),
+                    Some(format!("Player{}", i % 11)),
+                    Some(format!("Player{}", i % 17)),
+                    Some(x * 1e3),
+                    Some(0.0),
+                    Some(0.0),
+                    Some(y),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let outputs = (1..=16)
+            .map(|threads| {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                let mut stats = Stats::from_deaths(deaths.clone().into_par_iter(), &pool);
+                stats.filter_top_killers_by(5, 3, RankingMetric::AverageDistance, &pool);
+                stats.filter_top_weapons(3, &pool);
+                // The unrounded averages are compared too, since the JSON rounds them
+                let average_distances = stats
+                    .weapons()
+                    .ranked()
+                    .iter()
+                    .map(|(_, weapon_stats)| weapon_stats.average_distance())
+                    .collect::<Vec<_>>();
+                (stats.json_display().to_string(), average_distances)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(outputs.iter().all(|output| *output == outputs[0]));
+    }
+
+    #[test]
+    fn test_filter_top_killers_by_kill_death_ratio() {
+        const PLAYER_1_KILLS_PLAYER_3: &str =
+            "AK47,Player1,1.0,0.0,0.0,map,match-id,123,Player3,1.0,100.0,0.0";
+        const PLAYER_2_KILLS_PLAYER_3: &str =
+            "AK47,Player2,1.0,0.0,0.0,map,match-id,123,Player3,1.0,100.0,0.0";
+        const PLAYER_3_KILLS_PLAYER_1: &str =
+            "AK47,Player3,1.0,0.0,0.0,map,match-id,123,Player1,1.0,100.0,0.0";
+
+        // Player1 has the most kills (3) but was killed twice, Player2 has 2 kills and was never killed
+        let deaths = vec![
+            PLAYER_1_KILLS_PLAYER_3,
+            PLAYER_1_KILLS_PLAYER_3,
+            PLAYER_1_KILLS_PLAYER_3,
+            PLAYER_2_KILLS_PLAYER_3,
+            PLAYER_2_KILLS_PLAYER_3,
+            PLAYER_3_KILLS_PLAYER_1,
+            PLAYER_3_KILLS_PLAYER_1,
+        ];
+
+        let mut by_kills = stats_from_deaths(deaths.clone());
+        by_kills.filter_top_killers(1, 1, &pool());
+
+        let mut by_kill_death_ratio = stats_from_deaths(deaths);
+        by_kill_death_ratio.filter_top_killers_by(1, 1, RankingMetric::KillDeathRatio, &pool());
+
+        assert!(by_kills.players.as_map().contains_key("Player1"));
+        assert_eq!(by_kill_death_ratio.players.as_map().len(), 1);
+        assert!(by_kill_death_ratio.players.as_map().contains_key("Player2"));
+    }
+
+    #[test]
+    fn test_filter_top_weapons_by_average_distance() {
+        const DEATH_RECORD_4: &str =
+            "M4A4,Player1,1.0,0.0,0.0,map,match-id,123,Player2,1.0,500.0,0.0";
+
+        let mut stats = stats_from_deaths(vec![DEATH_RECORD_1, DEATH_RECORD_1, DEATH_RECORD_4]);
+
+        stats.filter_top_weapons_by(1, RankingMetric::AverageDistance, &pool());
+
+        assert_eq!(stats.weapons.as_map().len(), 1);
+        assert!(stats.weapons.as_map().contains_key("M4A4"));
+    }
 }
@@ -0,0 +1,509 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    config::AnalysisConfig,
+    player_stats::PlayersAggregator,
+    prometheus_writting::{
+        prometheus_metrics, AggregateMetrics, RowCounts, PROMETHEUS_CONTENT_TYPE,
+    },
+    ranking::ranked_json,
+    stats::Stats,
+    weapon_stats::WeaponsAggregator,
+};
+
+/// Query parameter with the number of elements of a leaderboard.
+const COUNT_PARAMETER: &str = "n";
+/// Content type of the JSON responses.
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Every player and weapon served by the HTTP API, not only the top ones, ranked by the metrics of the configuration.
+pub struct Leaderboards {
+    players: PlayersAggregator,
+    weapons: WeaponsAggregator,
+    top_players_count: usize,
+    top_weapons_count: usize,
+    top_weapons_of_player_count: usize,
+    /// Largest count a request can ask for.
+    max_count: usize,
+    /// Metrics of the run that loaded the stats, in the Prometheus text format.
+    metrics: String,
+}
+
+impl Leaderboards {
+    /// Copies the players and weapons of the stats and ranks them by the metrics of the configuration.
+    /// The sizes of the leaderboards of the configuration are used when a request does not give them, up to the
+    /// maximum count of the configuration.
+    pub fn new(stats: &Stats, config: &AnalysisConfig) -> Self {
+        let mut players = stats.players().clone();
+        let mut weapons = stats.weapons().clone();
+        players.rank_by(config.players_ranking, stats.total_deaths());
+        weapons.rank_by(config.weapons_ranking);
+
+        Self {
+            players,
+            weapons,
+            top_players_count: config.top_players_count,
+            top_weapons_count: config.top_weapons_count,
+            top_weapons_of_player_count: config.top_weapons_of_player_count,
+            max_count: config.max_count,
+            metrics: String::new(),
+        }
+    }
+
+    /// Copies the players and weapons of the stats like [`Leaderboards::new`], with the metrics of the run that read
+    /// them: the `rows` read and the `laps` of its timer.
+    pub fn of_run(
+        stats: &Stats,
+        config: &AnalysisConfig,
+        rows: RowCounts,
+        laps: &[(String, Duration)],
+    ) -> Self {
+        let metrics = prometheus_metrics(&AggregateMetrics::new(stats), rows, laps);
+        Self::new(stats, config).with_metrics(metrics)
+    }
+
+    /// Sets the metrics served by `/metrics`, see [`crate::prometheus_metrics`].
+    pub fn with_metrics(mut self, metrics: String) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the top players, with the same values as the JSON output of `analyze`.
+    pub fn top_killers(&self, count: usize) -> Value {
+        let players = self
+            .players
+            .top(count)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, player_stats))| {
+                let weapons = player_stats
+                    .weapons_percentage()
+                    .into_iter()
+                    .take(self.top_weapons_of_player_count)
+                    .map(|(weapon_name, percentage)| (weapon_name.clone(), json!(percentage)))
+                    .collect::<Map<_, _>>();
+                let stats = json!({
+                    "deaths": player_stats.kills(),
+                    "weapons_percentage": weapons,
+                });
+
+                (name.clone(), ranked_json(index + 1, stats))
+            })
+            .collect::<Map<_, _>>();
+
+        json!({ "top_killers": players })
+    }
+
+    /// Returns the top weapons, with the same values as the JSON output of `analyze`.
+    pub fn top_weapons(&self, count: usize) -> Value {
+        let total_deaths = self.weapons.total_deaths();
+        let weapons = self
+            .weapons
+            .top(count)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, weapon_stats))| {
+                (
+                    name.clone(),
+                    ranked_json(index + 1, weapon_stats.json_display(total_deaths)),
+                )
+            })
+            .collect::<Map<_, _>>();
+
+        json!({ "top_weapons": weapons })
+    }
+
+    /// Returns the rank and stats of a player, with all its weapons, or `None` if the player never killed.
+    pub fn player(&self, name: &str) -> Option<Value> {
+        let (Some(player_stats), Some(rank)) =
+            (self.players.as_map().get(name), self.players.rank_of(name))
+        else {
+            return None;
+        };
+
+        let stats = json!({
+            "name": name,
+            "deaths": player_stats.kills(),
+            "times_killed": player_stats.times_killed(),
+            "kill_death_ratio": rounded(player_stats.kill_death_ratio()),
+            "average_distance": rounded(player_stats.average_distance()),
+            "weapons_percentage": player_stats.json_display()["weapons_percentage"],
+        });
+
+        Some(ranked_json(rank, stats))
+    }
+
+    /// Returns the rank and stats of a weapon, or `None` if the weapon never killed.
+    pub fn weapon(&self, name: &str) -> Option<Value> {
+        let (Some(weapon_stats), Some(rank)) =
+            (self.weapons.as_map().get(name), self.weapons.rank_of(name))
+        else {
+            return None;
+        };
+
+        let mut stats = json!({
+            "name": name,
+            "deaths": weapon_stats.kills(),
+        });
+        if let (Some(obj), Value::Object(display)) = (
+            stats.as_object_mut(),
+            weapon_stats.json_display(self.weapons.total_deaths()),
+        ) {
+            obj.extend(display);
+        }
+
+        Some(ranked_json(rank, stats))
+    }
+}
+
+/// Leaderboards shared between the requests of the HTTP API and the ingestion that updates them.
+/// Requests keep a reference to the leaderboards they started with, so a slow request does not block the updates.
+pub struct SharedLeaderboards {
+    current: RwLock<Arc<Leaderboards>>,
+}
+
+impl SharedLeaderboards {
+    /// Creates the shared leaderboards with their initial value.
+    pub fn new(leaderboards: Leaderboards) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(leaderboards)),
+        }
+    }
+
+    /// Returns the current leaderboards.
+    pub fn load(&self) -> Arc<Leaderboards> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the leaderboards, which the following requests will use.
+    pub fn publish(&self, leaderboards: Leaderboards) {
+        let leaderboards = Arc::new(leaderboards);
+        match self.current.write() {
+            Ok(mut current) => *current = leaderboards,
+            Err(poisoned) => *poisoned.into_inner() = leaderboards,
+        }
+    }
+}
+
+/// Response of the HTTP API: a status code and a body, which is JSON except for the metrics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> Self {
+        Self {
+            status: 200,
+            content_type: JSON_CONTENT_TYPE,
+            body: body.to_string(),
+        }
+    }
+
+    /// Returns an error response, with the message under `error`.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: JSON_CONTENT_TYPE,
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// Answers a GET request to the given target, a path with an optional query string.
+///
+/// - `/top_killers?n=<count>`: the top players, with the same values as the JSON output of `analyze`.
+/// - `/top_weapons?n=<count>`: the top weapons, with the same values as the JSON output of `analyze`.
+/// - `/players/<name>`: the rank and stats of a player, with all its weapons.
+/// - `/weapons/<name>`: the rank and stats of a weapon.
+/// - `/metrics`: the metrics of the run, in the Prometheus text format.
+///
+/// Names are percent-decoded. Unknown paths, players and weapons are not found (404), and invalid counts or counts
+/// above the maximum of the configuration are bad requests (400).
+pub fn handle_request(leaderboards: &Leaderboards, target: &str) -> ApiResponse {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect::<Vec<_>>();
+
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["top_killers"] => match count_parameter(
+            query,
+            leaderboards.top_players_count,
+            leaderboards.max_count,
+        ) {
+            Ok(count) => ApiResponse::ok(leaderboards.top_killers(count)),
+            Err(response) => response,
+        },
+        ["top_weapons"] => match count_parameter(
+            query,
+            leaderboards.top_weapons_count,
+            leaderboards.max_count,
+        ) {
+            Ok(count) => ApiResponse::ok(leaderboards.top_weapons(count)),
+            Err(response) => response,
+        },
+        ["players", name] => match leaderboards.player(name) {
+            Some(player) => ApiResponse::ok(player),
+            None => ApiResponse::error(404, &format!("Unknown player: {}", name)),
+        },
+        ["weapons", name] => match leaderboards.weapon(name) {
+            Some(weapon) => ApiResponse::ok(weapon),
+            None => ApiResponse::error(404, &format!("Unknown weapon: {}", name)),
+        },
+        ["metrics"] => ApiResponse {
+            status: 200,
+            content_type: PROMETHEUS_CONTENT_TYPE,
+            body: leaderboards.metrics.clone(),
+        },
+        _ => ApiResponse::error(404, &format!("Unknown path: {}", path)),
+    }
+}
+
+/// Returns the count given by the query string, or the default count clamped to `max_count` if it has none.
+/// Returns a bad request response if the count is not a non-negative integer, or if it is above `max_count`.
+fn count_parameter(
+    query: &str,
+    default_count: usize,
+    max_count: usize,
+) -> Result<usize, ApiResponse> {
+    let value = query
+        .split('&')
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(key, _)| percent_decode(key) == COUNT_PARAMETER)
+        .map(|(_, value)| percent_decode(value));
+
+    match value {
+        Some(value) => match value.parse() {
+            Ok(count) if count <= max_count => Ok(count),
+            Ok(_) => Err(ApiResponse::error(
+                400,
+                &format!(
+                    "Value of {} above the maximum of {}: {}",
+                    COUNT_PARAMETER, max_count, value
+                ),
+            )),
+            Err(_) => Err(ApiResponse::error(
+                400,
+                &format!("Invalid value for {}: {}", COUNT_PARAMETER, value),
+            )),
+        },
+        None => Ok(default_count.min(max_count)),
+    }
+}
+
+/// Decodes the `%XX` escapes and the `+` of a component of the target.
+/// Invalid escapes are kept as they are, and invalid UTF-8 is replaced.
+fn percent_decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[index], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Rounds the value to 2 decimals, like the values of the JSON output.
+fn rounded(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPool, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::deaths::Death;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn death(killer: &str, weapon: &str, victim: &str, distance: f64) -> Death {
+        Death::new(
+            Some(weapon.to_string()),
+            Some(killer.to_string()),
+            Some(victim.to_string()),
+            Some(0.0),
+            Some(0.0),
+            Some(distance),
+            Some(0.0),
+        )
+    }
+
+    fn json_body(response: &ApiResponse) -> Value {
+        assert_eq!(response.content_type, JSON_CONTENT_TYPE);
+        serde_json::from_str(&response.body).unwrap()
+    }
+
+    fn leaderboards() -> Leaderboards {
+        let deaths = vec![
+            death("Player 1", "AK47", "Player2", 10.0),
+            death("Player 1", "AK47", "Player3", 20.0),
+            death("Player 1", "M4A4", "Player2", 30.0),
+            death("Player2", "M4A4", "Player 1", 40.0),
+            death("Player3", "AWM", "Player2", 500.0),
+        ];
+        let stats = Stats::from_deaths(deaths.into_par_iter(), &pool());
+        let mut config = AnalysisConfig::new("dataset");
+        config.top_weapons_of_player_count = 1;
+        config.max_count = 100;
+
+        Leaderboards::new(&stats, &config)
+    }
+
+    #[test]
+    fn test_top_killers_match_the_json_output() {
+        let response = handle_request(&leaderboards(), "/top_killers?n=2");
+
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            json_body(&response),
+            json!({
+                "top_killers": {
+                    "Player 1": {"rank": 1, "deaths": 3, "weapons_percentage": {"AK47": 66.67}},
+                    "Player2": {"rank": 2, "deaths": 1, "weapons_percentage": {"M4A4": 100.0}},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_top_weapons_with_default_count() {
+        let response = handle_request(&leaderboards(), "/top_weapons");
+
+        let body = json_body(&response);
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            body["top_weapons"]["AK47"],
+            json!({"rank": 1, "deaths_percentage": 40.0, "average_distance": 15.0})
+        );
+        assert_eq!(body["top_weapons"].as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_player_and_weapon() {
+        let leaderboards = leaderboards();
+
+        assert_eq!(
+            json_body(&handle_request(&leaderboards, "/players/Player%201")),
+            json!({
+                "rank": 1,
+                "name": "Player 1",
+                "deaths": 3,
+                "times_killed": 1,
+                "kill_death_ratio": 3.0,
+                "average_distance": 20.0,
+                "weapons_percentage": {"AK47": 66.67, "M4A4": 33.33},
+            })
+        );
+        assert_eq!(
+            json_body(&handle_request(&leaderboards, "/weapons/AWM")),
+            json!({
+                "rank": 3,
+                "name": "AWM",
+                "deaths": 1,
+                "deaths_percentage": 20.0,
+                "average_distance": 500.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let leaderboards = leaderboards();
+
+        assert_eq!(handle_request(&leaderboards, "/players/Nobody").status, 404);
+        assert_eq!(handle_request(&leaderboards, "/weapons/Pan").status, 404);
+        assert_eq!(handle_request(&leaderboards, "/unknown").status, 404);
+        assert_eq!(
+            handle_request(&leaderboards, "/top_killers?n=-1"),
+            ApiResponse::error(400, "Invalid value for n: -1")
+        );
+    }
+
+    #[test]
+    fn test_count_above_the_maximum() {
+        let leaderboards = leaderboards();
+
+        assert_eq!(
+            handle_request(&leaderboards, "/top_killers?n=100").status,
+            200
+        );
+        assert_eq!(
+            handle_request(&leaderboards, "/top_weapons?n=18446744073709551615"),
+            ApiResponse::error(
+                400,
+                "Value of n above the maximum of 100: 18446744073709551615"
+            )
+        );
+        assert_eq!(count_parameter("", 500, 100).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let leaderboards = leaderboards().with_metrics("pubg_deaths 5\n".to_string());
+
+        assert_eq!(
+            handle_request(&leaderboards, "/metrics"),
+            ApiResponse {
+                status: 200,
+                content_type: PROMETHEUS_CONTENT_TYPE,
+                body: "pubg_deaths 5\n".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_published_leaderboards_are_loaded() {
+        let shared = SharedLeaderboards::new(leaderboards());
+        let before = shared.load();
+        let stats = Stats::from_deaths(Vec::<Death>::new().into_par_iter(), &pool());
+
+        shared.publish(Leaderboards::new(&stats, &AnalysisConfig::new("dataset")));
+
+        assert_eq!(before.players.as_map().len(), 3);
+        assert!(shared.load().players.as_map().is_empty());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b+c%2Fd"), "a b c/d");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,538 @@
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPool,
+};
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+
+use std::path::PathBuf;
+
+use crate::{
+    config::AnalysisConfig,
+    deaths::Death,
+    errors::AnalyzerError,
+    file_reading::find_input_files_in_dir,
+    filter::Filter,
+    ingestion::{close_report, open_report, read_deaths, row_counts},
+    output_format::OutputFormat,
+    output_writting::write_output,
+    prometheus_writting::RowCounts,
+    snapshot::{is_snapshot, load_snapshot},
+    stats::Stats,
+    stats_api::Leaderboards,
+    time_tracking::Timer,
+};
+
+/// Commands of the shell, completed when nothing else is typed.
+const COMMANDS: [&str; 8] = [
+    "top", "player", "weapon", "filter", "export", "help", "quit", "exit",
+];
+/// Leaderboards of the `top` command.
+const LEADERBOARDS: [&str; 2] = ["killers", "weapons"];
+/// Formats of the `export` command.
+const FORMATS: [&str; 4] = ["json", "csv", "html", "markdown"];
+/// Argument of the `filter` command that removes the filter.
+const CLEAR_FILTER: &str = "clear";
+
+/// Help of the interactive shell, printed by `help`.
+pub const SHELL_HELP: &str = "Commands:
+  top killers [count]               Top players of the current deaths
+  top weapons [count]               Top weapons of the current deaths
+  player <name>                     Rank and stats of a player
+  weapon <name>                     Rank and stats of a weapon
+  filter <expression>               Only keep the deaths matching the expression, e.g. map = 'ERANGEL'
+  filter                            Print the current filter
+  filter clear                      Keep all the deaths again
+  export <format> <path>            Save the leaderboards of the current deaths as json, csv, html or markdown
+  help                              Print this help
+  quit                              Leave the shell
+
+Press Tab to complete the commands, and the names of the players and weapons.";
+
+/// A command of the interactive shell, see [`ShellCommand::parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellCommand {
+    /// The top players, with the given count or the one of the configuration.
+    TopKillers(Option<usize>),
+    /// The top weapons, with the given count or the one of the configuration.
+    TopWeapons(Option<usize>),
+    Player(String),
+    Weapon(String),
+    /// Replaces the filter of the deaths, which always applies to all the deaths loaded.
+    Filter(Filter),
+    ShowFilter,
+    ClearFilter,
+    /// Saves the leaderboards in the given format and path, like `analyze`.
+    Export {
+        format: OutputFormat,
+        path: String,
+    },
+    Help,
+    Quit,
+}
+
+impl ShellCommand {
+    /// Parses a line of the shell, see [`SHELL_HELP`]. Names are the rest of the line, so they can have spaces.
+    /// Returns `None` for an empty line, and the reason when the line is not a valid command.
+    pub fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let (command, rest) = line
+            .split_once(char::is_whitespace)
+            .map(|(command, rest)| (command, rest.trim()))
+            .unwrap_or((line, ""));
+
+        let command = match command {
+            "top" => {
+                let words = rest.split_whitespace().collect::<Vec<_>>();
+                let count = match words.get(1) {
+                    Some(count) => Some(
+                        count
+                            .parse()
+                            .map_err(|_| format!("Invalid count: {}", count))?,
+                    ),
+                    None => None,
+                };
+                match words.as_slice() {
+                    ["killers"] | ["killers", _] => ShellCommand::TopKillers(count),
+                    ["weapons"] | ["weapons", _] => ShellCommand::TopWeapons(count),
+                    _ => return Err("Usage: top killers|weapons [count]".to_string()),
+                }
+            }
+            "player" | "weapon" if rest.is_empty() => {
+                return Err(format!("Usage: {} <name>", command))
+            }
+            "player" => ShellCommand::Player(rest.to_string()),
+            "weapon" => ShellCommand::Weapon(rest.to_string()),
+            "filter" if rest.is_empty() => ShellCommand::ShowFilter,
+            "filter" if rest == CLEAR_FILTER => ShellCommand::ClearFilter,
+            "filter" => ShellCommand::Filter(
+                Filter::parse(rest).map_err(|e| format!("Invalid filter: {}", e))?,
+            ),
+            "export" => match rest.split_once(char::is_whitespace) {
+                Some((format, path)) => ShellCommand::Export {
+                    format: format.parse()?,
+                    path: path.trim().to_string(),
+                },
+                None => return Err("Usage: export <format> <path>".to_string()),
+            },
+            "help" if rest.is_empty() => ShellCommand::Help,
+            "quit" | "exit" if rest.is_empty() => ShellCommand::Quit,
+            _ => {
+                return Err(format!(
+                    "Unknown command: {}, type help to list the commands",
+                    line
+                ))
+            }
+        };
+
+        Ok(Some(command))
+    }
+}
+
+/// Completes the commands of the shell and their arguments, with the names of the players and weapons of the stats.
+pub struct ShellHelper {
+    /// Names of the players, sorted to find the ones with a prefix.
+    players: Vec<String>,
+    /// Names of the weapons, sorted to find the ones with a prefix.
+    weapons: Vec<String>,
+}
+
+impl ShellHelper {
+    /// Takes the names of the players and weapons of the stats, which should not be filtered.
+    pub fn new(stats: &Stats) -> Self {
+        let mut players = stats.players().as_map().keys().cloned().collect::<Vec<_>>();
+        let mut weapons = stats.weapons().as_map().keys().cloned().collect::<Vec<_>>();
+        players.sort();
+        weapons.sort();
+
+        Self { players, weapons }
+    }
+
+    /// Returns the start of the word being typed at the end of `line`, and the values that can complete it.
+    pub fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let Some((command, _)) = line.split_once(char::is_whitespace) else {
+            return (0, with_prefix(&COMMANDS, line));
+        };
+        let rest = line[command.len()..].trim_start();
+        let start = line.len() - rest.len();
+        let has_one_word = !rest.contains(char::is_whitespace);
+
+        let candidates = match command {
+            "player" => sorted_with_prefix(&self.players, rest),
+            "weapon" => sorted_with_prefix(&self.weapons, rest),
+            "top" if has_one_word => with_prefix(&LEADERBOARDS, rest),
+            "export" if has_one_word => with_prefix(&FORMATS, rest),
+            _ => Vec::new(),
+        };
+
+        (start, candidates)
+    }
+}
+
+/// Returns the values starting with the prefix.
+fn with_prefix(values: &[&str], prefix: &str) -> Vec<String> {
+    values
+        .iter()
+        .filter(|value| value.starts_with(prefix))
+        .map(|value| value.to_string())
+        .collect()
+}
+
+/// Returns the values of the sorted slice starting with the prefix, which are next to each other.
+fn sorted_with_prefix(values: &[String], prefix: &str) -> Vec<String> {
+    let first = values.partition_point(|value| value.as_str() < prefix);
+    values[first..]
+        .iter()
+        .take_while(|value| value.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Deaths loaded by `explore`, with the stats and leaderboards answering the commands of the shell.
+pub struct ExploreSession {
+    config: AnalysisConfig,
+    /// Deaths of the input files, kept to apply filters without reading the files again.
+    /// `None` when the input is a snapshot, which only has the stats.
+    deaths: Option<Vec<Death>>,
+    unfiltered: Stats,
+    /// Filter of the shell, with the stats of the deaths matching it.
+    filtered: Option<(Filter, Stats)>,
+    /// Leaderboards of the current stats, filtered or not.
+    leaderboards: Leaderboards,
+    input_files: Vec<PathBuf>,
+    rows: RowCounts,
+}
+
+impl ExploreSession {
+    /// Loads the deaths of the input of the configuration, or the stats if the input is a snapshot.
+    pub fn load(config: AnalysisConfig, pool: &ThreadPool) -> Result<Self, AnalyzerError> {
+        let (deaths, unfiltered, input_files, rows) = if is_snapshot(&config.input_dir) {
+            let stats = load_snapshot(&config.input_dir)?;
+            let input_files = vec![PathBuf::from(&config.input_dir)];
+            (None, stats, input_files, RowCounts::default())
+        } else {
+            let input_files = find_input_files_in_dir(&config.input_dir)?;
+            let report = open_report(&config)?;
+            let deaths = read_deaths(&config, input_files.clone(), &report, pool);
+            let deaths = pool.install(|| deaths.collect::<Vec<_>>());
+            close_report(&config, &report, true)?;
+            let stats = Stats::from_deaths(deaths.par_iter().cloned(), pool);
+            (Some(deaths), stats, input_files, row_counts(&report))
+        };
+
+        Ok(Self {
+            leaderboards: Leaderboards::new(&unfiltered, &config),
+            config,
+            deaths,
+            unfiltered,
+            filtered: None,
+            input_files,
+            rows,
+        })
+    }
+
+    /// Returns the stats of all the deaths loaded, whatever the filter of the shell.
+    pub fn unfiltered(&self) -> &Stats {
+        &self.unfiltered
+    }
+
+    /// Returns the stats of the deaths matching the filter of the shell, or of all the deaths if there is none.
+    pub fn current(&self) -> &Stats {
+        self.filtered
+            .as_ref()
+            .map_or(&self.unfiltered, |(_, stats)| stats)
+    }
+
+    /// Runs a command of the shell, returning the text to print, if any.
+    /// Returns the message to print when the command cannot be run, e.g. for an unknown player.
+    pub fn run(
+        &mut self,
+        command: ShellCommand,
+        pool: &ThreadPool,
+    ) -> Result<Option<String>, String> {
+        let output = match command {
+            ShellCommand::TopKillers(count) => json_text(
+                &self
+                    .leaderboards
+                    .top_killers(count.unwrap_or(self.config.top_players_count)),
+            )?,
+            ShellCommand::TopWeapons(count) => json_text(
+                &self
+                    .leaderboards
+                    .top_weapons(count.unwrap_or(self.config.top_weapons_count)),
+            )?,
+            ShellCommand::Player(name) => match self.leaderboards.player(&name) {
+                Some(player) => json_text(&player)?,
+                None => return Err(format!("Unknown player: {}", name)),
+            },
+            ShellCommand::Weapon(name) => match self.leaderboards.weapon(&name) {
+                Some(weapon) => json_text(&weapon)?,
+                None => return Err(format!("Unknown weapon: {}", name)),
+            },
+            ShellCommand::Filter(filter) => {
+                let Some(deaths) = &self.deaths else {
+                    return Err("The deaths of a snapshot cannot be filtered".to_string());
+                };
+                let stats = Stats::from_deaths(
+                    deaths
+                        .par_iter()
+                        .filter(|death| filter.matches(death))
+                        .cloned(),
+                    pool,
+                );
+                let output = format!("{} deaths match the filter", stats.total_deaths());
+                self.leaderboards = Leaderboards::new(&stats, &self.config);
+                self.filtered = Some((filter, stats));
+                output
+            }
+            ShellCommand::ShowFilter => match &self.filtered {
+                Some((filter, _)) => filter.to_string(),
+                None => "No filter, all the deaths are kept".to_string(),
+            },
+            ShellCommand::ClearFilter => {
+                self.filtered = None;
+                self.leaderboards = Leaderboards::new(&self.unfiltered, &self.config);
+                return Ok(None);
+            }
+            ShellCommand::Export { format, path } => {
+                let mut config = self.config.clone();
+                config.format = format;
+                config.output_file = path;
+                config.group_by.clear();
+                config.metrics_file = None;
+                write_output(
+                    &config,
+                    self.current().clone_without_custom(),
+                    self.input_files.clone(),
+                    self.rows,
+                    &mut Timer::new(),
+                    pool,
+                )
+                .map_err(|e| e.to_string())?;
+                return Ok(None);
+            }
+            ShellCommand::Help => SHELL_HELP.to_string(),
+            // Handled by the loop of the shell.
+            ShellCommand::Quit => return Ok(None),
+        };
+
+        Ok(Some(output))
+    }
+}
+
+/// Returns the value as indented JSON.
+fn json_text(value: &serde_json::Value) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| AnalyzerError::from(e).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::{iter::IntoParallelIterator, ThreadPoolBuilder};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn helper() -> ShellHelper {
+        let pool = pool();
+        let deaths = ["Player 1", "Player2", "Bot"]
+            .iter()
+            .zip(["AK47", "AWM", "M4A4"])
+            .map(|(killer, weapon)| {
+                Death::new(
+                    Some(weapon.to_string()),
+                    Some(killer.to_string()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        ShellHelper::new(&Stats::from_deaths(deaths.into_par_iter(), &pool))
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(ShellCommand::parse("  "), Ok(None));
+        assert_eq!(
+            ShellCommand::parse("top killers 20"),
+            Ok(Some(ShellCommand::TopKillers(Some(20))))
+        );
+        assert_eq!(
+            ShellCommand::parse("top weapons"),
+            Ok(Some(ShellCommand::TopWeapons(None)))
+        );
+        assert_eq!(
+            ShellCommand::parse("player  Player 1 "),
+            Ok(Some(ShellCommand::Player("Player 1".to_string())))
+        );
+        assert_eq!(
+            ShellCommand::parse("filter map = 'ERANGEL'"),
+            Ok(Some(ShellCommand::Filter(
+                Filter::parse("map = 'ERANGEL'").unwrap()
+            )))
+        );
+        assert_eq!(
+            ShellCommand::parse("filter clear"),
+            Ok(Some(ShellCommand::ClearFilter))
+        );
+        assert_eq!(
+            ShellCommand::parse("export json out/top.json"),
+            Ok(Some(ShellCommand::Export {
+                format: OutputFormat::Json,
+                path: "out/top.json".to_string(),
+            }))
+        );
+        assert_eq!(ShellCommand::parse("exit"), Ok(Some(ShellCommand::Quit)));
+    }
+
+    #[test]
+    fn test_parse_invalid_commands() {
+        assert!(ShellCommand::parse("top players").is_err());
+        assert!(ShellCommand::parse("top killers many").is_err());
+        assert!(ShellCommand::parse("player").is_err());
+        assert!(ShellCommand::parse("filter map = ").is_err());
+        assert!(ShellCommand::parse("export pdf report.pdf").is_err());
+        assert!(ShellCommand::parse("export json").is_err());
+        assert!(ShellCommand::parse("unknown").is_err());
+    }
+
+    #[test]
+    fn test_completions() {
+        let helper = helper();
+
+        assert_eq!(helper.candidates("e"), (0, strings(&["export", "exit"])));
+        assert_eq!(
+            helper.candidates("player Pl"),
+            (7, strings(&["Player 1", "Player2"]))
+        );
+        assert_eq!(
+            helper.candidates("player Player "),
+            (7, strings(&["Player 1"]))
+        );
+        assert_eq!(
+            helper.candidates("weapon  A"),
+            (8, strings(&["AK47", "AWM"]))
+        );
+        assert_eq!(helper.candidates("top k"), (4, strings(&["killers"])));
+        assert_eq!(helper.candidates("export j"), (7, strings(&["json"])));
+        assert!(helper.candidates("export json o").1.is_empty());
+    }
+
+    fn session(dir: &TempDir) -> ExploreSession {
+        std::fs::write(
+            dir.path().join("deaths.csv"),
+            "killed_by,killer_name,killer_placement,killer_position_x,killer_position_y,map,match_id,time,victim_name,victim_placement,victim_position_x,victim_position_y
+AK47,Player1,1,0,0,ERANGEL,m,10,Player2,2,10,0
+AK47,Player1,1,0,0,MIRAMAR,m,10,Player3,2,10,0
+AWM,Player2,1,0,0,MIRAMAR,m,10,Player1,2,10,0
+",
+        )
+        .unwrap();
+        let config = AnalysisConfig::new(dir.path().to_str().unwrap());
+
+        ExploreSession::load(config, &pool()).unwrap()
+    }
+
+    #[test]
+    fn test_session_commands() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session(&dir);
+        let mut run =
+            |line: &str| session.run(ShellCommand::parse(line).unwrap().unwrap(), &pool());
+
+        let top_killers = run("top killers 1").unwrap().unwrap();
+        let top_killers = serde_json::from_str::<serde_json::Value>(&top_killers).unwrap();
+        assert_eq!(top_killers["top_killers"]["Player1"]["deaths"], 2);
+        assert!(run("player Player2").unwrap().is_some());
+        assert_eq!(run("weapon M4A4"), Err("Unknown weapon: M4A4".to_string()));
+        assert_eq!(run("help"), Ok(Some(SHELL_HELP.to_string())));
+    }
+
+    #[test]
+    fn test_session_filters() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session(&dir);
+
+        let output = session
+            .run(
+                ShellCommand::Filter(Filter::parse("map = 'MIRAMAR'").unwrap()),
+                &pool(),
+            )
+            .unwrap();
+        assert_eq!(output, Some("2 deaths match the filter".to_string()));
+        assert_eq!(session.current().total_deaths(), 2);
+        assert_eq!(session.unfiltered().total_deaths(), 3);
+        assert_eq!(
+            session.run(ShellCommand::ShowFilter, &pool()),
+            Ok(Some("map = 'MIRAMAR'".to_string()))
+        );
+
+        assert_eq!(session.run(ShellCommand::ClearFilter, &pool()), Ok(None));
+        assert_eq!(session.current().total_deaths(), 3);
+    }
+
+    #[test]
+    fn test_session_exports_the_current_deaths() {
+        let dir = TempDir::new().unwrap();
+        let mut session = session(&dir);
+        let path = dir.path().join("top.json");
+        session
+            .run(
+                ShellCommand::Filter(Filter::parse("killer_name = 'Player2'").unwrap()),
+                &pool(),
+            )
+            .unwrap();
+
+        let output = session.run(
+            ShellCommand::Export {
+                format: OutputFormat::Json,
+                path: path.to_str().unwrap().to_string(),
+            },
+            &pool(),
+        );
+
+        assert_eq!(output, Ok(None));
+        let exported = std::fs::read_to_string(path).unwrap();
+        assert!(exported.contains("Player2"));
+        assert!(!exported.contains("Player1"));
+    }
+}
@@ -0,0 +1,400 @@
+use rayon::{prelude::*, ThreadPool};
+
+use std::{
+    collections::HashMap,
+    fs::{File, Metadata},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    deaths::RecordError, errors::AnalyzerError, file_reading::process_record,
+    ingestion_report::IngestionReport,
+};
+
+/// Position reached in a followed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilePosition {
+    /// Bytes of the complete lines already read.
+    offset: u64,
+    /// Number of complete lines already read, including the header.
+    lines: usize,
+    /// Identity of the file on disk, to notice when it is replaced by another one with the same name.
+    identity: Option<u64>,
+}
+
+/// Follows CSV files that grow by appending rows, like `tail -F`: each call only reads the rows appended since the
+/// previous one. A trailing line without line break is left for the next call, as it may still be being written.
+///
+/// A file that shrank was truncated, and one that was replaced by another file with the same name was rotated.
+/// In both cases the file is read again from the start, and the rows read before remain in the stats.
+#[derive(Debug, Default)]
+pub struct CsvFollower {
+    positions: HashMap<PathBuf, FilePosition>,
+}
+
+/// Complete lines appended to the followed files, see [`CsvFollower::read_appended_lines`].
+/// Only the ranges of the files holding them are kept, and the lines are read again when they are processed,
+/// so the memory used does not grow with the number of lines.
+#[derive(Debug, Default)]
+pub struct AppendedLines {
+    /// Files that were truncated or rotated, and so were read from the start.
+    pub restarted_files: Vec<PathBuf>,
+    ranges: Vec<AppendedRange>,
+    /// Number of lines in the ranges.
+    lines: usize,
+}
+
+/// Complete lines appended to a followed file, without its header.
+#[derive(Debug)]
+struct AppendedRange {
+    file: Arc<Path>,
+    /// Offset of the first line.
+    start: u64,
+    /// Offset after the last complete line.
+    end: u64,
+    /// Line number of the first line.
+    first_line: usize,
+}
+
+impl CsvFollower {
+    /// Creates a new `CsvFollower`, which has not read any file yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the complete lines appended to each of the given files since the last call, skipping their headers.
+    /// The files that were followed before and are not given anymore are forgotten, so they are read from the start
+    /// if they come back.
+    /// Returns an IO error if a file cannot be read, and then no position is advanced, so the next call reads the
+    /// same lines again.
+    pub fn read_appended_lines(
+        &mut self,
+        files: &[PathBuf],
+    ) -> Result<AppendedLines, AnalyzerError> {
+        let mut positions = HashMap::with_capacity(files.len());
+        let mut appended = AppendedLines::default();
+
+        for path in files {
+            let mut file = File::open(path).map_err(|e| AnalyzerError::io(path, e))?;
+            let metadata = file.metadata().map_err(|e| AnalyzerError::io(path, e))?;
+            let identity = file_identity(&metadata);
+
+            let position = match self.positions.get(path) {
+                Some(position)
+                    if position.identity == identity && metadata.len() >= position.offset =>
+                {
+                    position.clone()
+                }
+                previous => {
+                    if previous.is_some() {
+                        appended.restarted_files.push(path.clone());
+                    }
+                    FilePosition {
+                        offset: 0,
+                        lines: 0,
+                        identity,
+                    }
+                }
+            };
+            if metadata.len() == position.offset {
+                positions.insert(path.clone(), position);
+                continue;
+            }
+
+            file.seek(SeekFrom::Start(position.offset))
+                .map_err(|e| AnalyzerError::io(path, e))?;
+            let (position, range) = find_complete_lines(path, file, position)?;
+            if let Some(range) = range {
+                appended.lines += position.lines + 1 - range.first_line;
+                appended.ranges.push(range);
+            }
+            positions.insert(path.clone(), position);
+        }
+
+        // The positions are only advanced once every file was read
+        self.positions = positions;
+        Ok(appended)
+    }
+}
+
+impl AppendedLines {
+    /// Returns the number of lines appended, without the headers.
+    pub fn len(&self) -> usize {
+        self.lines
+    }
+
+    /// Returns true if no line was appended.
+    pub fn is_empty(&self) -> bool {
+        self.lines == 0
+    }
+
+    /// Processes the lines in parallel with the given function, in the same way as [`crate::read_csv_files`].
+    /// If a line cannot be read or the processing function returns an error, the line will be skipped and recorded in the `report`.
+    /// The lines of a file that cannot be opened anymore are recorded as a single error.
+    pub fn into_records<F, T>(
+        self,
+        process_line: F,
+        report: Arc<IngestionReport>,
+        pool: &ThreadPool,
+    ) -> impl ParallelIterator<Item = T>
+    where
+        F: Fn(String) -> Result<T, RecordError> + Send + Sync,
+        T: Send,
+    {
+        pool.install(|| {
+            self.ranges
+                .into_par_iter()
+                .flat_map(|range| range.lines().par_bridge())
+                .map(move |(file, line_number, line)| {
+                    process_record(&process_line, &report, &file, line_number, line)
+                })
+                .while_some()
+                .flatten()
+        })
+    }
+}
+
+impl AppendedRange {
+    /// Reads the lines of the range, with the file and the number of each line.
+    fn lines(self) -> impl Iterator<Item = (Arc<Path>, usize, std::io::Result<String>)> + Send {
+        let reader = File::open(&self.file).and_then(|mut file| {
+            file.seek(SeekFrom::Start(self.start))?;
+            Ok(BufReader::new(file.take(self.end - self.start)))
+        });
+        let (lines, error) = match reader {
+            Ok(reader) => (Some(reader.lines()), None),
+            Err(e) => (None, Some(Err(e))),
+        };
+
+        let file = self.file;
+        let first_line = self.first_line;
+        lines
+            .into_iter()
+            .flatten()
+            .chain(error)
+            .enumerate()
+            .map(move |(index, line)| (file.clone(), first_line + index, line))
+    }
+}
+
+/// Finds the complete lines of the file from its current position, counting them in buffered chunks.
+/// The first line of the file is its header, so it is left out of the range of the lines.
+/// Returns the position after the last complete line, with the range of the lines if there are any.
+fn find_complete_lines(
+    path: &Path,
+    file: File,
+    mut position: FilePosition,
+) -> Result<(FilePosition, Option<AppendedRange>), AnalyzerError> {
+    let mut reader = BufReader::new(file);
+    let mut start = position.offset;
+    let first_line = position.lines.max(1) + 1;
+    let mut scanned = position.offset;
+
+    loop {
+        let buffer = reader.fill_buf().map_err(|e| AnalyzerError::io(path, e))?;
+        if buffer.is_empty() {
+            break;
+        }
+
+        for (index, _) in buffer
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == b'\n')
+        {
+            position.offset = scanned + index as u64 + 1;
+            position.lines += 1;
+            if position.lines == 1 {
+                start = position.offset;
+            }
+        }
+        let length = buffer.len();
+        scanned += length as u64;
+        reader.consume(length);
+    }
+
+    let range = (position.lines >= first_line).then(|| AppendedRange {
+        file: Arc::from(path),
+        start,
+        end: position.offset,
+        first_line,
+    });
+    Ok((position, range))
+}
+
+/// Returns the inode of the file, which changes when the file is rotated.
+#[cfg(unix)]
+pub(crate) fn file_identity(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.ino())
+}
+
+/// Returns the identity of the file, which is not available on this platform, so only truncations are noticed.
+#[cfg(not(unix))]
+pub(crate) fn file_identity(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use rayon::ThreadPoolBuilder;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const HEADER: &str = "killed_by,killer_name";
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn append(path: &Path, content: &str) {
+        let mut file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    fn read(follower: &mut CsvFollower, path: &Path) -> Vec<String> {
+        let report = Arc::new(IngestionReport::new(None, None).unwrap());
+        let mut lines = follower
+            .read_appended_lines(&[path.to_path_buf()])
+            .unwrap()
+            .into_records(|line: String| Ok(line), report, &pool())
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn test_only_appended_lines_are_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        let mut follower = CsvFollower::new();
+        append(&path, &format!("{}\nAK47,Player1\n", HEADER));
+
+        assert_eq!(read(&mut follower, &path), vec!["AK47,Player1"]);
+        assert!(read(&mut follower, &path).is_empty());
+
+        append(&path, "M4A4,Player2\r\nAWM,Pla");
+        assert_eq!(read(&mut follower, &path), vec!["M4A4,Player2"]);
+
+        append(&path, "yer3\n");
+        assert_eq!(read(&mut follower, &path), vec!["AWM,Player3"]);
+    }
+
+    #[test]
+    fn test_partial_header_is_not_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        let mut follower = CsvFollower::new();
+        append(&path, "killed_by,kil");
+
+        assert!(read(&mut follower, &path).is_empty());
+
+        append(&path, "ler_name\nAK47,Player1\n");
+        assert_eq!(read(&mut follower, &path), vec!["AK47,Player1"]);
+    }
+
+    #[test]
+    fn test_truncated_and_rotated_files_are_read_from_the_start() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        let mut follower = CsvFollower::new();
+        append(&path, &format!("{}\nAK47,Player1\nM4A4,Player2\n", HEADER));
+        read(&mut follower, &path);
+
+        std::fs::write(&path, format!("{}\nAWM,Player3\n", HEADER)).unwrap();
+        let truncated = follower
+            .read_appended_lines(std::slice::from_ref(&path))
+            .unwrap();
+        assert_eq!(truncated.restarted_files, vec![path.clone()]);
+        assert_eq!(truncated.len(), 1);
+
+        let rotated = dir.path().join("rotated.csv");
+        append(
+            &rotated,
+            &format!("{}\nAK47,Player4\nAK47,Player5\n", HEADER),
+        );
+        std::fs::rename(&rotated, &path).unwrap();
+        assert_eq!(
+            read(&mut follower, &path),
+            vec!["AK47,Player4", "AK47,Player5"]
+        );
+    }
+
+    #[test]
+    fn test_removed_files_are_forgotten() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        let mut follower = CsvFollower::new();
+        append(&path, &format!("{}\nAK47,Player1\n", HEADER));
+        read(&mut follower, &path);
+
+        follower.read_appended_lines(&[]).unwrap();
+
+        assert_eq!(read(&mut follower, &path), vec!["AK47,Player1"]);
+    }
+
+    #[test]
+    fn test_unreadable_file_does_not_advance_the_positions() {
+        let dir = TempDir::new().unwrap();
+        let readable = dir.path().join("a.csv");
+        let unreadable = dir.path().join("b.csv");
+        let files = vec![readable.clone(), unreadable.clone()];
+        let mut follower = CsvFollower::new();
+        append(&readable, &format!("{}\nAK47,Player1\n", HEADER));
+        // A directory can be opened but not read
+        std::fs::create_dir(&unreadable).unwrap();
+
+        assert!(matches!(
+            follower.read_appended_lines(&files),
+            Err(AnalyzerError::Io { .. })
+        ));
+
+        std::fs::remove_dir(&unreadable).unwrap();
+        append(&unreadable, &format!("{}\nM4A4,Player2\n", HEADER));
+        let appended = follower.read_appended_lines(&files).unwrap();
+
+        assert_eq!(appended.len(), 2);
+        assert!(appended.restarted_files.is_empty());
+    }
+
+    #[test]
+    fn test_line_numbers_of_rejected_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("deaths.csv");
+        let reject_path = dir.path().join("rejects.tsv");
+        let mut follower = CsvFollower::new();
+        let report =
+            Arc::new(IngestionReport::new(Some(reject_path.to_str().unwrap()), None).unwrap());
+        append(&path, &format!("{}\nAK47,Player1\n", HEADER));
+        follower
+            .read_appended_lines(std::slice::from_ref(&path))
+            .unwrap();
+        append(&path, "invalid\n");
+
+        let records = follower
+            .read_appended_lines(std::slice::from_ref(&path))
+            .unwrap()
+            .into_records(
+                |_: String| Err::<String, _>(RecordError::InvalidFieldCount(1)),
+                report.clone(),
+                &pool(),
+            )
+            .collect::<Vec<_>>();
+        report.flush().unwrap();
+
+        assert!(records.is_empty());
+        assert_eq!(report.error_count(), 1);
+        let rejects = std::fs::read_to_string(reject_path).unwrap();
+        assert!(rejects.contains("\t3\t"));
+        assert!(rejects.ends_with("\tinvalid\n"));
+    }
+}
@@ -1,9 +1,17 @@
 use std::time::{Duration, Instant};
 
 /// A simple timer to measure the time taken by a program.
+/// The printed laps are kept with their message, to include them in reports.
 pub struct Timer {
     start: Instant,
     last_lap: Instant,
+    laps: Vec<(String, Duration)>,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Timer {
@@ -12,6 +20,7 @@ impl Timer {
         Self {
             start: Instant::now(),
             last_lap: Instant::now(),
+            laps: Vec::new(),
         }
     }
 
@@ -30,10 +39,16 @@ impl Timer {
         Instant::now() - self.start
     }
 
-    /// Prints the duration since the last lap with a message.
+    /// Returns the printed laps with their message, in order.
+    pub fn laps(&self) -> &[(String, Duration)] {
+        &self.laps
+    }
+
+    /// Prints the duration since the last lap with a message, and records it.
     pub fn print_lap(&mut self, message: &str) {
         let lap = self.lap();
         println!("{}: {:.2?}", message, lap);
+        self.laps.push((message.to_string(), lap));
     }
 
     /// Prints the total duration since the timer was created.
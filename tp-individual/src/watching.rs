@@ -0,0 +1,466 @@
+use rayon::{prelude::*, ThreadPool};
+
+use std::{path::PathBuf, sync::Arc, thread::JoinHandle, time::Duration};
+
+use crate::{
+    aggregator::CustomAggregators,
+    config::AnalysisConfig,
+    errors::AnalyzerError,
+    file_reading::{find_input_files_in_dir, split_by_format},
+    ingestion::{
+        close_report, ingestion_settings, open_report, parse_death_function,
+        read_stats_with_report, row_counts,
+    },
+    ingestion_report::IngestionReport,
+    manifest::{plan_update, Manifest},
+    prometheus_writting::RowCounts,
+    snapshot::is_snapshot,
+    stats::Stats,
+    stats_api::{Leaderboards, SharedLeaderboards},
+    tail_reading::CsvFollower,
+    time_tracking::Timer,
+};
+
+/// Keeps the stats of an input directory up to date in watch or follow mode, merging what changed since the
+/// previous scan, see [`InputWatcher::scan`].
+pub struct InputWatcher {
+    config: AnalysisConfig,
+    custom: CustomAggregators,
+    /// Stats of the deaths read so far that match the filter, with every player and weapon, not only the top ones.
+    stats: Option<Stats>,
+    /// Report of the lines discarded since the stats were last built from every file.
+    /// It is kept across scans, so the reject file is not truncated and the maximum number of errors applies to
+    /// the whole run.
+    report: Option<Arc<IngestionReport>>,
+    /// Manifest of the files read, in watch mode.
+    manifest: Option<Manifest>,
+    /// Positions reached in the CSV files, in follow mode.
+    follower: CsvFollower,
+}
+
+impl InputWatcher {
+    /// Creates a watcher of the input directory of the configuration, which has not read anything yet.
+    /// It follows the rows appended to the CSV files if the configuration is in follow mode.
+    /// Returns a configuration error if the input cannot be watched: snapshots are not scanned for changes.
+    pub fn new(config: AnalysisConfig, custom: CustomAggregators) -> Result<Self, AnalyzerError> {
+        if config.snapshot_file.is_some()
+            || config.incremental_file.is_some()
+            || is_snapshot(&config.input_dir)
+        {
+            return Err(AnalyzerError::Config(
+                "Watch and follow modes cannot be combined with snapshots".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            config,
+            custom,
+            stats: None,
+            report: None,
+            manifest: None,
+            follower: CsvFollower::new(),
+        })
+    }
+
+    /// Returns the configuration of the watched input.
+    pub fn config(&self) -> &AnalysisConfig {
+        &self.config
+    }
+
+    /// Returns the stats of the deaths read so far that match the filter, with every player and weapon, or `None`
+    /// before the first scan.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Returns the rows read and discarded since the stats were last built from every file.
+    pub fn rows(&self) -> RowCounts {
+        self.report.as_deref().map(row_counts).unwrap_or_default()
+    }
+
+    /// Returns `true` if too many lines were discarded, so the run cannot go on.
+    pub fn is_aborted(&self) -> bool {
+        self.report
+            .as_ref()
+            .is_some_and(|report| report.is_aborted())
+    }
+
+    /// Returns the leaderboards of the stats served by the HTTP API, with the metrics of the scan timed by `timer`,
+    /// or `None` before the first scan.
+    pub fn leaderboards(&self, timer: &Timer) -> Option<Leaderboards> {
+        let stats = self.stats.as_ref()?;
+        Some(Leaderboards::of_run(
+            stats,
+            &self.config,
+            self.rows(),
+            timer.laps(),
+        ))
+    }
+
+    /// Scans the input directory once, and publishes the leaderboards of the stats if they changed.
+    /// Returns `true` if the leaderboards were replaced.
+    pub fn publish_changes(
+        &mut self,
+        shared: &SharedLeaderboards,
+        pool: &ThreadPool,
+    ) -> Result<bool, AnalyzerError> {
+        let mut timer = Timer::new();
+        if self.scan(&mut timer, pool)?.is_none() {
+            return Ok(false);
+        }
+
+        match self.leaderboards(&timer) {
+            Some(leaderboards) => {
+                shared.publish(leaderboards);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Keeps scanning the input directory in another thread every `watch_interval` seconds, publishing the
+    /// leaderboards after each change, see [`InputWatcher::publish_changes`].
+    /// The errors of a scan are printed, and the scans stop once too many lines were discarded.
+    pub fn spawn_publisher(
+        mut self,
+        shared: Arc<SharedLeaderboards>,
+        pool: ThreadPool,
+    ) -> JoinHandle<()> {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(self.config.watch_interval as u64));
+            match self.publish_changes(&shared, &pool) {
+                Ok(_) => {}
+                Err(e) if self.is_aborted() => {
+                    eprintln!("{}, the input directory is not scanned anymore", e);
+                    break;
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        })
+    }
+
+    /// Scans the input directory once, merging what changed since the last scan into the stats.
+    ///
+    /// In watch mode, the deaths of the new files are merged into the stats, unless a file was modified or removed
+    /// or the settings changed, in which case the stats are rebuilt from every file, as in incremental runs.
+    /// In follow mode, only the rows appended to the CSV files are read and merged, see [`CsvFollower`].
+    /// Returns the input files if the stats changed, or `None` if nothing changed since the last scan.
+    pub fn scan(
+        &mut self,
+        timer: &mut Timer,
+        pool: &ThreadPool,
+    ) -> Result<Option<Vec<PathBuf>>, AnalyzerError> {
+        if self.config.follow {
+            self.follow_appended_rows(timer, pool)
+        } else {
+            self.read_new_files(timer, pool)
+        }
+    }
+
+    /// Reads the new files of the input directory in watch mode, see [`InputWatcher::scan`].
+    fn read_new_files(
+        &mut self,
+        timer: &mut Timer,
+        pool: &ThreadPool,
+    ) -> Result<Option<Vec<PathBuf>>, AnalyzerError> {
+        let config = &self.config;
+        let input_files = find_input_files_in_dir(&config.input_dir)?;
+        let update = plan_update(
+            self.manifest.as_ref(),
+            &ingestion_settings(config),
+            &input_files,
+        )?;
+        if self.stats.is_some()
+            && update.rebuild_reason.is_none()
+            && update.files_to_read.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let files_read = update.files_to_read.len();
+        let report = match (&update.rebuild_reason, &self.report) {
+            (None, Some(report)) if self.stats.is_some() => report.clone(),
+            _ => open_report(config)?,
+        };
+        let new_stats =
+            read_stats_with_report(config, update.files_to_read, &self.custom, &report, pool);
+        self.report = Some(report);
+        let new_stats = new_stats?;
+        timer.print_lap("Processing deaths");
+
+        match (update.rebuild_reason, self.stats.as_mut()) {
+            (None, Some(stats)) => {
+                println!("{} new files merged", files_read);
+                stats.merge(new_stats);
+            }
+            (reason, previous) => {
+                if previous.is_some() {
+                    println!(
+                        "Rebuilding the stats from {} files: {}",
+                        files_read,
+                        reason.unwrap_or_default()
+                    );
+                }
+                self.stats = Some(new_stats);
+            }
+        }
+        self.manifest = Some(update.manifest);
+
+        Ok(Some(input_files))
+    }
+
+    /// Reads the rows appended to the CSV files of the input directory in follow mode, see [`InputWatcher::scan`].
+    /// Other input files cannot be appended to, so they are ignored.
+    fn follow_appended_rows(
+        &mut self,
+        timer: &mut Timer,
+        pool: &ThreadPool,
+    ) -> Result<Option<Vec<PathBuf>>, AnalyzerError> {
+        let config = &self.config;
+        let (csv_files, _) = split_by_format(find_input_files_in_dir(&config.input_dir)?);
+        let appended = self.follower.read_appended_lines(&csv_files)?;
+        for file in &appended.restarted_files {
+            println!(
+                "{} was truncated or rotated, reading it from the start",
+                file.display()
+            );
+        }
+        if self.stats.is_some() && appended.is_empty() {
+            return Ok(None);
+        }
+
+        let rows_read = appended.len();
+        let report = match &self.report {
+            Some(report) => report.clone(),
+            None => open_report(config)?,
+        };
+        self.report = Some(report.clone());
+        let filter = config.filter.as_ref();
+        let deaths = appended
+            .into_records(parse_death_function(config), report.clone(), pool)
+            .filter(move |death| filter.is_none_or(|filter| filter.matches(death)));
+        let new_stats = Stats::from_deaths_with(deaths, &self.custom, pool);
+        close_report(config, &report, true)?;
+        timer.print_lap("Processing deaths");
+        println!("{} new rows merged", rows_read);
+
+        match self.stats.as_mut() {
+            Some(stats) => stats.merge(new_stats),
+            None => self.stats = Some(new_stats),
+        }
+
+        Ok(Some(csv_files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::Path};
+
+    use rayon::ThreadPoolBuilder;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::stats_api::handle_request;
+
+    const HEADER: &str = "killed_by,killer_name,killer_placement,killer_position_x,killer_position_y,map,match_id,time,victim_name,victim_placement,victim_position_x,victim_position_y";
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn records(killers: &[&str]) -> String {
+        killers
+            .iter()
+            .map(|killer| format!("AK47,{},1,0,0,ERANGEL,m,10,Victim,2,10,0\n", killer))
+            .collect()
+    }
+
+    fn write_deaths(path: &Path, killers: &[&str]) {
+        std::fs::write(path, format!("{}\n{}", HEADER, records(killers))).unwrap();
+    }
+
+    fn append(path: &Path, content: &str) {
+        let mut file = std::fs::File::options().append(true).open(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    fn watcher(dir: &TempDir) -> InputWatcher {
+        let config = AnalysisConfig::new(dir.path().to_str().unwrap());
+        InputWatcher::new(config, CustomAggregators::new()).unwrap()
+    }
+
+    fn follower(dir: &TempDir) -> InputWatcher {
+        let mut config = AnalysisConfig::new(dir.path().to_str().unwrap());
+        config.follow = true;
+        InputWatcher::new(config, CustomAggregators::new()).unwrap()
+    }
+
+    fn scan(watcher: &mut InputWatcher) -> Option<Vec<PathBuf>> {
+        watcher.scan(&mut Timer::new(), &pool()).unwrap()
+    }
+
+    fn total_deaths(watcher: &InputWatcher) -> usize {
+        watcher.stats().map_or(0, |stats| stats.total_deaths())
+    }
+
+    #[test]
+    fn test_new_files_are_merged() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = watcher(&dir);
+        write_deaths(&dir.path().join("day-1.csv"), &["Player1", "Player2"]);
+
+        assert_eq!(scan(&mut watcher).map(|files| files.len()), Some(1));
+        assert_eq!(total_deaths(&watcher), 2);
+        assert_eq!(scan(&mut watcher), None);
+
+        write_deaths(&dir.path().join("day-2.csv"), &["Player3"]);
+
+        assert_eq!(scan(&mut watcher).map(|files| files.len()), Some(2));
+        assert_eq!(total_deaths(&watcher), 3);
+        assert_eq!(watcher.rows().read, 3);
+    }
+
+    #[test]
+    fn test_modified_file_rebuilds_the_stats() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = watcher(&dir);
+        let path = dir.path().join("day-1.csv");
+        write_deaths(&path, &["Player1", "Player2"]);
+        write_deaths(&dir.path().join("day-2.csv"), &["Player3"]);
+        scan(&mut watcher);
+
+        write_deaths(&path, &["Player1"]);
+
+        assert!(scan(&mut watcher).is_some());
+        assert_eq!(total_deaths(&watcher), 2);
+        assert_eq!(watcher.rows().read, 2);
+    }
+
+    #[test]
+    fn test_rejects_and_errors_are_kept_across_scans() {
+        let dir = TempDir::new().unwrap();
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir(&input_dir).unwrap();
+        let reject_path = dir.path().join("rejects.tsv");
+        let mut config = AnalysisConfig::new(input_dir.to_str().unwrap());
+        config.reject_file = Some(reject_path.to_str().unwrap().to_string());
+        config.max_errors = Some(1);
+        let mut watcher = InputWatcher::new(config, CustomAggregators::new()).unwrap();
+
+        std::fs::write(
+            input_dir.join("day-1.csv"),
+            format!("{}\ninvalid\n", HEADER),
+        )
+        .unwrap();
+        scan(&mut watcher);
+        std::fs::write(
+            input_dir.join("day-2.csv"),
+            format!("{}\ninvalid\n", HEADER),
+        )
+        .unwrap();
+        let result = watcher.scan(&mut Timer::new(), &pool());
+
+        assert!(matches!(result, Err(AnalyzerError::Parse(_))));
+        assert!(watcher.is_aborted());
+        assert_eq!(watcher.rows().rejected, 2);
+        let rejects = std::fs::read_to_string(reject_path).unwrap();
+        assert_eq!(rejects.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_appended_rows_are_merged_in_follow_mode() {
+        let dir = TempDir::new().unwrap();
+        let mut follower = follower(&dir);
+        let path = dir.path().join("live.csv");
+        write_deaths(&path, &["Player1", "Player2"]);
+        std::fs::write(dir.path().join("deaths.parquet"), "ignored").unwrap();
+
+        assert_eq!(scan(&mut follower), Some(vec![path.clone()]));
+        assert_eq!(total_deaths(&follower), 2);
+        assert_eq!(scan(&mut follower), None);
+
+        append(&path, &records(&["Player3"]));
+
+        assert!(scan(&mut follower).is_some());
+        assert_eq!(total_deaths(&follower), 3);
+        assert_eq!(follower.rows().read, 3);
+    }
+
+    #[test]
+    fn test_truncated_file_is_followed_from_the_start() {
+        let dir = TempDir::new().unwrap();
+        let mut follower = follower(&dir);
+        let path = dir.path().join("live.csv");
+        write_deaths(&path, &["Player1", "Player2"]);
+        scan(&mut follower);
+
+        write_deaths(&path, &["Player3"]);
+
+        assert!(scan(&mut follower).is_some());
+        assert_eq!(total_deaths(&follower), 3);
+        assert_eq!(follower.rows().read, 3);
+
+        append(&path, &records(&["Player4"]));
+
+        assert!(scan(&mut follower).is_some());
+        assert_eq!(total_deaths(&follower), 4);
+    }
+
+    #[test]
+    fn test_rejects_are_kept_across_follow_scans() {
+        let dir = TempDir::new().unwrap();
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir(&input_dir).unwrap();
+        let reject_path = dir.path().join("rejects.tsv");
+        let mut config = AnalysisConfig::new(input_dir.to_str().unwrap());
+        config.follow = true;
+        config.reject_file = Some(reject_path.to_str().unwrap().to_string());
+        let mut follower = InputWatcher::new(config, CustomAggregators::new()).unwrap();
+        let path = input_dir.join("live.csv");
+
+        std::fs::write(&path, format!("{}\ninvalid\n", HEADER)).unwrap();
+        scan(&mut follower);
+        append(&path, &format!("{}invalid again\n", records(&["Player1"])));
+        scan(&mut follower);
+
+        assert_eq!(total_deaths(&follower), 1);
+        assert_eq!(follower.rows().rejected, 2);
+        let rejects = std::fs::read_to_string(reject_path).unwrap();
+        assert_eq!(rejects.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_changes_are_published() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = watcher(&dir);
+        write_deaths(&dir.path().join("day-1.csv"), &["Player1"]);
+        let mut timer = Timer::new();
+        scan(&mut watcher);
+        timer.print_lap("Processing deaths");
+        let shared = SharedLeaderboards::new(watcher.leaderboards(&timer).unwrap());
+        let before = shared.load();
+
+        assert!(!watcher.publish_changes(&shared, &pool()).unwrap());
+        assert!(Arc::ptr_eq(&before, &shared.load()));
+
+        write_deaths(&dir.path().join("day-2.csv"), &["Player2", "Player2"]);
+
+        assert!(watcher.publish_changes(&shared, &pool()).unwrap());
+        assert_eq!(handle_request(&before, "/players/Player2").status, 404);
+        let response = handle_request(&shared.load(), "/top_killers?n=1");
+        let top_killers: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(top_killers["top_killers"]["Player2"]["deaths"], 2);
+    }
+
+    #[test]
+    fn test_snapshots_cannot_be_watched() {
+        let mut config = AnalysisConfig::new("dataset");
+        config.incremental_file = Some("dataset.snapshot".to_string());
+
+        assert!(matches!(
+            InputWatcher::new(config, CustomAggregators::new()),
+            Err(AnalyzerError::Config(_))
+        ));
+    }
+}
@@ -1,29 +1,29 @@
-use crate::float_calculations::{calculate_average, calculate_percentage};
+use std::collections::HashMap;
+
+use rayon::ThreadPool;
+
+use crate::{
+    aggregator::Aggregator,
+    deaths::Death,
+    distance_histogram::DistanceHistogram,
+    float_calculations::{calculate_average, calculate_percentage, CompensatedSum},
+    ranking::{ranked_json, weapon_comparator, RankingMetric},
+    snapshot::{Decoder, Encoder},
+    sorting::{element_rank, ranked_elements, retain_top_elements, top_elements},
+};
 
 /// Struct to store the stats of a weapon.
+#[derive(Clone)]
 pub struct WeaponStats {
     death_count: usize,
     death_count_with_distance: usize,
-    total_distance: f64,
-}
-
-impl Eq for WeaponStats {}
-
-impl PartialEq for WeaponStats {
-    fn eq(&self, other: &Self) -> bool {
-        self.death_count == other.death_count
-    }
-}
-
-impl PartialOrd for WeaponStats {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
+    total_distance: CompensatedSum,
+    distances: DistanceHistogram,
 }
 
-impl Ord for WeaponStats {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.death_count.cmp(&other.death_count)
+impl Default for WeaponStats {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -33,7 +33,8 @@ impl WeaponStats {
         Self {
             death_count: 0,
             death_count_with_distance: 0,
-            total_distance: 0.0,
+            total_distance: CompensatedSum::new(),
+            distances: DistanceHistogram::new(),
         }
     }
 
@@ -43,7 +44,23 @@ impl WeaponStats {
         self.death_count += 1;
         if let Some(distance) = distance {
             self.death_count_with_distance += 1;
-            self.total_distance += distance;
+            self.total_distance.add(distance);
+            self.distances.add(distance);
+        }
+    }
+
+    /// Returns the number of deaths caused by the weapon.
+    pub fn kills(&self) -> usize {
+        self.death_count
+    }
+
+    /// Returns the average distance of the deaths caused by the weapon, without rounding.
+    /// Deaths without distance are not taken into account.
+    pub fn average_distance(&self) -> f64 {
+        if self.death_count_with_distance == 0 {
+            0.0
+        } else {
+            self.total_distance.value() / self.death_count_with_distance as f64
         }
     }
 
@@ -51,16 +68,186 @@ impl WeaponStats {
     pub fn merge(&mut self, other: &Self) {
         self.death_count += other.death_count;
         self.death_count_with_distance += other.death_count_with_distance;
-        self.total_distance += other.total_distance;
+        self.total_distance.merge(other.total_distance);
+        self.distances.merge(&other.distances);
+    }
+
+    /// Returns the histogram of the distances of the deaths caused by the weapon.
+    pub fn distances(&self) -> &DistanceHistogram {
+        &self.distances
+    }
+
+    /// Returns the percentage of the `total_deaths` caused by the weapon, rounded to 2 decimals.
+    pub fn deaths_percentage(&self, total_deaths: usize) -> f64 {
+        calculate_percentage(self.death_count, total_deaths)
+    }
+
+    /// Returns the average distance of the deaths caused by the weapon, rounded to 2 decimals.
+    pub fn rounded_average_distance(&self) -> f64 {
+        calculate_average(self.total_distance.value(), self.death_count_with_distance)
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.usize(self.death_count);
+        encoder.usize(self.death_count_with_distance);
+        self.total_distance.encode(encoder);
+        self.distances.encode(encoder);
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            death_count: decoder.usize()?,
+            death_count_with_distance: decoder.usize()?,
+            total_distance: CompensatedSum::decode(decoder)?,
+            distances: DistanceHistogram::decode(decoder)?,
+        })
     }
 
     /// Returns the stats of the weapon in a JSON format.
     pub fn json_display(&self, total_deaths: usize) -> serde_json::Value {
         serde_json::json!({
-            "deaths_percentage": calculate_percentage(self.death_count, total_deaths),
-            "average_distance": calculate_average(self.total_distance, self.death_count_with_distance),
+            "deaths_percentage": self.deaths_percentage(total_deaths),
+            "average_distance": self.rounded_average_distance(),
+        })
+    }
+}
+
+/// Aggregation of the stats of each weapon, keyed by the name of the weapon.
+/// It also counts every death, with or without weapon, to calculate the share of each weapon.
+#[derive(Clone, Default)]
+pub struct WeaponsAggregator {
+    total_deaths: usize,
+    weapons: HashMap<String, WeaponStats>,
+    /// Metric of the last filtering, used to order the weapons. Kills until they are filtered.
+    ranking: RankingMetric,
+}
+
+impl WeaponsAggregator {
+    /// Creates a new empty `WeaponsAggregator` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the total number of deaths folded, with or without weapon.
+    pub fn total_deaths(&self) -> usize {
+        self.total_deaths
+    }
+
+    /// Returns the stats of each weapon.
+    pub fn as_map(&self) -> &HashMap<String, WeaponStats> {
+        &self.weapons
+    }
+
+    /// Returns the weapons sorted by rank, from the highest to the lowest.
+    /// They are ranked by the metric of the last filtering, or by kills if they were not filtered.
+    /// Ties are resolved alphabetically.
+    pub fn ranked(&self) -> Vec<(&String, &WeaponStats)> {
+        ranked_elements(
+            &self.weapons,
+            weapon_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Writes the total of deaths and the stats of each weapon. The ranking is not kept.
+    pub(crate) fn encode(&self, encoder: &mut Encoder) {
+        encoder.usize(self.total_deaths);
+        encoder.map(&self.weapons, |encoder, weapon_stats| {
+            weapon_stats.encode(encoder)
+        });
+    }
+
+    pub(crate) fn decode(decoder: &mut Decoder) -> Result<Self, String> {
+        Ok(Self {
+            total_deaths: decoder.usize()?,
+            weapons: decoder.map(WeaponStats::decode)?,
+            ranking: RankingMetric::default(),
         })
     }
+
+    /// Returns the top `weapon_count` weapons sorted by rank, from the highest to the lowest, as in [`Self::ranked`].
+    pub fn top(&self, weapon_count: usize) -> Vec<(&String, &WeaponStats)> {
+        top_elements(
+            &self.weapons,
+            weapon_count,
+            weapon_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Returns the rank of the given weapon, starting at 1, as in [`Self::ranked`], or `None` if it caused no deaths.
+    pub fn rank_of(&self, name: &str) -> Option<usize> {
+        element_rank(
+            &self.weapons,
+            name,
+            weapon_comparator(self.ranking, self.total_deaths),
+        )
+    }
+
+    /// Ranks the weapons by the given metric without filtering them.
+    pub fn rank_by(&mut self, metric: RankingMetric) {
+        self.ranking = metric;
+    }
+
+    /// Filters the top `weapon_count` weapons, ranked by the given metric.
+    /// The filtering is done in parallel using the `pool` parameter.
+    pub fn filter_top(&mut self, weapon_count: usize, metric: RankingMetric, pool: &ThreadPool) {
+        self.rank_by(metric);
+        retain_top_elements(
+            &mut self.weapons,
+            weapon_count,
+            weapon_comparator(metric, self.total_deaths),
+            pool,
+        );
+    }
+}
+
+impl Aggregator for WeaponsAggregator {
+    /// Counts the death and adds it to the stats of its weapon, if any.
+    fn fold(&mut self, death: &Death) {
+        self.total_deaths += 1;
+
+        if let Some(killed_by) = &death.killed_by {
+            let distance = death.distance();
+
+            match self.weapons.get_mut(killed_by) {
+                Some(weapon_stats) => weapon_stats.add_death(distance),
+                None => {
+                    let mut weapon_stats = WeaponStats::new();
+                    weapon_stats.add_death(distance);
+                    self.weapons.insert(killed_by.clone(), weapon_stats);
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total_deaths += other.total_deaths;
+
+        for (name, other_weapon_stats) in other.weapons {
+            self.weapons
+                .entry(name)
+                .and_modify(|main_weapon_stats| {
+                    main_weapon_stats.merge(&other_weapon_stats);
+                })
+                .or_insert(other_weapon_stats);
+        }
+    }
+
+    /// Returns the weapons in order of rank, each with its rank.
+    fn render(&self) -> serde_json::Value {
+        let weapons = self
+            .ranked()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (weapon_name, weapon_stats))| {
+                (
+                    weapon_name.clone(),
+                    ranked_json(index + 1, weapon_stats.json_display(self.total_deaths)),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::Value::Object(weapons)
+    }
 }
 
 #[cfg(test)]
@@ -76,7 +263,7 @@ mod tests {
 
         assert_eq!(weapon_stats.death_count, 0);
         assert_eq!(weapon_stats.death_count_with_distance, 0);
-        assert_eq!(weapon_stats.total_distance, 0.0);
+        assert_eq!(weapon_stats.total_distance.value(), 0.0);
     }
 
     #[test]
@@ -86,7 +273,7 @@ mod tests {
         weapon_stats.add_death(Some(100.0));
         assert_eq!(weapon_stats.death_count, 1);
         assert_eq!(weapon_stats.death_count_with_distance, 1);
-        assert_eq!(weapon_stats.total_distance, 100.0);
+        assert_eq!(weapon_stats.total_distance.value(), 100.0);
     }
 
     #[test]
@@ -98,7 +285,7 @@ mod tests {
 
         assert_eq!(weapon_stats.death_count, 2);
         assert_eq!(weapon_stats.death_count_with_distance, 2);
-        assert_eq!(weapon_stats.total_distance, 300.0);
+        assert_eq!(weapon_stats.total_distance.value(), 300.0);
     }
 
     #[test]
@@ -109,7 +296,7 @@ mod tests {
 
         assert_eq!(weapon_stats.death_count, 1);
         assert_eq!(weapon_stats.death_count_with_distance, 0);
-        assert_eq!(weapon_stats.total_distance, 0.0);
+        assert_eq!(weapon_stats.total_distance.value(), 0.0);
     }
 
     #[test]
@@ -125,7 +312,7 @@ mod tests {
 
         assert_eq!(weapon_stats_1.death_count, 3);
         assert_eq!(weapon_stats_1.death_count_with_distance, 2);
-        assert_eq!(weapon_stats_1.total_distance, 300.0);
+        assert_eq!(weapon_stats_1.total_distance.value(), 300.0);
     }
 
     #[test]
@@ -0,0 +1,22 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tp_individual::IngestionReport;
+
+pub const HEADER: &str = "killed_by,killer_name,killer_placement,killer_position_x,killer_position_y,map,match_id,time,victim_name,victim_placement,victim_position_x,victim_position_y";
+pub const DEATH_RECORD_1: &str = "AK47,Player1,1.0,0.0,0.0,map,match-id,123,Player2,1.0,100.0,0.0";
+pub const DEATH_RECORD_2: &str = "M4A4,Player2,1.0,0.0,0.0,map,match-id,123,Player1,1.0,50.0,0.0";
+
+pub const INVALID_FIELDS_RECORD: &str = "AK47,Player1,1.0";
+pub const INVALID_NUMBER_RECORD: &str =
+    "AK47,Player1,1.0,abc,0.0,map,match-id,123,Player2,1.0,100.0,0.0";
+
+pub fn pool() -> ThreadPool {
+    ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+}
+
+pub fn report() -> Arc<IngestionReport> {
+    Arc::new(IngestionReport::new(None, None).unwrap())
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use rayon::prelude::*;
+use tempfile::NamedTempFile;
+use tp_individual::{read_csv_files, Death, IngestionReport};
+
+#[test]
+fn test_empty_no_csv_files() {
+    let csv_files = vec![];
+    let deaths = read_csv_files(csv_files, |_: String| Ok(()), report(), &pool());
+
+    assert_eq!(deaths.count(), 0);
+}
+
+#[test]
+fn test_empty_csv_files() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let csv_files = vec![temp_file.path().to_path_buf()];
+    let deaths = read_csv_files(csv_files, |_: String| Ok(()), report(), &pool());
+
+    assert_eq!(deaths.count(), 0);
+}
+
+#[test]
+fn test_single_csv_file() {
+    let temp_file_path = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path.clone(),
+        format!("{}\n{}", HEADER, DEATH_RECORD_1),
+    )
+    .unwrap();
+
+    let csv_files = vec![temp_file_path];
+    let deaths = read_csv_files(csv_files, |line: String| Ok(line), report(), &pool());
+
+    assert_eq!(deaths.count(), 1);
+}
+
+#[test]
+fn test_multiline_csv_file() {
+    let temp_file_path = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path.clone(),
+        format!("{}\n{}\n{}", HEADER, DEATH_RECORD_1, DEATH_RECORD_2),
+    )
+    .unwrap();
+
+    let csv_files = vec![temp_file_path];
+    let deaths = read_csv_files(csv_files, |line: String| Ok(line), report(), &pool());
+
+    assert_eq!(deaths.count(), 2);
+}
+
+#[test]
+fn test_multiple_csv_files() {
+    let temp_file_path_1 = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path_1.clone(),
+        format!("{}\n{}", HEADER, DEATH_RECORD_1),
+    )
+    .unwrap();
+
+    let temp_file_path_2 = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path_2.clone(),
+        format!("{}\n{}", HEADER, DEATH_RECORD_2),
+    )
+    .unwrap();
+
+    let csv_files = vec![temp_file_path_1, temp_file_path_2];
+
+    let deaths = read_csv_files(csv_files, |line: String| Ok(line), report(), &pool());
+
+    assert_eq!(deaths.count(), 2);
+}
+
+#[test]
+fn test_malformed_lines_are_reported() {
+    let temp_file_path = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path.clone(),
+        format!(
+            "{}\n{}\n{}\n{}",
+            HEADER, DEATH_RECORD_1, INVALID_FIELDS_RECORD, INVALID_NUMBER_RECORD
+        ),
+    )
+    .unwrap();
+
+    let report = report();
+    let csv_files = vec![temp_file_path];
+    let deaths = read_csv_files(csv_files, Death::from_csv_record, report.clone(), &pool());
+
+    assert_eq!(deaths.count(), 1);
+    assert_eq!(report.error_count(), 2);
+
+    let file_reports = report.file_reports();
+    assert_eq!(file_reports.len(), 1);
+    assert_eq!(file_reports[0].1.invalid_field_count, 1);
+    assert_eq!(file_reports[0].1.invalid_numbers, 1);
+}
+
+#[test]
+fn test_reading_stops_after_max_errors() {
+    let temp_file_path = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+
+    std::fs::write(
+        temp_file_path.clone(),
+        format!(
+            "{}\n{}\n{}\n{}",
+            HEADER, INVALID_FIELDS_RECORD, INVALID_FIELDS_RECORD, DEATH_RECORD_1
+        ),
+    )
+    .unwrap();
+
+    let report = Arc::new(IngestionReport::new(None, Some(1)).unwrap());
+    let csv_files = vec![temp_file_path];
+    let deaths = read_csv_files(csv_files, Death::from_csv_record, report.clone(), &pool());
+
+    assert_eq!(deaths.count(), 0);
+    assert!(report.is_aborted());
+}
+
+#[test]
+fn test_rejected_lines_are_written_with_line_number() {
+    let temp_file_path = tempfile::NamedTempFile::new().unwrap().path().to_path_buf();
+    let reject_file = NamedTempFile::new().unwrap();
+    let reject_path = reject_file.path().to_str().unwrap();
+
+    std::fs::write(
+        temp_file_path.clone(),
+        format!("{}\n{}\n{}", HEADER, DEATH_RECORD_1, INVALID_FIELDS_RECORD),
+    )
+    .unwrap();
+
+    let report = Arc::new(IngestionReport::new(Some(reject_path), None).unwrap());
+    let csv_files = vec![temp_file_path.clone()];
+    let deaths = read_csv_files(csv_files, Death::from_csv_record, report.clone(), &pool());
+
+    assert_eq!(deaths.count(), 1);
+    report.flush().unwrap();
+
+    let content = std::fs::read_to_string(reject_path).unwrap();
+    assert_eq!(
+        content,
+        format!(
+            "{}\t3\tInvalid number of fields: 3\t{}\n",
+            temp_file_path.display(),
+            INVALID_FIELDS_RECORD
+        )
+    );
+}
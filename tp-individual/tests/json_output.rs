@@ -0,0 +1,174 @@
+mod common;
+
+use assert_json_diff::assert_json_eq;
+use common::*;
+use rayon::prelude::*;
+use serde_json::json;
+use tempfile::NamedTempFile;
+use tp_individual::{save_as_json, AnalyzerError, Death, Stats, PADRON};
+
+#[test]
+fn test_save_as_json_unwritable_path() {
+    let stats = stats_from_deaths(vec![]);
+
+    let result = save_as_json(stats, "this/directory/does/not/exist/output.json");
+
+    assert!(matches!(result, Err(AnalyzerError::Io { .. })));
+}
+
+fn json_from_file(file_path: &str) -> serde_json::Value {
+    let reader = std::fs::File::open(file_path).unwrap();
+    serde_json::from_reader(reader).unwrap()
+}
+
+fn stats_from_deaths(deaths: Vec<String>) -> Stats {
+    Stats::from_deaths(
+        deaths
+            .into_par_iter()
+            .map(|record| Death::from_csv_record(record).unwrap()),
+        &pool(),
+    )
+}
+
+#[test]
+fn test_save_as_json_empty() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let output_path = temp_file.path().to_str().unwrap();
+
+    let deaths = vec![];
+    let stats = stats_from_deaths(deaths);
+
+    save_as_json(stats, output_path).unwrap();
+
+    let expected_json = json!({
+        "padron": PADRON,
+        "top_killers": {},
+        "top_weapons": {},
+    });
+
+    let output_json = json_from_file(output_path);
+
+    assert_json_eq!(expected_json, output_json);
+}
+
+#[test]
+fn test_save_as_json_single_death() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let output_path = temp_file.path().to_str().unwrap();
+
+    let deaths = vec![DEATH_RECORD_1.to_string()];
+    let stats = stats_from_deaths(deaths);
+
+    save_as_json(stats, output_path).unwrap();
+
+    let expected_json = json!({
+        "padron": PADRON,
+        "top_killers": {
+            "Player1": {
+                "rank": 1,
+                "deaths": 1,
+                "weapons_percentage": {
+                    "AK47": 100.0
+                }
+            }
+        },
+        "top_weapons": {
+            "AK47": {
+                "rank": 1,
+                "deaths_percentage": 100.0,
+                "average_distance": 100.0
+            }
+        },
+    });
+
+    let output_json = json_from_file(output_path);
+
+    assert_json_eq!(expected_json, output_json);
+}
+
+#[test]
+fn test_save_as_json_multiple_players_and_weapons() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let output_path = temp_file.path().to_str().unwrap();
+
+    let deaths = vec![DEATH_RECORD_1.to_string(), DEATH_RECORD_2.to_string()];
+    let stats = stats_from_deaths(deaths);
+
+    save_as_json(stats, output_path).unwrap();
+
+    let expected_json = json!({
+        "padron": PADRON,
+        "top_killers": {
+            "Player1": {
+                "rank": 1,
+                "deaths": 1,
+                "weapons_percentage": {
+                    "AK47": 100.0
+                }
+            },
+            "Player2": {
+                "rank": 2,
+                "deaths": 1,
+                "weapons_percentage": {
+                    "M4A4": 100.0
+                }
+            }
+        },
+        "top_weapons": {
+            "AK47": {
+                "rank": 1,
+                "deaths_percentage": 50.0,
+                "average_distance": 100.0
+            },
+            "M4A4": {
+                "rank": 2,
+                "deaths_percentage": 50.0,
+                "average_distance": 50.0
+            }
+        },
+    });
+
+    let output_json = json_from_file(output_path);
+
+    assert_json_eq!(expected_json, output_json);
+}
+
+#[test]
+fn test_save_as_json_is_identical_for_any_number_of_threads() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let deaths = (0..200)
+        .map(|i| {
+            format!(
+                "{},Player{},1.0,0.0,0.0,map,match-id,123,Player{},1.0,{}.0,0.0",
+                ["AK47", "M4A4", "AWM", "Punch"][i % 4],
+                i % 7,
+                i % 5,
+                i % 13
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let outputs = [1, 2, 4, 8]
+        .into_iter()
+        .map(|threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let deaths = deaths
+                .clone()
+                .into_par_iter()
+                .map(|record| Death::from_csv_record(record).unwrap());
+
+            let mut stats = Stats::from_deaths(deaths, &pool);
+            stats.filter_top_killers(5, 3, &pool);
+            stats.filter_top_weapons(3, &pool);
+
+            let output_path = dir.path().join(format!("output_{}.json", threads));
+            save_as_json(stats, output_path.to_str().unwrap()).unwrap();
+            std::fs::read(output_path).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert!(outputs.windows(2).all(|pair| pair[0] == pair[1]));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use assert_json_diff::assert_json_eq;
+use common::*;
+use rayon::prelude::*;
+use serde_json::json;
+use tempfile::TempDir;
+use tp_individual::{
+    find_input_files_in_dir, read_columnar_files, read_csv_files, split_by_format, Death, Filter,
+    Stats,
+};
+
+#[test]
+fn test_full_pipeline_from_directory() {
+    let dir = TempDir::new().unwrap();
+
+    std::fs::write(
+        dir.path().join("deaths_1.csv"),
+        format!("{}\n{}\n{}", HEADER, DEATH_RECORD_1, DEATH_RECORD_1),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("deaths_2.csv"),
+        format!("{}\n{}\n{}", HEADER, DEATH_RECORD_2, INVALID_FIELDS_RECORD),
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not a dataset").unwrap();
+
+    let pool = pool();
+    let report = report();
+
+    let files = find_input_files_in_dir(dir.path().to_str().unwrap()).unwrap();
+    let (csv_files, columnar_files) = split_by_format(files);
+
+    assert_eq!(csv_files.len(), 2);
+    assert!(columnar_files.is_empty());
+
+    let deaths = read_csv_files(csv_files, Death::from_csv_record, report.clone(), &pool).chain(
+        read_columnar_files(columnar_files, false, report.clone(), &pool),
+    );
+
+    let mut stats = Stats::from_deaths(deaths, &pool);
+    stats.filter_top_killers(1, 1, &pool);
+    stats.filter_top_weapons(1, &pool);
+
+    assert_eq!(report.error_count(), 1);
+    assert_json_eq!(
+        stats.json_display(),
+        json!({
+            "top_killers": {
+                "Player1": {
+                    "rank": 1,
+                    "deaths": 2,
+                    "weapons_percentage": {
+                        "AK47": 100.0
+                    }
+                }
+            },
+            "top_weapons": {
+                "AK47": {
+                    "rank": 1,
+                    "deaths_percentage": 66.67,
+                    "average_distance": 100.0
+                }
+            }
+        })
+    );
+}
+
+#[test]
+fn test_pipeline_with_filter() {
+    let dir = TempDir::new().unwrap();
+
+    std::fs::write(
+        dir.path().join("deaths.csv"),
+        format!("{}\n{}\n{}", HEADER, DEATH_RECORD_1, DEATH_RECORD_2),
+    )
+    .unwrap();
+
+    let pool = pool();
+    let filter = Filter::parse("killed_by not in ('AK47') and time > 100").unwrap();
+
+    let (csv_files, _) =
+        split_by_format(find_input_files_in_dir(dir.path().to_str().unwrap()).unwrap());
+    let deaths = read_csv_files(csv_files, Death::from_csv_record, report(), &pool)
+        .filter(|death| filter.matches(death));
+
+    let stats = Stats::from_deaths(deaths, &pool);
+
+    assert_eq!(stats.total_deaths(), 1);
+    assert_eq!(
+        stats.weapons().as_map().keys().collect::<Vec<_>>(),
+        vec!["M4A4"]
+    );
+}